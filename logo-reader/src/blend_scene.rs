@@ -0,0 +1,229 @@
+use blend::{Blend, Instance};
+
+/// A polygon as indices into `Mesh::positions`, with one UV per corner when
+/// the mesh has a UV layer.
+pub struct Polygon {
+    pub vertices: Vec<usize>,
+    pub uvs: Option<Vec<[f64; 2]>>,
+    pub material: usize,
+}
+
+/// A mesh object with its transform already applied, in the renderer's
+/// Y-up coordinates.
+pub struct Mesh {
+    pub name: String,
+    pub positions: Vec<[f64; 3]>,
+    pub polygons: Vec<Polygon>,
+    /// Names of the mesh's material slots, indexed by `Polygon::material`.
+    pub materials: Vec<Option<String>>,
+}
+
+pub struct Material {
+    pub name: String,
+    pub color: [f64; 3],
+    pub metallic: f64,
+    pub roughness: f64,
+}
+
+pub struct Camera {
+    pub lookfrom: [f64; 3],
+    pub lookat: [f64; 3],
+    pub vup: [f64; 3],
+    /// Vertical field of view in degrees.
+    pub vfov: f64,
+}
+
+pub struct BlendScene {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub camera: Option<Camera>,
+    pub image_width: i32,
+    pub aspect_ratio: f64,
+}
+
+impl BlendScene {
+    pub fn read(blend: &Blend) -> BlendScene {
+        let (image_width, aspect_ratio) = match blend.get_by_code(*b"SC").into_iter().next() {
+            Some(scene) => {
+                let r = scene.get("r");
+                let scale = r.get_i16("size") as f64 / 100.0;
+                let width = r.get_i32("xsch") as f64 * scale;
+                let height = r.get_i32("ysch") as f64 * scale;
+                (width as i32, width / height)
+            }
+            None => (600, 16.0 / 9.0),
+        };
+
+        let mut meshes = Vec::new();
+        let mut camera = None;
+        for obj in blend.get_by_code(*b"OB") {
+            if !obj.is_valid("data") {
+                continue;
+            }
+            let data = obj.get("data");
+            let transform = Transform::of(&obj);
+            if data.code()[0..2] == *b"ME" {
+                meshes.push(read_mesh(id_name(&obj), &data, &transform));
+            } else if data.code()[0..2] == *b"CA" && camera.is_none() {
+                camera = Some(read_camera(&data, &transform, aspect_ratio));
+            }
+        }
+
+        let materials = blend
+            .get_by_code(*b"MA")
+            .into_iter()
+            .map(|mat| Material {
+                name: id_name(&mat),
+                color: [mat.get_f32("r") as f64, mat.get_f32("g") as f64, mat.get_f32("b") as f64],
+                // Files from before Blender 2.8 have neither field.
+                metallic: f32_or(&mat, "metallic", 0.0),
+                roughness: f32_or(&mat, "roughness", 0.5),
+            })
+            .collect();
+
+        BlendScene {
+            meshes,
+            materials,
+            camera,
+            image_width,
+            aspect_ratio,
+        }
+    }
+}
+
+/// The datablock name without Blender's two-letter type prefix.
+fn id_name(instance: &Instance) -> String {
+    let name = instance.get("id").get_string("name");
+    name.get(2..).unwrap_or("").to_string()
+}
+
+fn f32_or(instance: &Instance, field: &str, default: f64) -> f64 {
+    if instance.is_valid(field) {
+        instance.get_f32(field) as f64
+    } else {
+        default
+    }
+}
+
+fn read_mesh(name: String, data: &Instance, transform: &Transform) -> Mesh {
+    let positions = data
+        .get_iter("mvert")
+        .map(|vert| transform.point(to_f64(&vert.get_f32_vec("co"))))
+        .collect();
+    let loops: Vec<usize> = data.get_iter("mloop").map(|l| l.get_i32("v") as usize).collect();
+    let uvs: Option<Vec<[f64; 2]>> = if data.is_valid("mloopuv") {
+        Some(
+            data.get_iter("mloopuv")
+                .map(|uv| {
+                    let uv = uv.get_f32_vec("uv");
+                    [uv[0] as f64, uv[1] as f64]
+                })
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    let polygons = data
+        .get_iter("mpoly")
+        .map(|poly| {
+            let start = poly.get_i32("loopstart") as usize;
+            let end = start + poly.get_i32("totloop") as usize;
+            Polygon {
+                vertices: loops[start..end].to_vec(),
+                uvs: uvs.as_ref().map(|uvs| uvs[start..end].to_vec()),
+                material: poly.get_i16("mat_nr").max(0) as usize,
+            }
+        })
+        .collect();
+
+    let materials = if data.is_valid("mat") {
+        data.get_iter("mat").map(|mat| Some(id_name(&mat))).collect()
+    } else {
+        Vec::new()
+    };
+
+    Mesh {
+        name,
+        positions,
+        polygons,
+        materials,
+    }
+}
+
+/// Blender cameras look down their local -Z axis with +Y up.
+fn read_camera(data: &Instance, transform: &Transform, aspect_ratio: f64) -> Camera {
+    let lookfrom = transform.point([0.0, 0.0, 0.0]);
+    let forward = transform.vector([0.0, 0.0, -1.0]);
+    let vup = transform.vector([0.0, 1.0, 0.0]);
+
+    let lens = data.get_f32("lens") as f64;
+    let sensor = data.get_f32("sensor_x") as f64;
+    // With the default automatic sensor fit the sensor width spans the longer
+    // side of the image.
+    let half_height = if aspect_ratio >= 1.0 {
+        sensor / 2.0 / aspect_ratio
+    } else {
+        sensor / 2.0
+    };
+    let vfov = 2.0 * (half_height / lens).atan().to_degrees();
+
+    Camera {
+        lookfrom,
+        lookat: [lookfrom[0] + forward[0], lookfrom[1] + forward[1], lookfrom[2] + forward[2]],
+        vup,
+        vfov,
+    }
+}
+
+/// An object's scale, XYZ Euler rotation and location. Parenting is not
+/// followed, so child objects are placed relative to the world origin.
+struct Transform {
+    loc: [f64; 3],
+    rot: [[f64; 3]; 3],
+    size: [f64; 3],
+}
+
+impl Transform {
+    fn of(obj: &Instance) -> Transform {
+        let loc = to_f64(&obj.get_f32_vec("loc"));
+        let [x, y, z] = to_f64(&obj.get_f32_vec("rot"));
+        let size = to_f64(&obj.get_f32_vec("size"));
+
+        let (sx, cx) = x.sin_cos();
+        let (sy, cy) = y.sin_cos();
+        let (sz, cz) = z.sin_cos();
+        // Rz * Ry * Rx, which applies X first as Blender's XYZ mode does.
+        let rot = [
+            [cz * cy, cz * sy * sx - sz * cx, cz * sy * cx + sz * sx],
+            [sz * cy, sz * sy * sx + cz * cx, sz * sy * cx - cz * sx],
+            [-sy, cy * sx, cy * cx],
+        ];
+        Transform { loc, rot, size }
+    }
+
+    fn vector(&self, v: [f64; 3]) -> [f64; 3] {
+        let r = &self.rot;
+        y_up([
+            r[0][0] * v[0] + r[0][1] * v[1] + r[0][2] * v[2],
+            r[1][0] * v[0] + r[1][1] * v[1] + r[1][2] * v[2],
+            r[2][0] * v[0] + r[2][1] * v[1] + r[2][2] * v[2],
+        ])
+    }
+
+    fn point(&self, p: [f64; 3]) -> [f64; 3] {
+        let scaled = [p[0] * self.size[0], p[1] * self.size[1], p[2] * self.size[2]];
+        let v = self.vector(scaled);
+        let loc = y_up(self.loc);
+        [v[0] + loc[0], v[1] + loc[1], v[2] + loc[2]]
+    }
+}
+
+/// Blender is Z-up; the renderer is Y-up.
+fn y_up(v: [f64; 3]) -> [f64; 3] {
+    [v[0], v[2], -v[1]]
+}
+
+fn to_f64(v: &[f32]) -> [f64; 3] {
+    [v[0] as f64, v[1] as f64, v[2] as f64]
+}
@@ -0,0 +1,338 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::blend_scene::{BlendScene, Mesh};
+
+const DEFAULT_MATERIAL: &str = "default";
+
+/// Writes `<stem>.obj`, `<stem>.mtl` and a `<stem>.toml` scene that loads the
+/// model into `out_dir`.
+/// Fails without writing anything if the scene has no camera and no model to
+/// frame.
+pub fn write_scene(scene: &BlendScene, out_dir: &Path, stem: &str) -> io::Result<()> {
+    let toml = toml(scene, stem)?;
+    fs::create_dir_all(out_dir)?;
+    fs::write(out_dir.join(format!("{}.obj", stem)), obj(scene, stem))?;
+    fs::write(out_dir.join(format!("{}.mtl", stem)), mtl(scene))?;
+    fs::write(out_dir.join(format!("{}.toml", stem)), toml)?;
+    Ok(())
+}
+
+fn obj(scene: &BlendScene, stem: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "mtllib {}.mtl", stem).unwrap();
+    // OBJ indices are global and one-based.
+    let mut vertex_base = 1;
+    let mut uv_base = 1;
+    for mesh in &scene.meshes {
+        writeln!(out, "o {}", mesh.name).unwrap();
+        for p in &mesh.positions {
+            writeln!(out, "v {} {} {}", p[0], p[1], p[2]).unwrap();
+        }
+        let mut uv_index = Vec::with_capacity(mesh.polygons.len());
+        for polygon in &mesh.polygons {
+            uv_index.push(uv_base);
+            for uv in polygon.uvs.iter().flatten() {
+                writeln!(out, "vt {} {}", uv[0], uv[1]).unwrap();
+                uv_base += 1;
+            }
+        }
+
+        // Out-of-range slots fall back to the last one, like Blender does.
+        let slots = mesh.materials.len().max(1);
+        for slot in 0..slots {
+            let polygons: Vec<usize> = (0..mesh.polygons.len())
+                .filter(|&i| mesh.polygons[i].material.min(slots - 1) == slot)
+                .collect();
+            if polygons.is_empty() {
+                continue;
+            }
+            writeln!(out, "usemtl {}", material_name(mesh, slot)).unwrap();
+            for i in polygons {
+                let polygon = &mesh.polygons[i];
+                out.push('f');
+                for (corner, &v) in polygon.vertices.iter().enumerate() {
+                    match polygon.uvs {
+                        Some(_) => write!(out, " {}/{}", vertex_base + v, uv_index[i] + corner).unwrap(),
+                        None => write!(out, " {}", vertex_base + v).unwrap(),
+                    }
+                }
+                out.push('\n');
+            }
+        }
+        vertex_base += mesh.positions.len();
+    }
+    out
+}
+
+fn material_name(mesh: &Mesh, slot: usize) -> String {
+    match mesh.materials.get(slot) {
+        Some(Some(name)) => sanitize(name),
+        _ => DEFAULT_MATERIAL.to_string(),
+    }
+}
+
+/// OBJ and MTL names end at whitespace.
+fn sanitize(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Metallic materials become mirrors the renderer loads as `Metal`, the rest
+/// plain diffuse colours.
+fn mtl(scene: &BlendScene) -> String {
+    let mut out = String::new();
+    writeln!(out, "newmtl {}\nKd 0.8 0.8 0.8\n", DEFAULT_MATERIAL).unwrap();
+    for material in &scene.materials {
+        let [r, g, b] = material.color;
+        writeln!(out, "newmtl {}", sanitize(&material.name)).unwrap();
+        if material.metallic > 0.5 {
+            writeln!(out, "Kd 0 0 0\nKs {} {} {}", r, g, b).unwrap();
+            writeln!(out, "Ns {}\nillum 3", (1.0 - material.roughness) * 1000.0).unwrap();
+        } else {
+            writeln!(out, "Kd {} {} {}\nillum 1", r, g, b).unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn toml(scene: &BlendScene, stem: &str) -> io::Result<String> {
+    let mut out = String::new();
+    writeln!(out, "background = [0.7, 0.8, 1.0]\n").unwrap();
+    writeln!(out, "[render]").unwrap();
+    writeln!(out, "image_width = {}", scene.image_width).unwrap();
+    writeln!(out, "aspect_ratio = {:?}\n", scene.aspect_ratio).unwrap();
+
+    writeln!(out, "[camera]").unwrap();
+    match &scene.camera {
+        Some(camera) => {
+            writeln!(out, "lookfrom = {}", array(camera.lookfrom)).unwrap();
+            writeln!(out, "lookat = {}", array(camera.lookat)).unwrap();
+            writeln!(out, "vup = {}", array(camera.vup)).unwrap();
+            writeln!(out, "vfov = {:?}\n", camera.vfov).unwrap();
+        }
+        None => {
+            // Frame the whole model from the front.
+            let (min, max) = bounds(scene).ok_or_else(nothing_to_frame)?;
+            let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, (min[2] + max[2]) / 2.0];
+            let radius = (0..3).map(|i| max[i] - min[i]).fold(0.0, f64::max);
+            if !(radius > 0.0 && radius.is_finite()) {
+                return Err(nothing_to_frame());
+            }
+            let lookfrom = [center[0], center[1], center[2] + 2.5 * radius];
+            writeln!(out, "lookfrom = {}", array(lookfrom)).unwrap();
+            writeln!(out, "lookat = {}\n", array(center)).unwrap();
+        }
+    }
+
+    writeln!(out, "[[objects]]\ntype = \"obj\"\npath = {}", string(&format!("{}.obj", stem))).unwrap();
+    Ok(out)
+}
+
+fn nothing_to_frame() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "the scene has no camera and no model to frame")
+}
+
+/// The corners of the box around every vertex, or `None` without vertices.
+fn bounds(scene: &BlendScene) -> Option<([f64; 3], [f64; 3])> {
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for p in scene.meshes.iter().flat_map(|mesh| mesh.positions.iter()) {
+        for i in 0..3 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    }
+    if min[0] > max[0] {
+        return None;
+    }
+    Some((min, max))
+}
+
+fn array(v: [f64; 3]) -> String {
+    format!("[{:?}, {:?}, {:?}]", v[0], v[1], v[2])
+}
+
+/// A TOML basic string holding `s`.
+fn string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => write!(out, "\\u{:04X}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blend_scene::{Camera, Material, Polygon};
+
+    fn polygon(vertices: &[usize], uvs: Option<&[[f64; 2]]>, material: usize) -> Polygon {
+        Polygon {
+            vertices: vertices.to_vec(),
+            uvs: uvs.map(|uvs| uvs.to_vec()),
+            material,
+        }
+    }
+
+    fn material(name: &str, metallic: f64) -> Material {
+        Material {
+            name: name.to_string(),
+            color: [0.25, 0.5, 1.0],
+            metallic,
+            roughness: 0.25,
+        }
+    }
+
+    /// A textured quad with two material slots and an untextured triangle
+    /// without any.
+    fn scene() -> BlendScene {
+        let quad = Mesh {
+            name: "Quad".to_string(),
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+            polygons: vec![
+                polygon(&[0, 1, 2], Some(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]]), 1),
+                polygon(&[0, 2, 3], Some(&[[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]]), 0),
+            ],
+            materials: vec![Some("Rust Orange".to_string()), Some("Steel".to_string())],
+        };
+        let triangle = Mesh {
+            name: "Triangle".to_string(),
+            positions: vec![[0.0, 0.0, -1.0], [2.0, 0.0, -1.0], [0.0, 2.0, -1.0]],
+            // Slot 5 is past the end and falls back to the last one.
+            polygons: vec![polygon(&[0, 1, 2], None, 5)],
+            materials: Vec::new(),
+        };
+        BlendScene {
+            meshes: vec![quad, triangle],
+            materials: vec![material("Rust Orange", 0.0), material("Steel", 1.0)],
+            camera: None,
+            image_width: 200,
+            aspect_ratio: 2.0,
+        }
+    }
+
+    #[test]
+    fn obj_indices_run_on_across_meshes() {
+        let expected = "\
+mtllib logo.mtl
+o Quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 0
+vt 1 1
+vt 0 1
+usemtl Rust_Orange
+f 1/4 3/5 4/6
+usemtl Steel
+f 1/1 2/2 3/3
+o Triangle
+v 0 0 -1
+v 2 0 -1
+v 0 2 -1
+usemtl default
+f 5 6 7
+";
+        assert_eq!(obj(&scene(), "logo"), expected);
+    }
+
+    #[test]
+    fn uv_indices_skip_untextured_polygons() {
+        let mut scene = scene();
+        scene.meshes.swap(0, 1);
+        scene.meshes[1].polygons.insert(0, polygon(&[1, 2, 3], None, 0));
+        let obj = obj(&scene, "logo");
+        let faces: Vec<&str> = obj.lines().filter(|line| line.starts_with("f ")).collect();
+        assert_eq!(faces, ["f 1 2 3", "f 5 6 7", "f 4/4 6/5 7/6", "f 4/1 5/2 6/3"]);
+    }
+
+    #[test]
+    fn mtl_has_every_material_and_a_default() {
+        let expected = "\
+newmtl default
+Kd 0.8 0.8 0.8
+
+newmtl Rust_Orange
+Kd 0.25 0.5 1
+illum 1
+
+newmtl Steel
+Kd 0 0 0
+Ks 0.25 0.5 1
+Ns 750
+illum 3
+
+";
+        assert_eq!(mtl(&scene()), expected);
+    }
+
+    #[test]
+    fn toml_frames_the_model_without_a_camera() {
+        let expected = "\
+background = [0.7, 0.8, 1.0]
+
+[render]
+image_width = 200
+aspect_ratio = 2.0
+
+[camera]
+lookfrom = [1.0, 1.0, 4.5]
+lookat = [1.0, 1.0, -0.5]
+
+[[objects]]
+type = \"obj\"
+path = \"logo.obj\"
+";
+        assert_eq!(toml(&scene(), "logo").unwrap(), expected);
+    }
+
+    #[test]
+    fn toml_keeps_the_camera_and_quotes_the_path() {
+        let mut scene = scene();
+        scene.camera = Some(Camera {
+            lookfrom: [0.0, -5.0, 1.0],
+            lookat: [0.0, 0.0, 0.5],
+            vup: [0.0, 0.0, 1.0],
+            vfov: 40.0,
+        });
+        let toml = toml(&scene, "my \"logo\"\\v2").unwrap();
+        assert!(toml.contains("[camera]\nlookfrom = [0.0, -5.0, 1.0]\nlookat = [0.0, 0.0, 0.5]\nvup = [0.0, 0.0, 1.0]\nvfov = 40.0\n"));
+        assert!(toml.ends_with("path = \"my \\\"logo\\\"\\\\v2.obj\"\n"), "{}", toml);
+    }
+
+    #[test]
+    fn nothing_to_frame_is_an_error() {
+        let mut scene = scene();
+        scene.meshes.clear();
+        assert_eq!(toml(&scene, "logo").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        // A single point is no better.
+        scene.meshes.push(Mesh {
+            name: "Point".to_string(),
+            positions: vec![[1.0, 2.0, 3.0]],
+            polygons: Vec::new(),
+            materials: Vec::new(),
+        });
+        assert!(toml(&scene, "logo").is_err());
+
+        let dir = std::env::temp_dir().join(format!("logo-reader-{}", std::process::id()));
+        assert!(write_scene(&scene, &dir, "logo").is_err());
+        assert!(!dir.exists());
+    }
+}
@@ -1,13 +1,45 @@
+mod blend_scene;
+mod export;
+
+use std::env;
+use std::path::Path;
+use std::process;
+
 use blend::Blend;
 
-/// Prints the name and position of every object
+use crate::blend_scene::BlendScene;
+
+/// Converts a .blend file into an OBJ/MTL model and a TOML scene for the
+/// `weekend` renderer.
+///
+/// Usage: logo-reader [input.blend] [output directory]
 fn main() {
-    let blend = Blend::from_path("rust_logo.blend");
+    let args: Vec<String> = env::args().collect();
+    let input = args.get(1).map_or("rust_logo.blend", |s| s.as_str());
+    let out_dir = Path::new(args.get(2).map_or(".", |s| s.as_str()));
+    let stem = Path::new(input)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("scene");
 
-    for obj in blend.get_by_code(*b"OB") {
-        let loc = obj.get_f32_vec("loc");
-        let name = obj.get("id").get_string("name");
+    let blend = Blend::from_path(input);
+    let scene = BlendScene::read(&blend);
+
+    for mesh in &scene.meshes {
+        println!(
+            "\"{}\": {} vertices, {} polygons",
+            mesh.name,
+            mesh.positions.len(),
+            mesh.polygons.len()
+        );
+    }
+    if scene.camera.is_none() {
+        println!("no camera found, framing the model from the front");
+    }
 
-        println!("\"{}\" at {:?}", name, loc);
+    if let Err(e) = export::write_scene(&scene, out_dir, stem) {
+        eprintln!("failed to write {}: {}", out_dir.display(), e);
+        process::exit(1);
     }
+    println!("Wrote {}", out_dir.join(format!("{}.toml", stem)).display());
 }
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::box_model::BoxModel;
+use weekend::builtin_scenes;
+use weekend::bvh::Bvh;
+use weekend::bvh_node::BvhNode;
+use weekend::hittable::Hittable;
+use weekend::hittable_list::HittableList;
+use weekend::material::Lambertian;
+use weekend::ray::Ray;
+use weekend::rotate::RotateY;
+use weekend::sampler::Sampler;
+use weekend::sphere::Sphere;
+use weekend::texture::SolidColor;
+use weekend::translate::Translate;
+use weekend::vec3::Vec3;
+
+/// The two object groups `final_scene` puts in BVHs: the ground boxes and
+/// the cluster of spheres.
+fn final_scene_groups(rng: &mut StdRng) -> (HittableList, HittableList) {
+    let ground = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Vec3::new(0.48, 0.83, 0.53)))));
+    let mut boxes = HittableList::new();
+    for i in 0..20 {
+        for j in 0..20 {
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let z0 = -1000.0 + j as f64 * w;
+            let y1 = rng.next_range(1.0..101.0);
+            boxes.add(Arc::new(BoxModel::new(Vec3::new(x0, 0.0, z0), Vec3::new(x0 + w, y1, z0 + w), ground.clone())));
+        }
+    }
+
+    let white = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Vec3::new(0.73, 0.73, 0.73)))));
+    let mut spheres = HittableList::new();
+    for _ in 0..1000 {
+        spheres.add(Arc::new(Sphere::new(Vec3::random_range(rng, 0.0..165.0), 10.0, white.clone())));
+    }
+    (boxes, spheres)
+}
+
+fn world(boxes: Arc<dyn Hittable>, spheres: Arc<dyn Hittable>) -> HittableList {
+    let mut world = HittableList::new();
+    world.add(boxes);
+    world.add(Arc::new(Translate::new(Arc::new(RotateY::new(spheres, 15.0)), Vec3::new(-100.0, 270.0, 395.0))));
+    world
+}
+
+/// Camera rays through random points of the `final_scene` image.
+fn camera_rays(rng: &mut StdRng, count: usize) -> Vec<Ray> {
+    let scene = builtin_scenes::final_scene(rng);
+    let camera = scene.camera();
+    (0..count)
+        .map(|_| {
+            let (s, t) = (rng.next_f64(), rng.next_f64());
+            camera.get_ray(rng, s, t)
+        })
+        .collect()
+}
+
+fn build(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let (boxes, spheres) = final_scene_groups(&mut rng);
+    let mut group = c.benchmark_group("build");
+    for (name, list) in [("boxes", &boxes), ("spheres", &spheres)] {
+        let len = list.objects.len();
+        group.bench_function(format!("bvh_node/{}", name), |b| {
+            b.iter_batched(
+                || (list.clone(), StdRng::seed_from_u64(1)),
+                |(list, mut rng)| BvhNode::new(&mut rng, list, 0, len, 0.0, 1.0),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(format!("sah_serial/{}", name), |b| {
+            b.iter_batched(|| list.clone(), |list| Bvh::new_serial(list, 0.0, 1.0), BatchSize::SmallInput)
+        });
+        group.bench_function(format!("sah/{}", name), |b| {
+            b.iter_batched(|| list.clone(), |list| Bvh::new(list, 0.0, 1.0), BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+fn hit(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let (boxes, spheres) = final_scene_groups(&mut rng);
+    let rays = camera_rays(&mut rng, 10_000);
+    let (boxes_len, spheres_len) = (boxes.objects.len(), spheres.objects.len());
+    let bvh_node = world(
+        Arc::new(BvhNode::new(&mut rng, boxes.clone(), 0, boxes_len, 0.0, 1.0)),
+        Arc::new(BvhNode::new(&mut rng, spheres.clone(), 0, spheres_len, 0.0, 1.0)),
+    );
+    let sah = world(Arc::new(Bvh::new(boxes, 0.0, 1.0)), Arc::new(Bvh::new(spheres, 0.0, 1.0)));
+
+    let mut group = c.benchmark_group("hit");
+    for (name, world) in [("bvh_node", &bvh_node), ("sah", &sah)] {
+        group.bench_function(name, |b| {
+            b.iter(|| rays.iter().filter(|r| world.hit(&mut rng, r, 0.001, f64::INFINITY).is_some()).count())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, build, hit);
+criterion_main!(benches);
@@ -41,6 +41,11 @@ impl Aabb {
         }
         true
     }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
 }
 
 pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::transform::AnimatedTransform;
+use crate::sampler::Sampler;
+
+/// An object moved by a keyframed transform, which is evaluated at each ray's
+/// time. Works for any object, so boxes, rectangles and meshes get motion
+/// blur the same way `MovingSphere` does.
+#[derive(Clone)]
+pub struct Animated {
+    object: Arc<dyn Hittable>,
+    motion: AnimatedTransform,
+}
+
+impl Animated {
+    pub fn new(object: Arc<dyn Hittable>, motion: AnimatedTransform) -> Animated {
+        Animated { object, motion }
+    }
+}
+
+impl Hittable for Animated {
+    fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let transform = self.motion.at(r.time);
+        let inverse = transform.inverse();
+        let local = Ray::new(inverse.point(&r.origin), inverse.vector(&r.direction), r.time);
+        let mut rec = self.object.hit(sampler, &local, t_min, t_max)?;
+        rec.p = transform.point(&rec.p);
+        rec.normal = transform.normal(&rec.normal).unit_vector();
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
+        let bbox = self.object.bounding_box(t0, t1)?;
+        Some(self.motion.bounding_box(&bbox, t0, t1))
+    }
+}
@@ -0,0 +1,113 @@
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::{GenericImageView, ImageResult};
+
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+
+/// The shape of the lens opening, which out-of-focus highlights take on.
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    /// A regular polygon with a corner `rotation` degrees counterclockwise
+    /// from straight up, as formed by `blades` diaphragm blades; at least 3.
+    Polygon { blades: u32, rotation: f64 },
+    Image(Arc<ApertureImage>),
+}
+
+impl Aperture {
+    /// A point on the opening with `z = 0`, inside the unit disk, or for a
+    /// mask the square around it.
+    pub fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        match self {
+            Aperture::Circle => Vec3::random_in_unit_disk(sampler),
+            Aperture::Polygon { blades, rotation } => {
+                // Pick one of the equal triangles fanning out from the centre,
+                // then a uniform point in it.
+                let sector = 2.0 * PI / *blades as f64;
+                let k = ((sampler.next_f64() * *blades as f64) as u32).min(blades - 1);
+                let a0 = rotation.to_radians() + PI / 2.0 + k as f64 * sector;
+                let (c0, c1) = (unit(a0), unit(a0 + sector));
+                let (mut u, mut v) = (sampler.next_f64(), sampler.next_f64());
+                if u + v > 1.0 {
+                    (u, v) = (1.0 - u, 1.0 - v);
+                }
+                c0 * u + c1 * v
+            }
+            Aperture::Image(image) => image.sample(sampler),
+        }
+    }
+}
+
+fn unit(angle: f64) -> Vec3 {
+    Vec3::new(angle.cos(), angle.sin(), 0.0)
+}
+
+/// A greyscale mask of the opening: points are picked with a density
+/// proportional to the brightness of the mask. The mask fills the square
+/// around the unit disk, with its top row at the top.
+pub struct ApertureImage {
+    width: usize,
+    height: usize,
+    /// Cumulative brightness along each row, normalised to end at 1.
+    rows: Vec<f64>,
+    /// Cumulative brightness of the rows, normalised to end at 1.
+    columns: Vec<f64>,
+}
+
+impl ApertureImage {
+    /// `weights` holds `width * height` brightnesses by rows from the top;
+    /// `None` if none of them is positive.
+    pub fn new(width: usize, height: usize, weights: &[f64]) -> Option<ApertureImage> {
+        assert_eq!(weights.len(), width * height);
+        let mut rows = Vec::with_capacity(weights.len());
+        let mut columns = Vec::with_capacity(height);
+        let mut total = 0.0;
+        for row in weights.chunks(width) {
+            let mut sum = 0.0;
+            for &w in row {
+                sum += w.max(0.0);
+                rows.push(sum);
+            }
+            if sum > 0.0 {
+                let start = rows.len() - width;
+                rows[start..].iter_mut().for_each(|c| *c /= sum);
+            }
+            total += sum;
+            columns.push(total);
+        }
+        if total <= 0.0 {
+            return None;
+        }
+        columns.iter_mut().for_each(|c| *c /= total);
+        Some(ApertureImage { width, height, rows, columns })
+    }
+
+    /// Loads a mask from an image file, by its luminance.
+    pub fn open<P: AsRef<Path>>(path: P) -> ImageResult<Option<ApertureImage>> {
+        let img = image::open(path)?;
+        let (width, height) = img.dimensions();
+        let weights: Vec<f64> = img
+            .into_luma8()
+            .pixels()
+            .map(|p| p[0] as f64 / 255.0)
+            .collect();
+        Ok(ApertureImage::new(width as usize, height as usize, &weights))
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let y = pick(&self.columns, sampler.next_f64());
+        let row = &self.rows[y * self.width..(y + 1) * self.width];
+        let x = pick(row, sampler.next_f64());
+        let px = (x as f64 + sampler.next_f64()) / self.width as f64;
+        let py = (y as f64 + sampler.next_f64()) / self.height as f64;
+        Vec3::new(2.0 * px - 1.0, 1.0 - 2.0 * py, 0.0)
+    }
+}
+
+/// The first entry of a cumulative distribution above `u`.
+fn pick(cdf: &[f64], u: f64) -> usize {
+    cdf.partition_point(|&c| c <= u).min(cdf.len() - 1)
+}
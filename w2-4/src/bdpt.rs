@@ -0,0 +1,506 @@
+use std::f64::consts::PI;
+
+use crate::camera::Camera;
+use crate::framebuffer::Film;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::ScatterRecord;
+use crate::onb::Onb;
+use crate::pdf::Pdf;
+use crate::ray::Ray;
+use crate::render::Integrator;
+use crate::sampler::Sampler;
+use crate::vec3::{Color, Point3, Vec3};
+
+/// Bidirectional path tracing: every camera sample also starts a path from a
+/// point on one of `lights`, and each prefix of the camera path is joined to
+/// each prefix of the light path by a shadow ray. The paths joined straight
+/// to the lens land elsewhere on the image and are splatted. Each way of
+/// building a path is weighted against the others that could have built it
+/// with the power heuristic, so the result matches `PathIntegrator` in
+/// expectation, with caustics and lights seen through small gaps found far
+/// more often.
+///
+/// Only lights that can pick points on their surface (spheres and
+/// rectangles) start light paths; light from others is only found by the
+/// camera path hitting it. Specular and glossy bounces are followed but never
+/// joined to, and light tracing only reaches cameras that can say where
+/// they see a point, which the perspective one does.
+pub struct BdptIntegrator {
+    pub background: Color,
+    /// Ray segments per path, counted as `RecursiveIntegrator` counts them.
+    pub max_depth: i32,
+    pub lights: HittableList,
+}
+
+impl BdptIntegrator {
+    pub fn new(background: Color, max_depth: i32) -> BdptIntegrator {
+        BdptIntegrator {
+            background,
+            max_depth,
+            lights: HittableList::new(),
+        }
+    }
+
+    pub fn with_lights(mut self, lights: HittableList) -> BdptIntegrator {
+        self.lights = lights;
+        self
+    }
+
+    fn emitters(&self) -> impl Iterator<Item = &dyn Hittable> {
+        self.lights.objects.iter().map(|light| &**light).filter(|light| light.area() > 0.0)
+    }
+
+    /// A point picked on the lights, with the area density of picking it.
+    fn sample_light(&self, sampler: &mut dyn Sampler) -> Option<(HitRecord<'_>, f64)> {
+        let count = self.emitters().count();
+        if count == 0 {
+            return None;
+        }
+        let light = self.emitters().nth(sampler.next_index(count))?;
+        let rec = light.sample_surface(sampler)?;
+        Some((rec, 1.0 / (count as f64 * light.area())))
+    }
+
+    /// The area density of `sample_light` picking the point `r` hits at `t`.
+    fn light_pdf(&self, sampler: &mut dyn Sampler, r: &Ray, t: f64) -> f64 {
+        let count = self.emitters().count();
+        self.emitters()
+            .filter(|light| light.hit(sampler, r, 0.001, f64::INFINITY).is_some_and(|rec| (rec.t - t).abs() <= 1e-6 * t))
+            .map(|light| 1.0 / (count as f64 * light.area()))
+            .sum()
+    }
+
+    /// Extends `path` by following `ray` through the scene until it has
+    /// `max_vertices` vertices, escapes or stops scattering. `beta` is the
+    /// path's throughput along `ray` and `pdf_dir` the solid-angle density of
+    /// the last vertex sending it. Returns the throughput of a ray that
+    /// escaped, black if none did.
+    #[allow(clippy::too_many_arguments)]
+    fn walk<'a>(
+        &self,
+        sampler: &mut dyn Sampler,
+        world: &'a dyn Hittable,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf_dir: f64,
+        max_vertices: usize,
+        camera_path: bool,
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Color {
+        while path.len() < max_vertices {
+            let rec = match world.hit(sampler, &ray, 0.001, f64::INFINITY) {
+                None => return beta,
+                Some(rec) => rec,
+            };
+            let mut vertex = Vertex {
+                kind: Kind::Surface,
+                p: rec.p,
+                normal: (!rec.mat_ptr.is_volume()).then_some(rec.normal),
+                hit: None,
+                scatter: Scatter::None,
+                beta,
+                emitted: Color::black(),
+                light_pdf: 0.0,
+                delta: false,
+                pdf_fwd: 0.0,
+                pdf_rev: 0.0,
+            };
+            let prev = path.len() - 1;
+            vertex.pdf_fwd = path[prev].area_pdf(pdf_dir, &vertex);
+            if camera_path {
+                vertex.emitted = rec.mat_ptr.emitted(rec.u, rec.v, &rec.p);
+                if vertex.emitted.length_squared() > 0.0 {
+                    vertex.light_pdf = self.light_pdf(sampler, &ray, rec.t);
+                }
+            }
+
+            let (next, pdf_rev_dir) = match rec.mat_ptr.scatter(sampler, &ray, &rec) {
+                None => (None, 0.0),
+                Some(ScatterRecord::Specular { attenuation, ray: scattered }) => {
+                    vertex.scatter = Scatter::Specular;
+                    vertex.delta = true;
+                    beta = beta * attenuation;
+                    pdf_dir = 0.0;
+                    (Some(scattered), 0.0)
+                }
+                Some(ScatterRecord::Diffuse { attenuation, pdf }) => {
+                    let direction = pdf.generate(sampler);
+                    let value = pdf.value(&direction);
+                    let scattered = Ray::new(rec.p, direction, ray.time);
+                    let pdf_rev_dir = pdf.value(&-ray.direction);
+                    let next = (value > 0.0).then(|| {
+                        beta = beta * attenuation * rec.mat_ptr.scattering_pdf(&ray, &rec, &scattered) / value;
+                        pdf_dir = value;
+                        scattered
+                    });
+                    vertex.scatter = Scatter::Diffuse { attenuation, pdf };
+                    (next, pdf_rev_dir)
+                }
+            };
+            path[prev].pdf_rev = vertex.area_pdf(pdf_rev_dir, &path[prev]);
+
+            match next {
+                None => {
+                    vertex.hit = Some((rec, ray));
+                    path.push(vertex);
+                    break;
+                }
+                Some(next) => {
+                    vertex.hit = Some((rec, std::mem::replace(&mut ray, next)));
+                    path.push(vertex);
+                }
+            }
+        }
+        Color::black()
+    }
+
+    /// The light path: a point picked on the lights and a cosine-weighted
+    /// direction from it, to either side since lights emit from both.
+    fn light_path<'a>(&'a self, sampler: &mut dyn Sampler, world: &'a dyn Hittable, time: f64) -> Vec<Vertex<'a>> {
+        let mut path = Vec::new();
+        let (rec, pdf_pos) = match self.sample_light(sampler) {
+            None => return path,
+            Some(light) => light,
+        };
+        let emitted = rec.mat_ptr.emitted(rec.u, rec.v, &rec.p);
+        let local = Vec3::random_cosine_direction(sampler);
+        let side = if sampler.next_f64() < 0.5 { 1.0 } else { -1.0 };
+        let direction = Onb::build_from_w(&rec.normal).local(&local) * side;
+        let pdf_dir = local.z / (2.0 * PI);
+        path.push(Vertex {
+            kind: Kind::Light,
+            p: rec.p,
+            normal: Some(rec.normal),
+            hit: None,
+            scatter: Scatter::None,
+            beta: emitted / pdf_pos,
+            emitted,
+            light_pdf: 0.0,
+            delta: false,
+            pdf_fwd: pdf_pos,
+            pdf_rev: 0.0,
+        });
+        if pdf_dir <= 0.0 {
+            return path;
+        }
+        let beta = emitted * local.z / (pdf_pos * pdf_dir);
+        let ray = Ray::new(rec.p, direction, time);
+        self.walk(sampler, world, ray, beta, pdf_dir, self.max_depth.max(0) as usize, false, &mut path);
+        path
+    }
+
+    /// The contribution of the path made of the first `s` vertices of `light`
+    /// and the first `t` of `eye`, weighted for MIS. Paths with `t` of 1 are
+    /// splatted onto `film` instead.
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        &self,
+        sampler: &mut dyn Sampler,
+        world: &dyn Hittable,
+        camera: &dyn Camera,
+        film: &Film,
+        light: &[Vertex],
+        eye: &[Vertex],
+        s: usize,
+        t: usize,
+        time: f64,
+    ) -> Color {
+        let pt = &eye[t - 1];
+        if s == 0 {
+            if pt.emitted.length_squared() <= 0.0 {
+                return Color::black();
+            }
+            let weight = if pt.light_pdf > 0.0 { mis_weight(camera, time, light, eye, s, t, None) } else { 1.0 };
+            return pt.beta * pt.emitted * weight;
+        }
+
+        if t == 1 {
+            let qs = &light[s - 1];
+            if !qs.is_connectable() {
+                return Color::black();
+            }
+            let lens = match camera.sample_lens(sampler, &qs.p, time) {
+                None => return Color::black(),
+                Some(lens) => lens,
+            };
+            let sampled = Vertex::camera(lens.point, Color::new(1.0, 1.0, 1.0), false);
+            let f = qs.f(&sampled.p);
+            if f.length_squared() <= 0.0 || !visible(sampler, world, &qs.p, &sampled.p, time) {
+                return Color::black();
+            }
+            let l = qs.beta * f * lens.pdf / (sampled.p - qs.p).length_squared();
+            let weight = mis_weight(camera, time, light, eye, s, t, Some(&sampled));
+            film.splat(lens.s, lens.t, l * weight);
+            return Color::black();
+        }
+
+        if !pt.is_connectable() {
+            return Color::black();
+        }
+        if s == 1 {
+            let (rec, pdf_pos) = match self.sample_light(sampler) {
+                None => return Color::black(),
+                Some(light) => light,
+            };
+            let emitted = rec.mat_ptr.emitted(rec.u, rec.v, &rec.p);
+            let sampled = Vertex {
+                kind: Kind::Light,
+                p: rec.p,
+                normal: Some(rec.normal),
+                hit: None,
+                scatter: Scatter::None,
+                beta: emitted / pdf_pos,
+                emitted,
+                light_pdf: 0.0,
+                delta: false,
+                pdf_fwd: pdf_pos,
+                pdf_rev: 0.0,
+            };
+            let l = pt.beta * pt.f(&sampled.p) * sampled.f(&pt.p) * sampled.beta / (sampled.p - pt.p).length_squared();
+            if l.length_squared() <= 0.0 || !visible(sampler, world, &pt.p, &sampled.p, time) {
+                return Color::black();
+            }
+            return l * mis_weight(camera, time, light, eye, s, t, Some(&sampled));
+        }
+
+        let qs = &light[s - 1];
+        if !qs.is_connectable() {
+            return Color::black();
+        }
+        let l = qs.beta * qs.f(&pt.p) * pt.f(&qs.p) * pt.beta / (qs.p - pt.p).length_squared();
+        if l.length_squared() <= 0.0 || !visible(sampler, world, &pt.p, &qs.p, time) {
+            return Color::black();
+        }
+        l * mis_weight(camera, time, light, eye, s, t, None)
+    }
+}
+
+impl Integrator for BdptIntegrator {
+    /// Without a camera to trace light to, the other ways of building each
+    /// path make up for light tracing.
+    fn ray_color(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable) -> Color {
+        self.sample(sampler, r, world, &NoCamera, &Film::new(1, 1))
+    }
+
+    fn splats(&self) -> bool {
+        true
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable, camera: &dyn Camera, film: &Film) -> Color {
+        let max_depth = self.max_depth.max(0) as usize;
+        let time = r.time;
+        let pdf_dir = camera.direction_pdf(&r.direction, time);
+        let mut eye = vec![Vertex::camera(r.origin, Color::new(1.0, 1.0, 1.0), pdf_dir <= 0.0)];
+        let ray = Ray::new(r.origin, r.direction, time);
+        let escaped = self.walk(sampler, world, ray, Color::new(1.0, 1.0, 1.0), pdf_dir, max_depth + 1, true, &mut eye);
+        let light = self.light_path(sampler, world, time);
+
+        let mut radiance = escaped * self.background;
+        for t in 1..=eye.len() {
+            for s in 0..=light.len() {
+                // A light path's first vertex seen straight through the lens
+                // is left to the camera path, which hits it just as easily.
+                if s + t < 2 || (s == 1 && t == 1) || s + t > max_depth + 1 {
+                    continue;
+                }
+                radiance = radiance + self.connect(sampler, world, camera, film, &light, &eye, s, t, time);
+            }
+        }
+        radiance
+    }
+}
+
+/// Stands in for the camera when there is none, so nothing is traced to it.
+struct NoCamera;
+
+impl Camera for NoCamera {
+    fn get_ray(&self, _: &mut dyn Sampler, _: f64, _: f64) -> Ray {
+        Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Camera,
+    Light,
+    Surface,
+}
+
+/// How a surface vertex passes light on.
+enum Scatter {
+    /// It does not: the path ends on a light or an absorbing surface.
+    None,
+    /// Through `Material::scattering_pdf`, sampled from `pdf`.
+    Diffuse { attenuation: Color, pdf: Box<dyn Pdf> },
+    /// Along directions no other vertex can be joined through.
+    Specular,
+}
+
+struct Vertex<'a> {
+    kind: Kind,
+    p: Point3,
+    /// The surface normal, on the side the path arrived from for surface
+    /// vertices; `None` for the camera and in a medium, where converting
+    /// densities to area takes no cosine.
+    normal: Option<Vec3>,
+    /// The hit and the ray that made it, for evaluating the material.
+    hit: Option<(HitRecord<'a>, Ray)>,
+    scatter: Scatter,
+    /// The path's throughput up to and including this vertex's own density.
+    beta: Color,
+    /// Radiance the vertex emits, on the lights.
+    emitted: Color,
+    /// For camera vertices on a light, the area density of a light path
+    /// starting there.
+    light_pdf: f64,
+    /// Whether no other vertex can be joined to this one.
+    delta: bool,
+    /// Area densities of the vertex being sampled from the previous vertex of
+    /// its own path, and from the next.
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl Vertex<'_> {
+    fn camera(p: Point3, beta: Color, delta: bool) -> Vertex<'static> {
+        Vertex {
+            kind: Kind::Camera,
+            p,
+            normal: None,
+            hit: None,
+            scatter: Scatter::None,
+            beta,
+            emitted: Color::black(),
+            light_pdf: 0.0,
+            delta,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn is_connectable(&self) -> bool {
+        match self.kind {
+            Kind::Camera | Kind::Light => !self.delta,
+            Kind::Surface => matches!(self.scatter, Scatter::Diffuse { .. }),
+        }
+    }
+
+    /// What the vertex passes on towards `to` of the light reaching it along
+    /// its path, cosine included; for the vertex a light path starts from,
+    /// just the cosine.
+    fn f(&self, to: &Point3) -> Color {
+        match (self.kind, &self.hit, &self.scatter) {
+            (Kind::Light, _, _) => {
+                let cosine = self.normal.map_or(1.0, |n| n.dot(&(*to - self.p).unit_vector()).abs());
+                Color::new(cosine, cosine, cosine)
+            }
+            (_, Some((rec, r_in)), Scatter::Diffuse { attenuation, .. }) => {
+                let scattered = Ray::new(rec.p, *to - rec.p, r_in.time);
+                *attenuation * rec.mat_ptr.scattering_pdf(r_in, rec, &scattered)
+            }
+            _ => Color::black(),
+        }
+    }
+
+    /// Converts a solid-angle density of leaving this vertex towards `next`
+    /// into an area density at `next`.
+    fn area_pdf(&self, pdf_dir: f64, next: &Vertex) -> f64 {
+        let d = next.p - self.p;
+        let distance_squared = d.length_squared();
+        if distance_squared <= 0.0 {
+            return 0.0;
+        }
+        let cosine = next.normal.map_or(1.0, |n| n.dot(&d).abs() / distance_squared.sqrt());
+        pdf_dir * cosine / distance_squared
+    }
+
+    /// The area density of this vertex sampling `next`.
+    fn pdf(&self, camera: &dyn Camera, time: f64, next: &Vertex) -> f64 {
+        let direction = next.p - self.p;
+        let pdf_dir = match (self.kind, &self.scatter) {
+            (Kind::Camera, _) => camera.direction_pdf(&direction, time),
+            (Kind::Light, _) => return self.pdf_light(next),
+            (Kind::Surface, Scatter::Diffuse { pdf, .. }) => pdf.value(&direction),
+            _ => 0.0,
+        };
+        self.area_pdf(pdf_dir, next)
+    }
+
+    /// The area density of a light path starting at this vertex going on to
+    /// `next`.
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let direction = (next.p - self.p).unit_vector();
+        let cosine = self.normal.map_or(1.0, |n| n.dot(&direction).abs());
+        self.area_pdf(cosine / (2.0 * PI), next)
+    }
+}
+
+/// The power-heuristic weight of building the path of `s` light and `t`
+/// camera vertices this way, against every other `s + t` split that could
+/// have built it. `sampled` replaces the last vertex on the side with just
+/// one.
+fn mis_weight(camera: &dyn Camera, time: f64, light: &[Vertex], eye: &[Vertex], s: usize, t: usize, sampled: Option<&Vertex>) -> f64 {
+    if s + t == 2 {
+        return 1.0;
+    }
+    let qs = match (s, sampled) {
+        (0, _) => None,
+        (1, Some(sampled)) => Some(sampled),
+        _ => Some(&light[s - 1]),
+    };
+    let pt = match (t, sampled) {
+        (1, Some(sampled)) => sampled,
+        _ => &eye[t - 1],
+    };
+
+    // Densities and delta flags along each side, with those that change
+    // when the two are joined recomputed.
+    let mut eye_pdfs: Vec<(f64, f64, bool)> = eye[..t].iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+    let mut light_pdfs: Vec<(f64, f64, bool)> = light[..s].iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+    eye_pdfs[t - 1] = (pt.pdf_fwd, pt.pdf_rev, false);
+    eye_pdfs[t - 1].1 = match qs {
+        Some(qs) => qs.pdf(camera, time, pt),
+        None => pt.light_pdf,
+    };
+    if t > 1 {
+        eye_pdfs[t - 2].1 = match qs {
+            Some(_) => pt.pdf(camera, time, &eye[t - 2]),
+            None => pt.pdf_light(&eye[t - 2]),
+        };
+    }
+    if let Some(qs) = qs {
+        light_pdfs[s - 1] = (qs.pdf_fwd, pt.pdf(camera, time, qs), false);
+        if s > 1 {
+            light_pdfs[s - 2].1 = qs.pdf(camera, time, &light[s - 2]);
+        }
+    }
+
+    let remap = |f: f64| if f != 0.0 { f } else { 1.0 };
+    let mut sum = 0.0;
+    let mut ratio = 1.0;
+    for i in (1..t).rev() {
+        let r = remap(eye_pdfs[i].1) / remap(eye_pdfs[i].0);
+        ratio *= r * r;
+        if !eye_pdfs[i].2 && !eye_pdfs[i - 1].2 {
+            sum += ratio;
+        }
+    }
+    ratio = 1.0;
+    for i in (0..s).rev() {
+        let r = remap(light_pdfs[i].1) / remap(light_pdfs[i].0);
+        ratio *= r * r;
+        let delta_before = i > 0 && light_pdfs[i - 1].2;
+        if !light_pdfs[i].2 && !delta_before {
+            sum += ratio;
+        }
+    }
+    1.0 / (1.0 + sum)
+}
+
+/// Whether nothing lies between `a` and `b`.
+fn visible(sampler: &mut dyn Sampler, world: &dyn Hittable, a: &Point3, b: &Point3, time: f64) -> bool {
+    let d = *b - *a;
+    let distance = d.length();
+    world.hit(sampler, &Ray::new(*a, d / distance, time), 0.001, distance - 0.001).is_none()
+}
@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::hittable_list::HittableList;
@@ -5,6 +6,7 @@ use crate::material::Material;
 use crate::ray::Ray;
 use crate::rect::{XyRect, XzRect, YzRect};
 use crate::vec3::Vec3;
+use crate::sampler::Sampler;
 
 #[derive(Clone)]
 pub struct BoxModel {
@@ -14,14 +16,14 @@ pub struct BoxModel {
 }
 
 impl BoxModel {
-  pub fn new(p0: Vec3, p1: Vec3, ptr: Box<dyn Material>) -> BoxModel {
+  pub fn new(p0: Vec3, p1: Vec3, ptr: Arc<dyn Material>) -> BoxModel {
     let mut sides = HittableList::new();
-    sides.add(Box::new(XyRect::new(p0.x, p1.x, p0.y, p1.y, p1.z, ptr.clone_box())));
-    sides.add(Box::new(XyRect::new(p0.x, p1.x, p0.y, p1.y, p0.z, ptr.clone_box())));
-    sides.add(Box::new(XzRect::new(p0.x, p1.x, p0.z, p1.z, p1.y, ptr.clone_box())));
-    sides.add(Box::new(XzRect::new(p0.x, p1.x, p0.z, p1.z, p0.y, ptr.clone_box())));
-    sides.add(Box::new(YzRect::new(p0.y, p1.y, p0.z, p1.z, p1.x, ptr.clone_box())));
-    sides.add(Box::new(YzRect::new(p0.y, p1.y, p0.z, p1.z, p0.x, ptr.clone_box())));
+    sides.add(Arc::new(XyRect::new(p0.x, p1.x, p0.y, p1.y, p1.z, ptr.clone())));
+    sides.add(Arc::new(XyRect::new(p0.x, p1.x, p0.y, p1.y, p0.z, ptr.clone())));
+    sides.add(Arc::new(XzRect::new(p0.x, p1.x, p0.z, p1.z, p1.y, ptr.clone())));
+    sides.add(Arc::new(XzRect::new(p0.x, p1.x, p0.z, p1.z, p0.y, ptr.clone())));
+    sides.add(Arc::new(YzRect::new(p0.y, p1.y, p0.z, p1.z, p1.x, ptr.clone())));
+    sides.add(Arc::new(YzRect::new(p0.y, p1.y, p0.z, p1.z, p0.x, ptr.clone())));
 
     BoxModel {
       box_min: p0,
@@ -32,8 +34,8 @@ impl BoxModel {
 }
 
 impl Hittable for BoxModel {
-  fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-    self.sides.hit(r, t_min, t_max)
+  fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+    self.sides.hit(sampler, r, t_min, t_max)
   }
 
   fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
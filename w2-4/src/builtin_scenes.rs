@@ -0,0 +1,227 @@
+use std::path::Path;
+use std::sync::Arc;
+use crate::box_model::BoxModel;
+use crate::bvh::Bvh;
+use crate::camera::CameraSettings;
+use crate::constant_medium::ConstantMedium;
+use crate::hittable_list::HittableList;
+use crate::material::{Dielactric, DiffuseLight, IsoTropic, Lambertian, Metal};
+use crate::projection::Projection;
+use crate::rect::XzRect;
+use crate::rotate::RotateY;
+use crate::sampler::Sampler;
+use crate::render::{IntegratorKind, RenderSettings};
+use crate::scene::{Scene, SceneError};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor};
+use crate::translate::Translate;
+use crate::vec3::{Color, Vec3};
+
+pub const NAMES: [&str; 8] = [
+    "random_scene",
+    "two_spheres",
+    "two_perlin_spheres",
+    "earth",
+    "simple_light",
+    "cornell_box",
+    "cornell_smoke",
+    "final_scene",
+];
+
+/// Builds one of the scenes from the book by name. The procedural scenes are
+/// generated from `rng`; the others are the files under `scenes/`.
+pub fn load<S: Sampler + ?Sized>(rng: &mut S, name: &str) -> Option<Result<Scene, SceneError>> {
+    let source = match name {
+        "random_scene" => return Some(Ok(random_scene(rng))),
+        "final_scene" => return Some(Ok(final_scene(rng))),
+        "two_spheres" => include_str!("../scenes/two_spheres.toml"),
+        "two_perlin_spheres" => include_str!("../scenes/two_perlin_spheres.toml"),
+        "earth" => include_str!("../scenes/earth.toml"),
+        "simple_light" => include_str!("../scenes/simple_light.toml"),
+        "cornell_box" => include_str!("../scenes/cornell_box.toml"),
+        "cornell_smoke" => include_str!("../scenes/cornell_smoke.toml"),
+        _ => return None,
+    };
+    let base_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+    Some(Scene::parse(rng, source, &base_dir))
+}
+
+pub fn random_scene<S: Sampler + ?Sized>(rng: &mut S) -> Scene {
+  let mut world = HittableList::new();
+
+  let checker = Arc::new(CheckerTexture::new(
+    Arc::new(SolidColor::new(Vec3::new(0.2, 0.3, 0.1))),
+    Arc::new(SolidColor::new(Vec3::new(0.9, 0.9, 0.9))),
+  ));
+
+  let ground_material = Lambertian::new(checker);
+  let ground = Sphere::new(
+    Vec3::new(0.0, -1000.0, 0.0),
+    1000.0,
+    Arc::new(ground_material)
+  );
+  world.add(Arc::new(ground));
+
+  let mut objects = HittableList::new();
+
+  for a in -11..11 {
+    for b in -11..11 {
+      let choose_mat = rng.next_f64();
+      let center = Vec3::new((a as f64) + 0.9*rng.next_f64(), 0.2, (b as f64) + 0.9*rng.next_f64());
+
+      if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+        if choose_mat < 0.8 {
+          let albedo = Vec3::random(rng) * Vec3::random(rng);        
+          let r = Lambertian::new(Arc::new(SolidColor::new(albedo)));
+          objects.add(Arc::new(Sphere::new(center, 0.2, Arc::new(r))));
+        } else if choose_mat < 0.95 {
+          let albedo = Vec3::random(rng) * Vec3::random_range(rng, 0.5..1.0);
+          let fuzz = rng.next_range(0.0..0.5);
+          let r = Metal::new(albedo, fuzz);
+          objects.add(Arc::new(Sphere::new(center, 0.2, Arc::new(r))));
+        } else {
+          let r = Dielactric::new(1.5);
+          objects.add(Arc::new(Sphere::new(center, 0.2, Arc::new(r))));
+        };        
+      }
+    }
+  }
+
+  objects.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(Dielactric::new(1.5)))));
+  objects.add(Arc::new(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, Arc::new(Lambertian::new(Arc::new(SolidColor::new(Vec3::new(0.4, 0.2, 0.1))))))));
+  objects.add(Arc::new(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, Arc::new(Metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0)))));
+
+  // world.add(Arc::new(objects));
+  world.add(Arc::new(Bvh::new(objects, 0.0, 0.0)));
+
+  Scene {
+    world,
+    lights: HittableList::new(),
+    camera: CameraSettings {
+      lookfrom: Vec3::new(13.0, 2.0, 3.0),
+      lookat: Vec3::new(0.0, 0.0, 0.0),
+      lookfrom1: Vec3::new(13.0, 2.0, 3.0),
+      lookat1: Vec3::new(0.0, 0.0, 0.0),
+      blades: 0,
+      exposure: 1.0,
+      projection: Projection::Perspective,
+      vup: Vec3::new(0.0, 1.0, 0.0),
+      vfov: 20.0,
+      aperture: 0.1,
+      focus_dist: 10.0,
+      time0: 0.0,
+      time1: 1.0
+    },
+    settings: RenderSettings {
+      image_width: 400,
+      image_height: 225,
+      samples_per_pixel: 100,
+      max_depth: 50,
+      background: Color::new(0.70, 0.80, 1.00),
+      seed: 0,
+      integrator: IntegratorKind::Recursive
+    },
+    timeline: None,
+    aperture_image: None
+  }
+}
+
+pub fn final_scene<S: Sampler + ?Sized>(rng: &mut S) -> Scene {
+    let mut boxes1 = HittableList::new();
+    let ground = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Vec3::new(0.48, 0.83, 0.53)))));
+    const BOXES_PER_SIDE: usize = 20;
+
+    for i in 0 .. BOXES_PER_SIDE {
+        for j in 0 .. BOXES_PER_SIDE {
+            let w = 100.0;
+            boxes1.add(
+                Arc::new(BoxModel::new(
+                    Vec3::new(
+                        -1000.0 + i as f64 * w,
+                        0.0,
+                        -1000.0 + j as f64 * w) ,
+                    Vec3::new(
+                        -1000.0 + i as f64 * w + w,
+                        rng.next_range(1.0..101.0),
+                        -1000.0 + j as f64 * w + w),
+                    ground.clone()
+                ))
+            );
+        }
+    }
+
+    let mut objects = HittableList::new();
+    objects.add(Arc::new(Bvh::new(boxes1, 0.0, 1.0)));
+
+    let light = Arc::new(DiffuseLight::new(Arc::new(SolidColor::new(Vec3::new(7.0, 7.0, 7.0)))));
+    let light = Arc::new(XzRect::new(123.0, 423.0, 147.0, 412.0, 554.0, light));
+    let mut lights = HittableList::new();
+    lights.add(light.clone());
+    objects.add(light);
+
+    let center1 = Vec3::new(400.0, 400.0, 200.0);
+    let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
+    let moving_sphere_material = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Vec3::new(0.7, 0.3, 0.1)))));
+    objects.add(Arc::new(MovingSphere::new(center1, center2, 0.0, 1.0, 50.0, moving_sphere_material)));
+
+    objects.add(Arc::new(Sphere::new(Vec3::new(260.0, 150.0, 45.0), 50.0, Arc::new(Dielactric::new(1.5)))));
+    objects.add(Arc::new(Sphere::new(Vec3::new(0.0, 150.0, 145.0), 50.0, Arc::new(Metal::new(Vec3::new(0.8, 0.8, 0.9), 10.0)))));
+
+    let boundary = Arc::new(Sphere::new(Vec3::new(360.0, 150.0, 145.0), 70.0, Arc::new(Dielactric::new(1.5))));
+    objects.add(boundary);
+    let boundary = Arc::new(Sphere::new(Vec3::new(360.0, 150.0, 145.0), 70.0, Arc::new(Dielactric::new(1.5))));
+    objects.add(Arc::new(ConstantMedium::new(boundary, 0.2, Arc::new(IsoTropic::new(Arc::new(SolidColor::new(Vec3::new(0.2, 0.4, 0.9))))))));
+    let boundary = Arc::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 5000.0, Arc::new(Dielactric::new(1.5))));
+    objects.add(Arc::new(ConstantMedium::new(boundary, 0.0001, Arc::new(IsoTropic::new(Arc::new(SolidColor::new(Vec3::new(1.0, 1.0, 1.0))))))));
+
+    let emat = Arc::new(Lambertian::new(Arc::new(ImageTexture::new(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/earthmap.jpg")))));
+    objects.add(Arc::new(Sphere::new(Vec3::new(400.0, 200.0, 400.0), 100.0, emat)));
+    let pertext = Arc::new(NoiseTexture::new(rng, 0.1));
+    objects.add(Arc::new(Sphere::new(Vec3::new(220.0, 280.0, 300.0), 80.0, Arc::new(Lambertian::new(pertext)))));
+
+    let mut boxes2 = HittableList::new();
+    let white = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Vec3::new(0.73, 0.73, 0.73)))));
+    let ns = 1000;
+    for _ in 0 .. ns {
+        boxes2.add(Arc::new(Sphere::new(Vec3::random_range(rng, 0.0..165.0), 10.0, white.clone())));
+    }
+
+    objects.add(Arc::new(Translate::new(
+        Arc::new(RotateY::new(
+            Arc::new(Bvh::new(boxes2, 0.0, 1.0)),
+            15.0
+        )),
+        Vec3::new(-100.0, 270.0, 395.0)
+    )));
+
+    Scene {
+        world: objects,
+        lights,
+        camera: CameraSettings {
+            lookfrom: Vec3::new(478.0, 278.0, -600.0),
+            lookat: Vec3::new(278.0, 278.0, 0.0),
+            lookfrom1: Vec3::new(478.0, 278.0, -600.0),
+            lookat1: Vec3::new(278.0, 278.0, 0.0),
+            blades: 0,
+            exposure: 1.0,
+            projection: Projection::Perspective,
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 40.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+            time0: 0.0,
+            time1: 1.0
+        },
+        settings: RenderSettings {
+            image_width: 600,
+            image_height: 600,
+            samples_per_pixel: 50,
+            max_depth: 50,
+            background: Color::black(),
+            seed: 0,
+            integrator: IntegratorKind::Recursive
+        },
+        timeline: None,
+        aperture_image: None
+    }
+}
@@ -0,0 +1,384 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::aabb::{surrounding_box, Aabb};
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+
+/// Number of buckets the centroids are sorted into when looking for a split.
+const BINS: usize = 12;
+/// Nodes with more objects than this are always split.
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of visiting a node, relative to intersecting one object.
+const TRAVERSAL_COST: f64 = 0.125;
+/// Deeper nodes become leaves, which bounds the traversal stack.
+const MAX_DEPTH: usize = 64;
+/// Subtrees over at least this many objects are built in parallel.
+const PARALLEL_SUBTREE: usize = 256;
+/// Nodes over at least this many objects are binned in parallel.
+const PARALLEL_BINNING: usize = 16 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+    /// `count` items starting at `first` in `Tree::order`.
+    Leaf { first: usize, count: usize },
+    /// The first child directly follows its parent in `Tree::nodes`.
+    Inner { second: usize, axis: i32 },
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    bbox: Aabb,
+    kind: Kind,
+}
+
+type Bin = (Option<Aabb>, usize);
+
+const EMPTY_BIN: Bin = (None, 0);
+
+/// The nodes of a BVH built with the surface area heuristic over items known
+/// only by their bounding boxes. Nodes are stored depth first in one array and
+/// leaves hold up to a few items.
+#[derive(Clone)]
+pub(crate) struct Tree {
+    nodes: Vec<Node>,
+    /// The items in leaf order: leaves refer to ranges of this list.
+    pub(crate) order: Vec<usize>,
+}
+
+impl Tree {
+    /// Builds large subtrees on the rayon thread pool. Every split is decided
+    /// exactly as `build_serial` decides it, so the trees are the same.
+    pub(crate) fn build(bounds: &[Aabb]) -> Tree {
+        let mut order: Vec<usize> = (0..bounds.len()).collect();
+        let nodes = if bounds.is_empty() {
+            Vec::new()
+        } else {
+            build_parallel(bounds, &mut order, 0, 0)
+        };
+        Tree { nodes, order }
+    }
+
+    pub(crate) fn build_serial(bounds: &[Aabb]) -> Tree {
+        let mut order: Vec<usize> = (0..bounds.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * bounds.len());
+        if !bounds.is_empty() {
+            build(bounds, &mut order, 0, 0, &mut nodes);
+        }
+        Tree { nodes, order }
+    }
+
+    pub(crate) fn bbox(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.bbox)
+    }
+
+    /// Finds the closest hit, calling `hit_item` with the position in `order`
+    /// of each item whose leaf the ray enters and the closest hit so far.
+    pub(crate) fn hit<'a, F>(&self, r: &Ray, t_min: f64, t_max: f64, mut hit_item: F) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f64) -> Option<HitRecord<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest = t_max;
+        let mut hit = None;
+        let mut stack = [0; MAX_DEPTH + 1];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let index = stack[len];
+            let node = &self.nodes[index];
+            if !node.bbox.hit(r, t_min, closest) {
+                continue;
+            }
+            match node.kind {
+                Kind::Leaf { first, count } => {
+                    for item in first..first + count {
+                        if let Some(rec) = hit_item(item, closest) {
+                            closest = rec.t;
+                            hit = Some(rec);
+                        }
+                    }
+                }
+                Kind::Inner { second, axis } => {
+                    // Visit the child on the side the ray comes from first, so
+                    // hits there can cull the other one.
+                    let (near, far) = if r.direction.d(axis) < 0.0 {
+                        (second, index + 1)
+                    } else {
+                        (index + 1, second)
+                    };
+                    stack[len] = far;
+                    stack[len + 1] = near;
+                    len += 2;
+                }
+            }
+        }
+        hit
+    }
+}
+
+/// A bounding volume hierarchy over scene objects, see `Tree`. The objects
+/// are reordered so every leaf's objects are contiguous.
+#[derive(Clone)]
+pub struct Bvh {
+    tree: Tree,
+    objects: Vec<Arc<dyn Hittable>>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy, in parallel, over the bounding boxes the objects
+    /// have between `time0` and `time1`; every object must have one.
+    pub fn new(list: HittableList, time0: f64, time1: f64) -> Bvh {
+        let bounds = object_bounds(&list, time0, time1);
+        Bvh::with_tree(list, Tree::build(&bounds))
+    }
+
+    /// Same as `new` on the current thread only.
+    pub fn new_serial(list: HittableList, time0: f64, time1: f64) -> Bvh {
+        let bounds = object_bounds(&list, time0, time1);
+        Bvh::with_tree(list, Tree::build_serial(&bounds))
+    }
+
+    fn with_tree(list: HittableList, tree: Tree) -> Bvh {
+        let objects = tree.order.iter().map(|&i| list.objects[i].clone()).collect();
+        Bvh { tree, objects }
+    }
+}
+
+fn object_bounds(list: &HittableList, time0: f64, time1: f64) -> Vec<Aabb> {
+    list.objects
+        .iter()
+        .map(|object| object.bounding_box(time0, time1).expect("objects in a BVH need a bounding box"))
+        .collect()
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.tree.hit(r, t_min, t_max, |i, closest| self.objects[i].hit(sampler, r, t_min, closest))
+    }
+
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        self.tree.bbox()
+    }
+}
+
+/// Appends the subtree over `order`, whose items start at `first` in the
+/// final order.
+fn build(bounds: &[Aabb], order: &mut [usize], first: usize, depth: usize, nodes: &mut Vec<Node>) {
+    let bbox = union(order, |i| bounds[i]);
+    let index = nodes.len();
+    nodes.push(Node {
+        bbox,
+        kind: Kind::Leaf {
+            first,
+            count: order.len(),
+        },
+    });
+    if depth == MAX_DEPTH {
+        return;
+    }
+
+    if let Some((axis, mid)) = split(bounds, order, &bbox) {
+        build(bounds, &mut order[..mid], first, depth + 1, nodes);
+        let second = nodes.len();
+        build(bounds, &mut order[mid..], first + mid, depth + 1, nodes);
+        nodes[index].kind = Kind::Inner { second, axis };
+    }
+}
+
+/// Like `build`, but returns the subtree with node indices relative to its
+/// root, so both halves can be built at the same time and then joined.
+fn build_parallel(bounds: &[Aabb], order: &mut [usize], first: usize, depth: usize) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    if order.len() < PARALLEL_SUBTREE || depth == MAX_DEPTH {
+        build(bounds, order, first, depth, &mut nodes);
+        return nodes;
+    }
+
+    let bbox = union(order, |i| bounds[i]);
+    nodes.push(Node {
+        bbox,
+        kind: Kind::Leaf {
+            first,
+            count: order.len(),
+        },
+    });
+    if let Some((axis, mid)) = split(bounds, order, &bbox) {
+        let (left, right) = order.split_at_mut(mid);
+        let (left, right) = rayon::join(
+            || build_parallel(bounds, left, first, depth + 1),
+            || build_parallel(bounds, right, first + mid, depth + 1),
+        );
+        let second = 1 + left.len();
+        nodes[0].kind = Kind::Inner { second, axis };
+        append(&mut nodes, left, 1);
+        append(&mut nodes, right, second);
+    }
+    nodes
+}
+
+fn append(nodes: &mut Vec<Node>, subtree: Vec<Node>, offset: usize) {
+    nodes.extend(subtree.into_iter().map(|mut node| {
+        if let Kind::Inner { second, .. } = &mut node.kind {
+            *second += offset;
+        }
+        node
+    }));
+}
+
+/// The box around `f` of every item, computed in parallel for large nodes.
+/// Unions are exact, so the split of the work does not change the result.
+fn union<F: Fn(usize) -> Aabb + Sync>(order: &[usize], f: F) -> Aabb {
+    if order.len() >= PARALLEL_BINNING {
+        order.par_iter().map(|&i| f(i)).reduce_with(surrounding_box).unwrap()
+    } else {
+        order.iter().map(|&i| f(i)).reduce(surrounding_box).unwrap()
+    }
+}
+
+fn centroid(b: &Aabb) -> Aabb {
+    let c = (b.min + b.max) * 0.5;
+    Aabb::new(c, c)
+}
+
+/// Finds the cheapest binned SAH split along the longest axis of the
+/// centroids and partitions `order` by it. Returns the axis and the size of
+/// the first half, or `None` if a leaf is cheaper.
+fn split(bounds: &[Aabb], order: &mut [usize], bbox: &Aabb) -> Option<(i32, usize)> {
+    let n = order.len();
+    if n <= 1 {
+        return None;
+    }
+
+    let centroids = union(order, |i| centroid(&bounds[i]));
+    let extent = centroids.max - centroids.min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+    if extent.d(axis) <= 0.0 {
+        // All centroids coincide, so no plane separates them.
+        return if n <= MAX_LEAF_SIZE { None } else { Some((axis, n / 2)) };
+    }
+
+    let bin_of = |i: usize| {
+        let offset = (centroid(&bounds[i]).min.d(axis) - centroids.min.d(axis)) / extent.d(axis);
+        ((offset * BINS as f64) as usize).min(BINS - 1)
+    };
+    let add = |mut bins: [Bin; BINS], &i: &usize| {
+        let k = bin_of(i);
+        bins[k] = merge(bins[k], (Some(bounds[i]), 1));
+        bins
+    };
+    let bins = if n >= PARALLEL_BINNING {
+        order
+            .par_iter()
+            .fold(|| [EMPTY_BIN; BINS], add)
+            .reduce(|| [EMPTY_BIN; BINS], |a, b| std::array::from_fn(|k| merge(a[k], b[k])))
+    } else {
+        order.iter().fold([EMPTY_BIN; BINS], add)
+    };
+
+    // Split k puts bins[..k] on the left.
+    let mut right = [(0.0, 0); BINS];
+    let mut acc = EMPTY_BIN;
+    for k in (1..BINS).rev() {
+        acc = merge(acc, bins[k]);
+        right[k] = (acc.0.map_or(0.0, |b| b.surface_area()), acc.1);
+    }
+    let mut best: Option<(f64, usize)> = None;
+    let mut left = EMPTY_BIN;
+    for k in 1..BINS {
+        left = merge(left, bins[k - 1]);
+        let (right_area, right_count) = right[k];
+        if left.1 == 0 || right_count == 0 {
+            continue;
+        }
+        let left_area = left.0.map_or(0.0, |b| b.surface_area());
+        let cost = TRAVERSAL_COST + (left_area * left.1 as f64 + right_area * right_count as f64) / bbox.surface_area();
+        if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+            best = Some((cost, k));
+        }
+    }
+
+    let (cost, k) = best?;
+    if n <= MAX_LEAF_SIZE && cost >= n as f64 {
+        return None;
+    }
+    let mut mid = 0;
+    for j in 0..n {
+        if bin_of(order[j]) < k {
+            order.swap(mid, j);
+            mid += 1;
+        }
+    }
+    Some((axis, mid))
+}
+
+fn merge(a: Bin, b: Bin) -> Bin {
+    let bbox = match (a.0, b.0) {
+        (Some(a), Some(b)) => Some(surrounding_box(a, b)),
+        (a, b) => a.or(b),
+    };
+    (bbox, a.1 + b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::vec3::Vec3;
+
+    /// The boxes and spheres of `final_scene`, then enough random boxes for
+    /// the root to be binned in parallel.
+    fn bounds() -> Vec<Aabb> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut bounds = Vec::new();
+        for i in 0..20 {
+            for j in 0..20 {
+                let (x0, z0) = (-1000.0 + i as f64 * 100.0, -1000.0 + j as f64 * 100.0);
+                let y1 = rng.next_range(1.0..101.0);
+                bounds.push(Aabb::new(Vec3::new(x0, 0.0, z0), Vec3::new(x0 + 100.0, y1, z0 + 100.0)));
+            }
+        }
+        for _ in 0..1000 {
+            let c = Vec3::random_range(&mut rng, 0.0..165.0);
+            bounds.push(Aabb::new(c - Vec3::new(10.0, 10.0, 10.0), c + Vec3::new(10.0, 10.0, 10.0)));
+        }
+        for _ in 0..PARALLEL_BINNING {
+            let min = Vec3::random_range(&mut rng, -1000.0..1000.0);
+            bounds.push(Aabb::new(min, min + Vec3::random_range(&mut rng, 0.0..20.0)));
+        }
+        bounds
+    }
+
+    fn words(node: &Node) -> ([u64; 6], Kind) {
+        let (min, max) = (node.bbox.min, node.bbox.max);
+        ([min.x, min.y, min.z, max.x, max.y, max.z].map(f64::to_bits), node.kind)
+    }
+
+    #[test]
+    fn parallel_and_serial_builds_agree() {
+        let bounds = bounds();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        for n in [400, 1400, bounds.len()] {
+            let parallel = pool.install(|| Tree::build(&bounds[..n]));
+            let serial = Tree::build_serial(&bounds[..n]);
+            assert!(parallel.nodes.len() > n / MAX_LEAF_SIZE);
+            let nodes = |tree: &Tree| tree.nodes.iter().map(words).collect::<Vec<_>>();
+            assert_eq!(nodes(&parallel), nodes(&serial));
+            assert_eq!(parallel.order, serial.order);
+        }
+    }
+}
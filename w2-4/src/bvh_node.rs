@@ -1,29 +1,29 @@
 use std::cmp::Ordering;
-use rand::prelude::ThreadRng;
-use rand::Rng;
+use std::sync::Arc;
 use crate::aabb::{Aabb, surrounding_box};
 use crate::hittable::{HitRecord, Hittable};
 use crate::hittable_list::HittableList;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 #[derive(Clone)]
 pub struct BvhNode {
-    left: Box<dyn Hittable>,
-    right: Box<dyn Hittable>,
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
     r#box: Aabb
 }
 
 impl Hittable for BvhNode {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         if !self.r#box.hit(r, t_min, t_max) {
             return None;
         }
 
-        let hit_left = self.left.hit(r, t_min, t_max);
+        let hit_left = self.left.hit(sampler, r, t_min, t_max);
         let hit_right =
             match hit_left {
-                Some(ref hl) => self.right.hit(r, t_min, hl.t),
-                None =>  self.right.hit(r, t_min,t_max),
+                Some(ref hl) => self.right.hit(sampler, r, t_min, hl.t),
+                None =>  self.right.hit(sampler, r, t_min,t_max),
             };
         if hit_right.is_some() {
             hit_right
@@ -40,8 +40,8 @@ impl Hittable for BvhNode {
 }
 
 impl BvhNode {
-    pub fn new(
-        rng: &mut ThreadRng,
+    pub fn new<S: Sampler + ?Sized>(
+        sampler: &mut S,
         mut hittable_list: HittableList,
         start: usize,
         end: usize,
@@ -49,7 +49,7 @@ impl BvhNode {
         time1: f64
     ) -> BvhNode {
         let objects = &mut hittable_list.objects;
-        let axis = rng.gen_range(0..=2);
+        let axis = sampler.next_index(3);
         let comparator =
             if axis == 0 {
                 box_x_compare
@@ -64,52 +64,52 @@ impl BvhNode {
         let (left, right) =
             if object_span == 1 {
                 let x = &objects[start];
-                (x.clone_box(), x.clone_box())
+                (x.clone(), x.clone())
             } else if object_span == 2 {
                 let start_object= &objects[start];
                 let next_object= &objects[start+1];
 
-                if comparator(start_object, next_object).is_gt() {
-                    (start_object.clone_box(), next_object.clone_box())
+                if comparator(&**start_object, &**next_object).is_gt() {
+                    (start_object.clone(), next_object.clone())
                 } else {
-                    (next_object.clone_box(), start_object.clone_box())
+                    (next_object.clone(), start_object.clone())
                 }
             } else {
                 let (_, temp) = &mut objects.split_at_mut(start);
                 let (temp2, _) = temp.split_at_mut(end - start);
-                temp2.sort_by(comparator);
+                temp2.sort_by(|a, b| comparator(&**a, &**b));
 
                 let mid = start + object_span / 2;
                 (
-                    Box::new(BvhNode::new(rng, HittableList { objects: objects.to_vec() }, start, mid, time0, time1)) as Box<dyn Hittable>,
-                    Box::new(BvhNode::new(rng, HittableList { objects: objects.to_vec() }, mid, end, time0, time1)) as Box<dyn Hittable>
+                    Arc::new(BvhNode::new(sampler, HittableList { objects: objects.to_vec() }, start, mid, time0, time1)) as Arc<dyn Hittable>,
+                    Arc::new(BvhNode::new(sampler, HittableList { objects: objects.to_vec() }, mid, end, time0, time1)) as Arc<dyn Hittable>
                 )
             };
         let box_left = left.bounding_box(time0, time1).unwrap();
         let box_right =right.bounding_box(time0, time1).unwrap();
         BvhNode {
-            left: left,
-            right: right,
+            left,
+            right,
             r#box: surrounding_box(box_left, box_right)
         }
     }
 }
 #[inline]
-fn box_compare(a: &Box<dyn Hittable>, b: &Box<dyn Hittable>, axis: i32) -> Ordering {
+fn box_compare(a: &dyn Hittable, b: &dyn Hittable, axis: i32) -> Ordering {
     let box_a = a.bounding_box(0.0, 0.0).unwrap();
     let box_b = b.bounding_box(0.0, 0.0).unwrap();
 
     box_a.min.d(axis).partial_cmp(&box_b.min.d(axis)).unwrap()
 }
 
-fn box_x_compare(a: &Box<dyn Hittable>, b: &Box<dyn Hittable>) -> Ordering {
+fn box_x_compare(a: &dyn Hittable, b: &dyn Hittable) -> Ordering {
     box_compare(a, b, 0)
 }
 
-fn box_y_compare(a: &Box<dyn Hittable>, b: &Box<dyn Hittable>) -> Ordering {
+fn box_y_compare(a: &dyn Hittable, b: &dyn Hittable) -> Ordering {
     box_compare(a, b, 1)
 }
 
-fn box_z_compare(a: &Box<dyn Hittable>, b: &Box<dyn Hittable>) -> Ordering {
+fn box_z_compare(a: &dyn Hittable, b: &dyn Hittable) -> Ordering {
     box_compare(a, b, 2)
 }
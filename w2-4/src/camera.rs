@@ -1,23 +1,269 @@
-use rand::Rng;
-use rand::rngs::ThreadRng;
-
+use crate::aperture::Aperture;
+use crate::projection::{CubeMapCamera, EquirectangularCamera, FisheyeCamera, OrthographicCamera, Pose, Projection};
 use crate::vec3::Vec3;
 use crate::vec3::Point3;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+
+/// Turns image coordinates into rays: `s` runs from 0 at the left edge to 1
+/// at the right, `t` from 0 at the bottom to 1 at the top.
+pub trait Camera: Send + Sync {
+  fn get_ray(&self, sampler: &mut dyn Sampler, s: f64, t: f64) -> Ray;
+
+  /// What every sample's radiance is scaled by.
+  fn exposure(&self) -> f64 {
+    1.0
+  }
+
+  /// For light tracing: picks a point on the lens to see `p` from at `time`,
+  /// or `None` if this camera cannot be reached that way.
+  fn sample_lens(&self, _sampler: &mut dyn Sampler, _p: &Point3, _time: f64) -> Option<LensSample> {
+    None
+  }
+
+  /// The density, over solid angle, of `get_ray` sending a ray along
+  /// `direction` at `time` for `s` and `t` uniform over the unit square; 0
+  /// where that is not known.
+  fn direction_pdf(&self, _direction: &Vec3, _time: f64) -> f64 {
+    0.0
+  }
+}
+
+/// A point on the lens and where on the image it sees a given point.
+#[derive(Clone, Copy)]
+pub struct LensSample {
+  pub point: Point3,
+  pub s: f64,
+  pub t: f64,
+  /// `direction_pdf` of the ray from `point` to the given point.
+  pub pdf: f64
+}
+
+#[derive(Clone, Copy)]
+pub struct CameraSettings {
+  pub lookfrom: Point3,
+  pub lookat: Point3,
+  pub vup: Vec3,
+  pub vfov: f64,
+  pub aperture: f64,
+  pub focus_dist: f64,
+  pub time0: f64,
+  pub time1: f64,
+  /// Where the camera is and looks at `time1`; it moves there in a straight
+  /// line from `lookfrom` and `lookat` over the shutter interval.
+  pub lookfrom1: Point3,
+  pub lookat1: Point3,
+  /// Number of aperture blades, which shape the lens opening into a polygon;
+  /// 0 for a round one.
+  pub blades: u32,
+  /// Scales the radiance the camera records, see `Exposure`.
+  pub exposure: f64,
+  /// How the image maps onto directions; `vfov`, `aperture`, `focus_dist`
+  /// and `blades` only apply to the perspective projection.
+  pub projection: Projection
+}
+
+impl CameraSettings {
+  pub fn build(&self, aspect_ratio: f64) -> Box<dyn Camera> {
+    let pose = Pose::new(self.lookfrom, self.lookat, self.vup, self.time0, self.time1)
+      .with_motion(self.lookfrom1, self.lookat1);
+    match self.projection {
+      Projection::Perspective => Box::new(self.perspective(aspect_ratio)),
+      Projection::Orthographic { height } =>
+        Box::new(OrthographicCamera::new(pose, height, aspect_ratio).with_exposure(self.exposure)),
+      Projection::Fisheye { fov } =>
+        Box::new(FisheyeCamera::new(pose, fov, aspect_ratio).with_exposure(self.exposure)),
+      Projection::Equirectangular => Box::new(EquirectangularCamera::new(pose).with_exposure(self.exposure)),
+      Projection::CubeMap => Box::new(CubeMapCamera::new(pose).with_exposure(self.exposure))
+    }
+  }
+
+  /// The height of the perspective view at `focus_dist`.
+  pub fn focus_height(&self) -> f64 {
+    2.0 * self.focus_dist * (self.vfov.to_radians() / 2.0).tan()
+  }
+
+  /// The perspective camera these settings describe, whatever their
+  /// `projection`.
+  pub fn perspective(&self, aspect_ratio: f64) -> PerspectiveCamera {
+    let camera = PerspectiveCamera::new(
+      self.lookfrom,
+      self.lookat,
+      self.vup,
+      self.vfov,
+      aspect_ratio,
+      self.aperture,
+      self.focus_dist,
+      self.time0,
+      self.time1
+    )
+    .with_motion(self.lookfrom1, self.lookat1)
+    .with_exposure(self.exposure);
+    if self.blades >= 3 {
+      camera.with_aperture(Aperture::Polygon { blades: self.blades, rotation: 0.0 })
+    } else {
+      camera
+    }
+  }
+}
+
+/// How much light reaches the sensor: the shutter time in seconds and the
+/// ISO sensitivity, together with the lens's f-number.
+#[derive(Clone, Copy)]
+pub struct Exposure {
+  pub shutter_time: f64,
+  pub iso: f64
+}
+
+impl Exposure {
+  /// The factor radiance is scaled by, from the exposure value at ISO 100
+  /// and saturation-based sensitivity: `t * S / (1.2 * 100 * N^2)`. At f/1,
+  /// one second and ISO 100 a radiance of 1.2 is white.
+  pub fn scale(&self, f_number: f64) -> f64 {
+    let ev100 = (f_number * f_number / self.shutter_time * 100.0 / self.iso).log2();
+    1.0 / (1.2 * 2f64.powf(ev100))
+  }
+}
+
+/// A camera described by the parts of a real one. The sensor and the focal
+/// length are in millimetres, `focus_dist` in scene units, and
+/// `units_per_metre` relates the two. The image covers the height of the
+/// sensor, and as much of its width as the image's aspect ratio takes.
+#[derive(Clone)]
+pub struct PhysicalCamera {
+  pub lookfrom: Point3,
+  pub lookat: Point3,
+  pub vup: Vec3,
+  pub sensor_width: f64,
+  pub sensor_height: f64,
+  pub focal_length: f64,
+  pub f_number: f64,
+  pub focus_dist: f64,
+  pub units_per_metre: f64,
+  pub aperture: Aperture,
+  pub exposure: Option<Exposure>,
+  pub time0: f64,
+  pub time1: f64
+}
+
+impl PhysicalCamera {
+  /// A full-frame (36 x 24 mm) camera with a round f/2.8 50 mm lens focused
+  /// on `lookat`, in a scene measured in metres.
+  pub fn new(lookfrom: Point3, lookat: Point3) -> PhysicalCamera {
+    PhysicalCamera {
+      lookfrom,
+      lookat,
+      vup: Vec3::new(0.0, 1.0, 0.0),
+      sensor_width: 36.0,
+      sensor_height: 24.0,
+      focal_length: 50.0,
+      f_number: 2.8,
+      focus_dist: (lookat - lookfrom).length(),
+      units_per_metre: 1.0,
+      aperture: Aperture::Circle,
+      exposure: None,
+      time0: 0.0,
+      time1: 0.0
+    }
+  }
+
+  /// The vertical field of view in degrees.
+  pub fn vfov(&self) -> f64 {
+    2.0 * (self.sensor_height / (2.0 * self.focal_length)).atan().to_degrees()
+  }
+
+  /// The diameter of the entrance pupil, in scene units.
+  pub fn aperture_diameter(&self) -> f64 {
+    self.focal_length / self.f_number / 1000.0 * self.units_per_metre
+  }
+
+  /// The equivalent settings; a mask aperture becomes a round one.
+  pub fn settings(&self) -> CameraSettings {
+    CameraSettings {
+      lookfrom: self.lookfrom,
+      lookat: self.lookat,
+      vup: self.vup,
+      vfov: self.vfov(),
+      aperture: self.aperture_diameter(),
+      focus_dist: self.focus_dist,
+      time0: self.time0,
+      time1: self.time1,
+      lookfrom1: self.lookfrom,
+      lookat1: self.lookat,
+      blades: match self.aperture {
+        Aperture::Polygon { blades, .. } => blades,
+        _ => 0
+      },
+      exposure: self.exposure.map_or(1.0, |exposure| exposure.scale(self.f_number)),
+      projection: Projection::Perspective
+    }
+  }
 
-pub struct Camera {
+  pub fn build(&self, aspect_ratio: f64) -> PerspectiveCamera {
+    self.settings().perspective(aspect_ratio).with_aperture(self.aperture.clone())
+  }
+}
+
+/// The camera's position and orientation at one time.
+#[derive(Clone, Copy)]
+struct Frame {
   origin: Point3,
   lower_left_corner: Point3,
   horizontal: Vec3,
   vertical: Vec3,
   u: Vec3,
   v: Vec3,
+  w: Vec3
+}
+
+impl Frame {
+  fn new(lookfrom: Point3, lookat: Point3, view: &View) -> Frame {
+    let w = (lookfrom - lookat).unit_vector();
+    let u = view.vup.cross(&w).unit_vector();
+    let v = w.cross(&u);
+
+    let origin = lookfrom;
+    let horizontal = u * (view.focus_dist * view.viewport_width);
+    let vertical = v * (view.focus_dist * view.viewport_height);
+
+    Frame {
+      origin,
+      horizontal,
+      vertical,
+      u,
+      v,
+      w,
+      lower_left_corner: origin - horizontal/2.0 - vertical/2.0 - w*view.focus_dist
+    }
+  }
+}
+
+/// What stays fixed while the camera moves.
+#[derive(Clone, Copy)]
+struct View {
+  vup: Vec3,
+  viewport_width: f64,
+  viewport_height: f64,
+  focus_dist: f64
+}
+
+/// A pinhole or thin-lens camera.
+pub struct PerspectiveCamera {
+  lookfrom: Point3,
+  lookat: Point3,
+  frame: Frame,
+  view: View,
+  /// Where the camera is and looks at `time1`, if it moves.
+  motion: Option<(Point3, Point3)>,
   lens_radius: f64,
+  aperture: Aperture,
+  exposure: f64,
   time0: f64,
   time1: f64
 }
 
-impl Camera {
+impl PerspectiveCamera {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     lookfrom: Point3,
     lookat: Point3,
@@ -28,48 +274,114 @@ impl Camera {
     focus_dist: f64,
     time0: f64,
     time1: f64,
-  ) -> Camera {
+  ) -> PerspectiveCamera {
     let theta = vfov.to_radians();
     let h = (theta/2.0).tan();
-    let viewport_heght = 2.0 * h;
-    let viewport_width = aspect_ratio * viewport_heght;
+    let viewport_height = 2.0 * h;
+    let view = View {
+      vup,
+      viewport_width: aspect_ratio * viewport_height,
+      viewport_height,
+      focus_dist
+    };
 
-    let w = (lookfrom - lookat).unit_vector();
-    let u = vup.cross(&w).unit_vector();
-    let v = w.cross(&u);
-
-    let origin = lookfrom;
-    let horizontal = u * (focus_dist * viewport_width);
-    let vertical = v * (focus_dist * viewport_heght);
-
-    Camera {
-      origin: origin,
-      horizontal: horizontal,
-      vertical: vertical,
-      u: u,
-      v: v,
-      lower_left_corner: {
-        origin - horizontal/2.0 - vertical/2.0 - w*focus_dist
-      },
+    PerspectiveCamera {
+      lookfrom,
+      lookat,
+      frame: Frame::new(lookfrom, lookat, &view),
+      view,
+      motion: None,
       lens_radius: aperture / 2.0,
+      aperture: Aperture::Circle,
+      exposure: 1.0,
       time0,
       time1
     }
   }
 
-  pub fn get_ray(&self, rng: &mut ThreadRng, s: f64, t: f64) -> Ray {
-    let rd = Vec3::random_in_unit_disk(rng) * self.lens_radius;
-    let offset = self.u * rd.x + self.v * rd.y;
+  /// Moves the camera in a straight line to `lookfrom1`, looking at
+  /// `lookat1`, by `time1`, which blurs everything that moves relative to it.
+  pub fn with_motion(mut self, lookfrom1: Point3, lookat1: Point3) -> PerspectiveCamera {
+    let moves = (lookfrom1 - self.lookfrom).length_squared() > 0.0 || (lookat1 - self.lookat).length_squared() > 0.0;
+    self.motion = if moves && self.time0 < self.time1 {
+      Some((lookfrom1, lookat1))
+    } else {
+      None
+    };
+    self
+  }
+
+  /// Shapes the lens opening, and so the out-of-focus highlights.
+  pub fn with_aperture(mut self, aperture: Aperture) -> PerspectiveCamera {
+    self.aperture = aperture;
+    self
+  }
+
+  /// Scales the radiance of every sample by `exposure`.
+  pub fn with_exposure(mut self, exposure: f64) -> PerspectiveCamera {
+    self.exposure = exposure;
+    self
+  }
+
+  fn frame_at(&self, time: f64) -> Frame {
+    match self.motion {
+      None => self.frame,
+      Some((lookfrom1, lookat1)) => {
+        let t = (time - self.time0) / (self.time1 - self.time0);
+        let lookfrom = self.lookfrom + (lookfrom1 - self.lookfrom) * t;
+        let lookat = self.lookat + (lookat1 - self.lookat) * t;
+        Frame::new(lookfrom, lookat, &self.view)
+      }
+    }
+  }
+}
+
+impl Camera for PerspectiveCamera {
+  fn get_ray(&self, sampler: &mut dyn Sampler, s: f64, t: f64) -> Ray {
+    let rd = self.aperture.sample(sampler) * self.lens_radius;
     let time =
         if self.time0 < self.time1 {
-          rng.gen_range(self.time0..self.time1)
+          sampler.next_range(self.time0..self.time1)
         } else {
           self.time0
         };
+    let frame = self.frame_at(time);
+    let offset = frame.u * rd.x + frame.v * rd.y;
     Ray {
-      origin: self.origin + offset,
-      direction: self.lower_left_corner + self.horizontal*s + self.vertical*t - self.origin - offset,
+      origin: frame.origin + offset,
+      direction: frame.lower_left_corner + frame.horizontal*s + frame.vertical*t - frame.origin - offset,
       time
     }
   }
+
+  fn exposure(&self) -> f64 {
+    self.exposure
+  }
+
+  fn sample_lens(&self, sampler: &mut dyn Sampler, p: &Point3, time: f64) -> Option<LensSample> {
+    let rd = self.aperture.sample(sampler) * self.lens_radius;
+    let frame = self.frame_at(time);
+    let point = frame.origin + frame.u * rd.x + frame.v * rd.y;
+    let direction = (*p - point).unit_vector();
+    let cosine = -direction.dot(&frame.w);
+    if cosine <= 0.0 {
+      return None;
+    }
+    // Where the ray crosses the plane in focus, which the image spans.
+    let focus = point + direction * (self.view.focus_dist / cosine) - frame.lower_left_corner;
+    Some(LensSample {
+      point,
+      s: focus.dot(&frame.horizontal) / frame.horizontal.length_squared(),
+      t: focus.dot(&frame.vertical) / frame.vertical.length_squared(),
+      pdf: self.direction_pdf(&direction, time)
+    })
+  }
+
+  fn direction_pdf(&self, direction: &Vec3, time: f64) -> f64 {
+    let cosine = -direction.unit_vector().dot(&self.frame_at(time).w);
+    if cosine <= 0.0 {
+      return 0.0;
+    }
+    1.0 / (self.view.viewport_width * self.view.viewport_height * cosine * cosine * cosine)
+  }
 }
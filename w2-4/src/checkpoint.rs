@@ -0,0 +1,176 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::camera::CameraSettings;
+use crate::framebuffer::Accumulator;
+use crate::render::RenderSettings;
+use crate::vec3::Color;
+use crate::wire::{camera_words, read_camera, read_settings, read_u64, read_vec3, write_camera, write_settings, write_u64, write_vec3};
+
+const MAGIC: &[u8; 8] = b"WKCKPT5\n";
+/// Three little-endian f64s per pixel.
+const PIXEL_BYTES: usize = 24;
+
+/// The state of a progressive render, enough to continue it later with the
+/// same result as if it had never stopped. The random streams of each pixel
+/// are fully determined by the seed and the pass index, so those stand in
+/// for the RNG state.
+pub struct Checkpoint {
+    /// Identifies the scene description; see `scene_hash`.
+    pub scene_hash: u64,
+    pub settings: RenderSettings,
+    pub camera: CameraSettings,
+    pub accumulator: Accumulator,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    /// Not a checkpoint file, or a truncated one.
+    Format(String),
+    /// The checkpoint belongs to a different render.
+    Mismatch(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "{}", e),
+            CheckpointError::Format(message) => write!(f, "invalid checkpoint: {}", message),
+            CheckpointError::Mismatch(message) => write!(f, "cannot resume: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            CheckpointError::Format("file is truncated".to_string())
+        } else {
+            CheckpointError::Io(e)
+        }
+    }
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint next to `path` and renames it into place, so an
+    /// older checkpoint survives a crash while saving.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let mut out = BufWriter::new(File::create(&partial)?);
+        self.write(&mut out)?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Checkpoint, CheckpointError> {
+        Checkpoint::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Little-endian: magic, scene hash, settings, camera, sample and pass
+    /// counts, then the per-pixel RGB sums row by row from the top. Every
+    /// pixel has the same sample count, so it is stored once.
+    pub fn write<W: Write>(&self, out: &mut W) -> Result<(), CheckpointError> {
+        out.write_all(MAGIC)?;
+        write_u64(out, self.scene_hash)?;
+        write_settings(out, &self.settings)?;
+        write_camera(out, &self.camera)?;
+        write_u64(out, self.accumulator.samples)?;
+        write_u64(out, self.accumulator.passes)?;
+        for sum in self.accumulator.sums.pixels.iter() {
+            write_vec3(out, sum)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(input: &mut R) -> Result<Checkpoint, CheckpointError> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::Format("not a checkpoint file".to_string()));
+        }
+        let scene_hash = read_u64(input)?;
+        let settings = read_settings(input)?;
+        let (width, height) = (settings.image_width as usize, settings.image_height as usize);
+        let len = match width.checked_mul(height).and_then(|pixels| pixels.checked_mul(PIXEL_BYTES)) {
+            Some(len) if settings.image_width > 0 && settings.image_height > 0 => len,
+            _ => return Err(CheckpointError::Format("bad image size".to_string())),
+        };
+        let camera = read_camera(input)?;
+        let samples = read_u64(input)?;
+        let passes = read_u64(input)?;
+
+        // The pixels are read before anything is allocated for them, so a
+        // damaged header cannot ask for more memory than the file holds.
+        let mut pixels = Vec::new();
+        input.take(len as u64).read_to_end(&mut pixels)?;
+        if pixels.len() < len {
+            return Err(CheckpointError::Format("file is truncated".to_string()));
+        }
+        let mut accumulator = Accumulator::new(width, height);
+        accumulator.samples = samples;
+        accumulator.passes = passes;
+        let mut pixels = &pixels[..];
+        for sum in accumulator.sums.pixels.iter_mut() {
+            *sum = read_vec3(&mut pixels)?;
+        }
+        if input.read(&mut [0])? != 0 {
+            return Err(CheckpointError::Format("trailing data".to_string()));
+        }
+
+        Ok(Checkpoint {
+            scene_hash,
+            settings,
+            camera,
+            accumulator,
+        })
+    }
+
+    /// Checks that continuing this checkpoint renders the same image as a
+    /// fresh render of `scene_hash` with `settings` and `camera` would. Only
+    /// the target sample count may differ.
+    pub fn check_resumable(&self, scene_hash: u64, settings: &RenderSettings, camera: &CameraSettings) -> Result<(), CheckpointError> {
+        let mismatch = |what: &str| Err(CheckpointError::Mismatch(format!("the checkpoint was rendered with a different {}", what)));
+        let s = &self.settings;
+        if self.scene_hash != scene_hash {
+            return mismatch("scene");
+        }
+        if camera_words(&self.camera).map(f64::to_bits) != camera_words(camera).map(f64::to_bits) {
+            return mismatch("camera");
+        }
+        if (s.image_width, s.image_height) != (settings.image_width, settings.image_height) {
+            return mismatch("image size");
+        }
+        if s.max_depth != settings.max_depth || !same_color(&s.background, &settings.background) {
+            return mismatch("depth or background");
+        }
+        if s.seed != settings.seed {
+            return mismatch("seed");
+        }
+        if s.integrator != settings.integrator {
+            return mismatch("integrator");
+        }
+        Ok(())
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is guaranteed to stay the same
+/// across Rust releases.
+pub fn scene_hash(bytes: &[u8]) -> u64 {
+    let mut h = 0xcbf2_9ce4_8422_2325u64;
+    for &b in bytes {
+        h = (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h
+}
+
+fn same_color(a: &Color, b: &Color) -> bool {
+    [a.x, a.y, a.z].map(f64::to_bits) == [b.x, b.y, b.z].map(f64::to_bits)
+}
@@ -1,19 +1,21 @@
+use std::sync::Arc;
 use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::Vec3;
 
 
 #[derive(Clone)]
 pub struct ConstantMedium {
-    boundary: Box<dyn Hittable>,
-    phase_function: Box<dyn Material>,
+    boundary: Arc<dyn Hittable>,
+    phase_function: Arc<dyn Material>,
     neg_inv_density: f64,
 }
 
 impl ConstantMedium {
-    pub fn new(b: Box<dyn Hittable>, d: f64, a: Box<dyn Material>) -> ConstantMedium {
+    pub fn new(b: Arc<dyn Hittable>, d: f64, a: Arc<dyn Material>) -> ConstantMedium {
         ConstantMedium {
             boundary: b,
             phase_function: a,
@@ -23,18 +25,9 @@ impl ConstantMedium {
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut rec1 = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY);
-        if rec1.is_none() {
-            return None;
-        }
-        let rec1 = rec1?;
-
-        let mut rec2 = self.boundary.hit(r, rec1.t + 0.0001, f64::INFINITY);
-        if rec2.is_none() {
-            return None;
-        }
-        let rec2 = rec2?;
+    fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let rec1 = self.boundary.hit(sampler, r, f64::NEG_INFINITY, f64::INFINITY)?;
+        let rec2 = self.boundary.hit(sampler, r, rec1.t + 0.0001, f64::INFINITY)?;
 
         let rec1t = if rec1.t < t_min { t_min } else { rec1.t };
         let rec2t = if rec2.t > t_max { t_max } else { rec2.t };
@@ -47,7 +40,7 @@ impl Hittable for ConstantMedium {
 
         let ray_length = r.direction.length();
         let distance_inside_boundary = (rec2t - rec1t) * ray_length;
-        let hit_distance = self.neg_inv_density * rand::random::<f64>().ln();
+        let hit_distance = self.neg_inv_density * (1.0 - sampler.next_f64()).ln();
 
         if hit_distance > distance_inside_boundary {
             return None;
@@ -0,0 +1,477 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::camera::{Camera, CameraSettings};
+use crate::framebuffer::Framebuffer;
+use crate::render::{RenderSettings, Renderer, Tile};
+use crate::scene::Scene;
+use crate::vec3::Color;
+use crate::wire::{
+    read_camera, read_settings, read_str, read_u32, read_vec3, write_camera, write_settings, write_str, write_u32,
+    write_vec3,
+};
+
+const MAGIC: &[u8; 8] = b"WKDIST5\n";
+
+// Message tags. Every message is a tag byte, a u32 payload length and the
+// payload.
+const JOB: u8 = 1;
+const READY: u8 = 2;
+const FAILED: u8 = 3;
+const TILE: u8 = 4;
+const RESULT: u8 = 5;
+const DONE: u8 = 6;
+
+/// The largest payload either side accepts, far more than the pixels of a
+/// 1024 by 1024 tile.
+const MAX_MESSAGE: usize = 256 << 20;
+
+// Once no tile is pending, idle workers render copies of tiles that have been
+// out for `OVERDUE_MEDIANS` times the median tile time, and at least
+// `MIN_OVERDUE`.
+const OVERDUE_MEDIANS: u32 = 4;
+const MIN_OVERDUE: Duration = Duration::from_millis(50);
+/// How often idle workers look for overdue tiles.
+const OVERDUE_POLL: Duration = Duration::from_millis(20);
+
+/// What a worker needs to render tiles of the coordinator's image.
+#[derive(Clone)]
+pub struct Job {
+    /// A built-in scene name or a path to a scene file, loaded by each worker.
+    /// Workers build procedural scenes from `settings.seed`.
+    pub scene: String,
+    pub settings: RenderSettings,
+    pub camera: CameraSettings,
+}
+
+/// Reported by `Coordinator::render` as the render goes on.
+pub enum Event {
+    WorkerJoined(SocketAddr),
+    /// The worker is gone; its unfinished tile, if any, goes back in the queue
+    /// unless another worker has a copy.
+    WorkerLeft { addr: SocketAddr, reason: String },
+    TileDone { done: usize, total: usize },
+}
+
+#[derive(Debug)]
+pub enum DistError {
+    Io(io::Error),
+    Protocol(String),
+    /// A worker could not load the job's scene.
+    Scene(String),
+    /// The peer sent nothing for longer than the coordinator's tile timeout.
+    Timeout,
+}
+
+impl fmt::Display for DistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DistError::Io(e) => write!(f, "{}", e),
+            DistError::Protocol(message) => write!(f, "protocol error: {}", message),
+            DistError::Scene(message) => write!(f, "cannot load scene: {}", message),
+            DistError::Timeout => write!(f, "timed out"),
+        }
+    }
+}
+
+impl std::error::Error for DistError {}
+
+impl From<io::Error> for DistError {
+    fn from(e: io::Error) -> Self {
+        DistError::Io(e)
+    }
+}
+
+/// Splits the image into tiles and hands them out to the workers that
+/// connect, one tile per worker at a time. Tiles of workers that disconnect
+/// are handed to the next free worker, and once none is left to hand out,
+/// free workers also render copies of overdue tiles; the first copy back
+/// wins.
+pub struct Coordinator {
+    listener: TcpListener,
+    tile_size: usize,
+    tile_timeout: Option<Duration>,
+}
+
+struct Queue {
+    tiles: Vec<Tile>,
+    /// Indices of the tiles no worker has.
+    pending: VecDeque<usize>,
+    finished: Vec<bool>,
+    /// How many workers have each tile, and when the last of them got it.
+    copies: Vec<usize>,
+    issued: Vec<Instant>,
+    done: usize,
+    fb: Framebuffer,
+    /// How long each tile that came back took, in no particular order.
+    tile_times: Vec<Duration>,
+}
+
+impl Queue {
+    fn median_tile_time(&self) -> Option<Duration> {
+        let mut times = self.tile_times.clone();
+        times.sort();
+        times.get(times.len() / 2).copied()
+    }
+
+    /// The index of the next tile to render, if any is pending or overdue.
+    fn take(&mut self) -> Option<usize> {
+        let index = match self.pending.pop_front() {
+            Some(index) => index,
+            None => {
+                let overdue = (self.median_tile_time()? * OVERDUE_MEDIANS).max(MIN_OVERDUE);
+                (0..self.tiles.len())
+                    .filter(|&i| !self.finished[i] && self.issued[i].elapsed() >= overdue)
+                    .min_by_key(|&i| self.issued[i])?
+            }
+        };
+        self.copies[index] += 1;
+        self.issued[index] = Instant::now();
+        Some(index)
+    }
+
+    /// Gives up one copy of a tile, putting it back in the queue if it was
+    /// the last.
+    fn give_back(&mut self, index: usize) {
+        self.copies[index] -= 1;
+        if self.copies[index] == 0 && !self.finished[index] {
+            self.pending.push_front(index);
+        }
+    }
+}
+
+impl Coordinator {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Coordinator> {
+        Ok(Coordinator {
+            listener: TcpListener::bind(addr)?,
+            tile_size: 32,
+            tile_timeout: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn with_tile_size(mut self, size: usize) -> Coordinator {
+        self.tile_size = size.max(1);
+        self
+    }
+
+    /// Drops workers that take longer than `timeout` to load the scene or to
+    /// return a tile. By default workers are only dropped when their
+    /// connection closes, however slow they are, and tiles of workers that
+    /// hang are left to the copies rendered by the others.
+    pub fn with_tile_timeout(mut self, timeout: Duration) -> Coordinator {
+        self.tile_timeout = Some(timeout);
+        self
+    }
+
+    /// Renders `job` with whichever workers connect, returning once every
+    /// tile is back.
+    pub fn render<F: Fn(Event) + Sync>(&self, job: &Job, on_event: F) -> io::Result<Framebuffer> {
+        let width = job.settings.image_width as usize;
+        let height = job.settings.image_height as usize;
+        let tiles = Tile::grid(width, height, self.tile_size);
+        let total = tiles.len();
+        let queue = Mutex::new(Queue {
+            pending: (0..total).collect(),
+            finished: vec![false; total],
+            copies: vec![0; total],
+            issued: vec![Instant::now(); total],
+            tiles,
+            done: 0,
+            fb: Framebuffer::new(width, height),
+            tile_times: Vec::new(),
+        });
+        let changed = Condvar::new();
+        // Workers still rendering copies of finished tiles are cut off at the
+        // end instead of being waited for.
+        let streams = Mutex::new(Vec::new());
+
+        self.listener.set_nonblocking(true)?;
+        thread::scope(|scope| {
+            while queue.lock().unwrap().done < total {
+                match self.listener.accept() {
+                    Ok((stream, addr)) => {
+                        streams.lock().unwrap().push(stream.try_clone()?);
+                        let (queue, changed, on_event) = (&queue, &changed, &on_event);
+                        scope.spawn(move || {
+                            let reason = match self.serve(stream, addr, job, queue, changed, on_event) {
+                                Ok(()) => "finished".to_string(),
+                                Err(_) if queue.lock().unwrap().done == total => "cut off at the end".to_string(),
+                                Err(e) => e.to_string(),
+                            };
+                            on_event(Event::WorkerLeft { addr, reason });
+                        });
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(20)),
+                    Err(e) => return Err(e),
+                }
+            }
+            for stream in streams.lock().unwrap().iter() {
+                // Fails for workers that are already gone.
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            Ok(())
+        })?;
+
+        Ok(queue.into_inner().unwrap().fb)
+    }
+
+    fn serve<F: Fn(Event)>(
+        &self,
+        stream: TcpStream,
+        addr: SocketAddr,
+        job: &Job,
+        queue: &Mutex<Queue>,
+        changed: &Condvar,
+        on_event: &F,
+    ) -> Result<(), DistError> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(self.tile_timeout)?;
+        let mut connection = Connection::new(stream)?;
+
+        let mut payload = MAGIC.to_vec();
+        write_str(&mut payload, &job.scene)?;
+        write_settings(&mut payload, &job.settings)?;
+        write_camera(&mut payload, &job.camera)?;
+        connection.send(JOB, &payload)?;
+        match connection.receive()? {
+            (READY, _) => {}
+            (FAILED, payload) => return Err(DistError::Scene(read_str(&mut Cursor::new(payload))?)),
+            (tag, _) => return Err(unexpected(tag)),
+        }
+        on_event(Event::WorkerJoined(addr));
+
+        loop {
+            // Tiles are pending, with one or more workers or done; when none
+            // is pending a worker waits for one to come back or to be overdue.
+            let (index, tile) = {
+                let mut queue = queue.lock().unwrap();
+                let index = loop {
+                    if queue.done == queue.tiles.len() {
+                        break None;
+                    }
+                    if let Some(index) = queue.take() {
+                        break Some(index);
+                    }
+                    queue = changed.wait_timeout(queue, OVERDUE_POLL).unwrap().0;
+                };
+                match index {
+                    Some(index) => (index, queue.tiles[index]),
+                    None => {
+                        drop(queue);
+                        connection.send(DONE, &[])?;
+                        return Ok(());
+                    }
+                }
+            };
+
+            let start = Instant::now();
+            let result = connection.render(&tile);
+            let mut queue = queue.lock().unwrap();
+            changed.notify_all();
+            let pixels = match result {
+                Ok(pixels) => pixels,
+                Err(e) => {
+                    queue.give_back(index);
+                    return Err(e);
+                }
+            };
+            queue.copies[index] -= 1;
+            queue.tile_times.push(start.elapsed());
+            if queue.finished[index] {
+                continue;
+            }
+            for (i, pixel) in pixels.into_iter().enumerate() {
+                queue.fb.set(tile.x + i % tile.width, tile.y + i / tile.width, pixel);
+            }
+            queue.finished[index] = true;
+            queue.done += 1;
+            let (done, total) = (queue.done, queue.tiles.len());
+            drop(queue);
+            on_event(Event::TileDone { done, total });
+        }
+    }
+}
+
+/// Renders tiles for a `Coordinator`.
+pub struct Worker {
+    connection: Connection,
+    scene: Scene,
+    renderer: Renderer<'static>,
+    camera: Box<dyn Camera>,
+}
+
+impl Worker {
+    /// Connects to a coordinator and loads its job's scene with `load`, which
+    /// may return an error message.
+    pub fn connect<A, F>(addr: A, load: F) -> Result<Worker, DistError>
+    where
+        A: ToSocketAddrs,
+        F: FnOnce(&Job) -> Result<Scene, String>,
+    {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut connection = Connection::new(stream)?;
+
+        let job = match connection.receive()? {
+            (JOB, payload) => {
+                let mut input = Cursor::new(payload);
+                let mut magic = [0; 8];
+                input.read_exact(&mut magic)?;
+                if &magic != MAGIC {
+                    return Err(DistError::Protocol("not a weekend coordinator".to_string()));
+                }
+                Job {
+                    scene: read_str(&mut input)?,
+                    settings: read_settings(&mut input)?,
+                    camera: read_camera(&mut input)?,
+                }
+            }
+            (tag, _) => return Err(unexpected(tag)),
+        };
+
+        let mut scene = match load(&job) {
+            Ok(scene) => scene,
+            Err(message) => {
+                let mut payload = Vec::new();
+                write_str(&mut payload, &message)?;
+                connection.send(FAILED, &payload)?;
+                return Err(DistError::Scene(message));
+            }
+        };
+        scene.settings = job.settings;
+        scene.camera = job.camera;
+        let renderer = scene.renderer();
+        let camera = scene.camera();
+        connection.send(READY, &[])?;
+
+        Ok(Worker {
+            connection,
+            scene,
+            renderer,
+            camera,
+        })
+    }
+
+    /// Waits for the next tile; `None` once the coordinator is done.
+    pub fn next_tile(&mut self) -> Result<Option<Tile>, DistError> {
+        match self.connection.receive()? {
+            (TILE, payload) => {
+                let mut input = Cursor::new(payload);
+                let mut read = || read_u32(&mut input).map(|v| v as usize);
+                Ok(Some(Tile {
+                    x: read()?,
+                    y: read()?,
+                    width: read()?,
+                    height: read()?,
+                }))
+            }
+            (DONE, _) => Ok(None),
+            (tag, _) => Err(unexpected(tag)),
+        }
+    }
+
+    pub fn render(&self, tile: &Tile) -> Framebuffer {
+        self.renderer.render_tile(&self.scene.world, &*self.camera, tile)
+    }
+
+    pub fn send(&mut self, fb: &Framebuffer) -> Result<(), DistError> {
+        let mut payload = Vec::with_capacity(fb.pixels.len() * 24);
+        for pixel in fb.pixels.iter() {
+            write_vec3(&mut payload, pixel)?;
+        }
+        self.connection.send(RESULT, &payload)
+    }
+
+    /// Renders tiles until the coordinator is done, returning how many.
+    pub fn run(mut self) -> Result<usize, DistError> {
+        let mut count = 0;
+        while let Some(tile) = self.next_tile()? {
+            let fb = self.render(&tile);
+            self.send(&fb)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Connection> {
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    fn send(&mut self, tag: u8, payload: &[u8]) -> Result<(), DistError> {
+        if payload.len() > MAX_MESSAGE {
+            return Err(DistError::Protocol("message too large".to_string()));
+        }
+        self.writer.write_all(&[tag])?;
+        write_u32(&mut self.writer, payload.len() as u32)?;
+        self.writer.write_all(payload)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<(u8, Vec<u8>), DistError> {
+        let mut tag = [0];
+        self.reader.read_exact(&mut tag).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => DistError::Protocol("connection closed".to_string()),
+            _ => read_error(e),
+        })?;
+        let len = read_u32(&mut self.reader).map_err(read_error)? as usize;
+        if len > MAX_MESSAGE {
+            return Err(DistError::Protocol("message too large".to_string()));
+        }
+        let mut payload = vec![0; len];
+        self.reader.read_exact(&mut payload).map_err(read_error)?;
+        Ok((tag[0], payload))
+    }
+
+    /// Sends a tile to the worker and waits for its pixels.
+    fn render(&mut self, tile: &Tile) -> Result<Vec<Color>, DistError> {
+        let mut payload = Vec::new();
+        for v in [tile.x, tile.y, tile.width, tile.height] {
+            write_u32(&mut payload, v as u32)?;
+        }
+        self.send(TILE, &payload)?;
+        let payload = match self.receive()? {
+            (RESULT, payload) => payload,
+            (tag, _) => return Err(unexpected(tag)),
+        };
+        if payload.len() != tile.width * tile.height * 24 {
+            return Err(DistError::Protocol("tile has the wrong size".to_string()));
+        }
+        let mut input = Cursor::new(payload);
+        (0..tile.width * tile.height)
+            .map(|_| read_vec3(&mut input).map_err(DistError::Io))
+            .collect()
+    }
+}
+
+/// Read timeouts show up as `WouldBlock` on Unix and `TimedOut` on Windows.
+fn read_error(e: io::Error) -> DistError {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => DistError::Timeout,
+        _ => DistError::Io(e),
+    }
+}
+
+fn unexpected(tag: u8) -> DistError {
+    DistError::Protocol(format!("unexpected message {}", tag))
+}
@@ -0,0 +1,150 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use crate::vec3::Color;
+
+/// Linear radiance for every pixel, stored row by row from the top of the
+/// image.
+#[derive(Clone)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::black(); width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[x + self.width * y]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[x + self.width * y] = color;
+    }
+}
+
+/// Running per-pixel radiance sums of a progressive render. Every pass adds
+/// the same number of samples to each pixel.
+#[derive(Clone)]
+pub struct Accumulator {
+    pub sums: Framebuffer,
+    /// Samples per pixel so far.
+    pub samples: u64,
+    /// Passes so far; also the index of the next pass.
+    pub passes: u64,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Accumulator {
+        Accumulator {
+            sums: Framebuffer::new(width, height),
+            samples: 0,
+            passes: 0,
+        }
+    }
+
+    /// Adds a pass holding the average of `samples` samples per pixel.
+    pub fn add(&mut self, pass: &Framebuffer, samples: u64) {
+        for (sum, pixel) in self.sums.pixels.iter_mut().zip(pass.pixels.iter()) {
+            *sum = *sum + *pixel * samples as f64;
+        }
+        self.samples += samples;
+        self.passes += 1;
+    }
+
+    /// The average of all passes so far; black before the first one.
+    pub fn image(&self) -> Framebuffer {
+        let scale = 1.0 / self.samples.max(1) as f64;
+        Framebuffer {
+            width: self.sums.width,
+            height: self.sums.height,
+            pixels: self.sums.pixels.iter().map(|sum| *sum * scale).collect(),
+        }
+    }
+}
+
+/// Light splatted onto the image at arbitrary points by many threads at
+/// once, as light tracing does. Sums are kept in fixed point, so they come
+/// out the same whatever order the threads add in.
+///
+/// Each splat is rounded to a multiple of 2^-48, about 3.6e-15, and clamped
+/// to 2^46, about 7e13. A sum holds at least 2^32 splats and saturates at
+/// 2^47 rather than wrapping around; only saturated sums depend on the order
+/// of the splats.
+pub struct Film {
+    width: usize,
+    height: usize,
+    /// The high and low words of every sum: the low `LOW_BITS` bits of each
+    /// splat go to the low word, the rest to the high one. Kept apart, the
+    /// two need no carry between them.
+    sums: Vec<(AtomicI64, AtomicU64)>,
+}
+
+/// Fractional bits of a splat.
+const FILM_BITS: i32 = 48;
+const LOW_BITS: u32 = 32;
+const MAX_SPLAT: f64 = (1u64 << 46) as f64;
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Film {
+        Film {
+            width,
+            height,
+            sums: (0..width * height * 3).map(|_| (AtomicI64::new(0), AtomicU64::new(0))).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Adds light at image point `(s, t)`, in the coordinates `Camera::get_ray`
+    /// takes. `color` is per unit of that image area, as one light path finds
+    /// it; the renderer traces one with every camera sample. Points off the
+    /// image are dropped.
+    pub fn splat(&self, s: f64, t: f64, color: Color) {
+        let finite = color.x.is_finite() && color.y.is_finite() && color.z.is_finite();
+        if !(s >= 0.0 && t >= 0.0 && finite) {
+            return;
+        }
+        let x = (s * (self.width - 1) as f64) as usize;
+        let j = (t * (self.height - 1) as f64) as usize;
+        if x >= self.width || j >= self.height {
+            return;
+        }
+        // Samples of a pixel cover 1/((width-1)(height-1)) of the image area,
+        // and the renderer divides by the samples per pixel, which is the
+        // light paths traced divided by the number of pixels.
+        let scale = ((self.width - 1) * (self.height - 1)) as f64 / (self.width * self.height) as f64;
+        let i = 3 * (x + self.width * (self.height - 1 - j));
+        for ((high, low), c) in self.sums[i..i + 3].iter().zip([color.x, color.y, color.z]) {
+            let fixed = ((c * scale).clamp(-MAX_SPLAT, MAX_SPLAT) * 2f64.powi(FILM_BITS)).round() as i128;
+            let carry = (fixed >> LOW_BITS) as i64;
+            // Two splats of the largest size already fill the high word.
+            let _ = high.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |h| Some(h.saturating_add(carry)));
+            low.fetch_add((fixed & ((1 << LOW_BITS) - 1)) as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// The light splatted so far into the pixel at column `x` and row `y`,
+    /// counting rows from the top, as a sum over the pixel's samples.
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        let i = 3 * (x + self.width * y);
+        let c = |k: usize| {
+            let (high, low) = &self.sums[i + k];
+            let fixed = ((high.load(Ordering::Relaxed) as i128) << LOW_BITS) + low.load(Ordering::Relaxed) as i128;
+            fixed as f64 * 2f64.powi(-FILM_BITS)
+        };
+        Color::new(c(0), c(1), c(2))
+    }
+}
@@ -3,6 +3,7 @@ use crate::ray::Ray;
 use crate::vec3::Vec3;
 use crate::vec3::Point3;
 use crate::material::Material;
+use crate::sampler::Sampler;
 
 pub struct HitRecord<'a> {
   pub p: Point3,
@@ -25,27 +26,30 @@ impl HitRecord<'_> {
   }
 }
 
-pub trait CloneHittable {
-  fn clone_box(&self) -> Box<dyn Hittable>;
-}
-pub trait Hittable: Sync + CloneHittable {
-  fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+  /// The closest hit along `r` between `t_min` and `t_max`. Objects that
+  /// are hit at random, like participating media, draw from `sampler`.
+  fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
   fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb>;
 
-}
+  /// Solid-angle density of `random` choosing direction `v` from `o`.
+  fn pdf_value(&self, _: &Point3, _: &Vec3) -> f64 {
+    0.0
+  }
 
+  /// A direction from `o` towards a random point on the object.
+  fn random(&self, _: &mut dyn Sampler, _: &Point3) -> Vec3 {
+    Vec3::new(1.0, 0.0, 0.0)
+  }
 
-impl<T> CloneHittable for T
-  where
-      T: 'static + Hittable + Clone,
-{
-  fn clone_box(&self) -> Box<dyn Hittable> {
-    Box::new(self.clone())
+  /// A point picked uniformly on the object's surface, for light paths to
+  /// start from: a hit with `t` of 0 and the outward normal.
+  fn sample_surface(&self, _: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+    None
   }
-}
 
-impl Clone for Box<dyn Hittable> {
-  fn clone(&self) -> Box<dyn Hittable> {
-    self.clone_box()
+  /// The area `sample_surface` picks from; 0 if it picks nothing.
+  fn area(&self) -> f64 {
+    0.0
   }
 }
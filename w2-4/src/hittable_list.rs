@@ -1,11 +1,20 @@
+use std::sync::Arc;
 use crate::aabb::{Aabb, surrounding_box};
 use crate::ray::Ray;
 use crate::hittable::Hittable;
 use crate::hittable::HitRecord;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
 
 #[derive(Clone)]
 pub struct HittableList {
-  pub objects: Vec<Box<dyn Hittable>>
+  pub objects: Vec<Arc<dyn Hittable>>
+}
+
+impl Default for HittableList {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 
 impl HittableList {
@@ -17,18 +26,18 @@ impl HittableList {
     self.objects.clear();
   }
 
-  pub fn add(&mut self, object: Box<dyn Hittable>) {
+  pub fn add(&mut self, object: Arc<dyn Hittable>) {
     self.objects.push(object)
   }
 }
 
 impl Hittable for HittableList {
-  fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+  fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
     let mut temp_rec: Option<HitRecord> = None;
     let mut closest_so_far = t_max;
     let objects = &(self.objects);
-    for object in objects.into_iter() {
-      match object.hit(r, t_min, closest_so_far) {
+    for object in objects.iter() {
+      match object.hit(sampler, r, t_min, closest_so_far) {
         None => {}
         Some(hit_rec) => {
           closest_so_far = hit_rec.t;
@@ -43,7 +52,7 @@ impl Hittable for HittableList {
     if self.objects.is_empty() { return None };
     let mut output_box: Option<Aabb> = None;
 
-    for object in <Vec<Box<dyn Hittable>> as Clone>::clone(&self.objects).into_iter() {
+    for object in self.objects.iter() {
       match object.bounding_box(t0, t1) {
         None => return None,
         Some(temp_box) => {
@@ -56,4 +65,15 @@ impl Hittable for HittableList {
     }
     output_box
   }
+
+  fn pdf_value(&self, o: &Point3, v: &Vec3) -> f64 {
+    if self.objects.is_empty() { return 0.0 };
+    let weight = 1.0 / self.objects.len() as f64;
+    self.objects.iter().map(|object| weight * object.pdf_value(o, v)).sum()
+  }
+
+  fn random(&self, sampler: &mut dyn Sampler, o: &Point3) -> Vec3 {
+    let index = sampler.next_index(self.objects.len());
+    self.objects[index].random(sampler, o)
+  }
 }
@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use crate::sampler::Sampler;
+
+/// One placement of a shared object: scaled, rotated about the y axis and
+/// moved, in that order, optionally with its own material. An instance only
+/// holds a reference to the object, so placing one mesh thousands of times
+/// costs little more than placing it once.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    material: Option<Arc<dyn Material>>,
+    scale: f64,
+    sin_theta: f64,
+    cos_theta: f64,
+    offset: Vec3,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>) -> Instance {
+        Instance {
+            object,
+            material: None,
+            scale: 1.0,
+            sin_theta: 0.0,
+            cos_theta: 1.0,
+            offset: Vec3::zero(),
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Instance {
+        self.scale = scale;
+        self
+    }
+
+    /// Rotates counterclockwise by `angle` degrees, looking down the y axis.
+    pub fn with_rotation_y(mut self, angle: f64) -> Instance {
+        let (sin_theta, cos_theta) = angle.to_radians().sin_cos();
+        self.sin_theta = sin_theta;
+        self.cos_theta = cos_theta;
+        self
+    }
+
+    pub fn with_offset(mut self, offset: Vec3) -> Instance {
+        self.offset = offset;
+        self
+    }
+
+    /// Replaces the material of every part of the object.
+    pub fn with_material(mut self, material: Arc<dyn Material>) -> Instance {
+        self.material = Some(material);
+        self
+    }
+
+    /// Rotation about y, then scale: object to world without the offset.
+    fn to_world(&self, v: &Vec3) -> Vec3 {
+        self.scale
+            * Vec3::new(
+                self.cos_theta * v.x + self.sin_theta * v.z,
+                v.y,
+                -self.sin_theta * v.x + self.cos_theta * v.z,
+            )
+    }
+
+    fn to_object(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x - self.sin_theta * v.z,
+            v.y,
+            self.sin_theta * v.x + self.cos_theta * v.z,
+        ) / self.scale
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // Scaling the direction along with the origin keeps `t` the same in
+        // both spaces.
+        let local = Ray::new(self.to_object(&(r.origin - self.offset)), self.to_object(&r.direction), r.time);
+        let mut rec = self.object.hit(sampler, &local, t_min, t_max)?;
+        rec.p = self.to_world(&rec.p) + self.offset;
+        rec.normal = self.to_world(&rec.normal).unit_vector();
+        if let Some(material) = &self.material {
+            rec.mat_ptr = &**material;
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
+        let bbox = self.object.bounding_box(t0, t1)?;
+        let mut min = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for corner in 0..8 {
+            let p = Point3::new(
+                if corner & 1 == 0 { bbox.min.x } else { bbox.max.x },
+                if corner & 2 == 0 { bbox.min.y } else { bbox.max.y },
+                if corner & 4 == 0 { bbox.min.z } else { bbox.max.z },
+            );
+            let p = self.to_world(&p) + self.offset;
+            min = Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+        Some(Aabb::new(min, max))
+    }
+}
@@ -4,13 +4,38 @@ pub mod sphere;
 pub mod vec3;
 pub mod hittable_list;
 pub mod camera;
+pub mod aperture;
+pub mod projection;
 pub mod material;
 pub mod aabb;
 pub mod bvh_node;
+pub mod bvh;
 pub mod texture;
 pub mod perlin;
 pub mod rect;
 pub mod box_model;
 pub mod translate;
+pub mod transform;
+pub mod transformed;
+pub mod animated;
+pub mod timeline;
+pub mod instance;
 pub mod rotate;
 pub mod constant_medium;
+pub mod scene;
+pub mod builtin_scenes;
+pub mod framebuffer;
+pub mod output;
+pub mod render;
+pub mod path;
+pub mod bdpt;
+pub mod photon;
+pub mod mlt;
+pub mod sampler;
+pub mod onb;
+pub mod pdf;
+pub mod triangle;
+pub mod obj;
+pub mod checkpoint;
+pub mod distributed;
+mod wire;
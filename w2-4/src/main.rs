@@ -1,447 +1,787 @@
-use std::sync::mpsc;
-use std::sync::Mutex;
-use rand::Rng;
-use rand::rngs::ThreadRng;
-use rayon::iter::IntoParallelIterator;
-use rayon::prelude::ParallelIterator;
-use weekend::bvh_node::BvhNode;
-use weekend::ray::Ray;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap::error::ErrorKind;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use weekend::builtin_scenes;
+use weekend::checkpoint::{self, Checkpoint};
+use weekend::distributed::{Coordinator, Event, Job, Worker};
+use weekend::framebuffer::Accumulator;
+use weekend::output::{self, ImageFormat};
+use weekend::mlt::MltSettings;
+use weekend::photon::PhotonSettings;
+use weekend::projection::Projection;
+use weekend::render::{IntegratorKind, Progressive, Renderer};
 use weekend::vec3::Vec3;
-use weekend::vec3::Color;
-use weekend::hittable::{CloneHittable, Hittable};
-use weekend::hittable_list::HittableList;
-use weekend::sphere::{MovingSphere, Sphere};
-use weekend::material::{DiffuseLight, IsoTropic, Lambertian};
-use weekend::camera::Camera;
-use weekend::material::Metal;
-use weekend::material::Dielactric;
-use weekend::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor};
-use weekend::rect::{XyRect, XzRect, YzRect};
-use weekend::box_model::BoxModel;
-use weekend::constant_medium::ConstantMedium;
-use weekend::rotate::RotateY;
-use weekend::translate::Translate;
-
-fn ray_color(rng: &mut ThreadRng, r: &Ray, background: &Color, world: &dyn Hittable, depth: i32) -> Vec3 {
-  if depth <= 0 {
-    return Color::black();
-  }
-
-  match world.hit(r, 0.001, f64::INFINITY) {
-    None => {
-      return *background;
-    },
-    Some(rec) => {
-      let emitted = rec.mat_ptr.emitted(rec.u, rec.v, &rec.p);
-      match rec.mat_ptr.scatter(rng, r, &rec) {
-        None => {
-          return emitted;
-        }
-        Some((attenuation, scattered)) => {
-          return emitted + attenuation * ray_color(rng, &scattered, background, world, depth-1);
-        }
-      }
-    }
-  }
+use weekend::scene::Scene;
+use weekend::timeline::Timeline;
+
+/// Renders one of the book's scenes, or a TOML scene file, with the ray tracer.
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+  /// Built-in scene name (see --list-scenes) or path to a TOML scene file
+  #[arg(short, long, default_value = "final_scene")]
+  scene: String,
+
+  /// Print the names of the built-in scenes and exit
+  #[arg(long)]
+  list_scenes: bool,
+
+  /// Image width in pixels [default: from the scene]
+  #[arg(short = 'W', long, value_parser = clap::value_parser!(i32).range(2..))]
+  width: Option<i32>,
+
+  /// Image height in pixels [default: keeps the scene's aspect ratio]
+  #[arg(short = 'H', long, value_parser = clap::value_parser!(i32).range(2..))]
+  height: Option<i32>,
+
+  /// Samples per pixel [default: from the scene]
+  #[arg(short = 'n', long, value_parser = clap::value_parser!(i32).range(1..))]
+  samples: Option<i32>,
+
+  /// Maximum number of bounces per ray [default: from the scene]
+  #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..))]
+  depth: Option<i32>,
+
+  /// Camera position as X,Y,Z
+  #[arg(long, value_name = "X,Y,Z", value_parser = parse_vec3, allow_hyphen_values = true)]
+  lookfrom: Option<Vec3>,
+
+  /// Point the camera looks at as X,Y,Z
+  #[arg(long, value_name = "X,Y,Z", value_parser = parse_vec3, allow_hyphen_values = true)]
+  lookat: Option<Vec3>,
+
+  /// Vertical field of view in degrees
+  #[arg(long)]
+  vfov: Option<f64>,
+
+  /// Lens aperture; 0 gives a pinhole camera
+  #[arg(long)]
+  aperture: Option<f64>,
+
+  /// Distance to the plane in focus
+  #[arg(long)]
+  focus_dist: Option<f64>,
+
+  /// Integrator [default: from the scene]
+  #[arg(long, value_enum)]
+  integrator: Option<Integrator>,
+
+  /// Photons traced per pass by the photon mapping integrators
+  #[arg(long, value_name = "N")]
+  photons: Option<u32>,
+
+  /// Radius photons are gathered from; 0 picks one from the photons
+  #[arg(long, value_name = "R")]
+  photon_radius: Option<f64>,
+
+  /// Paths the mlt integrator estimates the image's brightness from
+  #[arg(long, value_name = "N")]
+  bootstrap_paths: Option<u32>,
+
+  /// Markov chains the mlt integrator runs
+  #[arg(long, value_name = "N")]
+  chains: Option<u32>,
+
+  /// Probability that an mlt mutation draws a whole new path
+  #[arg(long, value_name = "P")]
+  large_step_probability: Option<f64>,
+
+  /// Standard deviation of the mlt integrator's small mutations
+  #[arg(long, value_name = "SIGMA")]
+  mutation_sigma: Option<f64>,
+
+  /// Camera projection [default: from the scene]
+  #[arg(long, value_enum)]
+  projection: Option<ProjectionKind>,
+
+  /// Output file [default: stdout]
+  #[arg(short, long)]
+  output: Option<PathBuf>,
+
+  /// Output image format [default: from the output file extension, or ppm]
+  #[arg(short, long, value_enum)]
+  format: Option<Format>,
+
+  /// Render progressively in passes of N samples per pixel, rewriting the
+  /// output file after each pass; Ctrl-C stops after the last finished pass
+  #[arg(long, value_name = "N", value_parser = clap::value_parser!(i32).range(1..))]
+  pass_samples: Option<i32>,
+
+  /// Stop rendering after this many seconds, keeping the finished passes
+  /// (renders progressively, with 4 samples per pass unless --pass-samples is given)
+  #[arg(long, value_name = "SECONDS")]
+  time_limit: Option<f64>,
+
+  /// Save the accumulated samples to FILE while rendering progressively, so
+  /// the render can be continued with --resume
+  #[arg(long, value_name = "FILE")]
+  checkpoint: Option<PathBuf>,
+
+  /// Minimum time between two checkpoint saves; one is always saved at the end
+  #[arg(long, value_name = "SECONDS", default_value_t = 60.0)]
+  checkpoint_interval: f64,
+
+  /// Continue the render saved in the --checkpoint file; fails if the scene,
+  /// camera, image size, depth, background or seed differ. Only the scene
+  /// file itself is compared, not the meshes and textures it loads
+  #[arg(long, requires = "checkpoint")]
+  resume: bool,
+
+  /// Render by handing out tiles to --worker processes that connect to ADDR
+  /// (e.g. 0.0.0.0:7878); tiles of workers that go away are rendered by others,
+  /// and idle workers render copies of tiles that are overdue
+  #[arg(long, value_name = "ADDR", conflicts_with_all = ["pass_samples", "time_limit", "checkpoint"])]
+  listen: Option<String>,
+
+  /// Render tiles for the --listen process at ADDR; the scene, size and camera
+  /// come from there
+  #[arg(long, value_name = "ADDR", conflicts_with_all = ["listen", "output"])]
+  worker: Option<String>,
+
+  /// Width and height in pixels of the tiles handed to workers
+  #[arg(long, value_name = "N", default_value_t = 32, value_parser = clap::value_parser!(u32).range(1..))]
+  tile_size: u32,
+
+  /// Drop workers that send nothing for this many seconds, while loading the
+  /// scene or rendering a tile [default: only when they disconnect]
+  #[arg(long, value_name = "SECONDS", requires = "listen")]
+  tile_timeout: Option<f64>,
+
+  /// Render the frames of the scene file's [animation] into DIR as
+  /// frame_0001.png and so on, in the --format given or PNG
+  #[arg(
+    long,
+    value_name = "DIR",
+    conflicts_with_all = ["output", "pass_samples", "time_limit", "checkpoint", "listen", "worker"]
+  )]
+  animation: Option<PathBuf>,
+
+  /// Render only frames FIRST to LAST, or the single frame N, of the animation
+  #[arg(long, value_name = "FIRST-LAST", value_parser = parse_frames, requires = "animation")]
+  frames: Option<(u32, u32)>,
+
+  /// Leave animation frames whose image already exists alone
+  #[arg(long, requires = "animation")]
+  skip_existing: bool,
+
+  /// Number of render threads [default: one per core]
+  #[arg(short = 'j', long, value_parser = clap::value_parser!(u16).range(1..))]
+  threads: Option<u16>,
+
+  /// Seed for the procedural scenes, noise textures and pixel sampling; the
+  /// same seed renders the same image whatever the thread count
+  #[arg(long, default_value_t = 0)]
+  seed: u64,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+  /// ASCII PPM (P3)
+  Ppm,
+  /// Binary PPM (P6)
+  PpmBinary,
+  /// 8-bit PNG
+  Png,
+  /// 16-bit PNG
+  Png16,
+  /// Radiance HDR, linear
+  Hdr,
+  /// OpenEXR, linear 32-bit float
+  Exr,
+}
 
-fn random_scene<'a>(rng: &mut ThreadRng) -> HittableList {
-  let mut world = HittableList::new();
-
-  let checker = Box::new(CheckerTexture::new(
-    Box::new(SolidColor::new(Vec3::new(0.2, 0.3, 0.1))),
-    Box::new(SolidColor::new(Vec3::new(0.9, 0.9, 0.9))),
-  ));
-
-  let ground_material = Lambertian::new(checker);
-  let ground = Sphere::new(
-    Vec3::new(0.0, -1000.0, 0.0),
-    1000.0,
-    Box::new(ground_material)
-  );
-  world.add(Box::new(ground));
-
-  let mut objects = HittableList::new();
-
-  for a in -11..11 {
-    for b in -11..11 {
-      let choose_mat = rng.gen::<f64>();
-      let center = Vec3::new((a as f64) + 0.9*rng.gen::<f64>(), 0.2, (b as f64) + 0.9*rng.gen::<f64>());
-
-      if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-        if choose_mat < 0.8 {
-          let albedo = Vec3::random(rng) * Vec3::random(rng);        
-          let r = Lambertian::new(Box::new(SolidColor::new(albedo)));
-          objects.add(Box::new(Sphere::new(center, 0.2, Box::new(r))));
-        } else if choose_mat < 0.95 {
-          let albedo = Vec3::random(rng) * Vec3::random_range(rng, 0.5..1.0);
-          let fuzz = rng.gen_range(0.0..0.5);
-          let r = Metal::new(albedo, fuzz);
-          objects.add(Box::new(Sphere::new(center, 0.2, Box::new(r))));
-        } else {
-          let r = Dielactric::new(1.5);
-          objects.add(Box::new(Sphere::new(center, 0.2, Box::new(r))));
-        };        
-      }
+impl From<Format> for ImageFormat {
+  fn from(format: Format) -> ImageFormat {
+    match format {
+      Format::Ppm => ImageFormat::Ppm,
+      Format::PpmBinary => ImageFormat::PpmBinary,
+      Format::Png => ImageFormat::Png,
+      Format::Png16 => ImageFormat::Png16,
+      Format::Hdr => ImageFormat::Hdr,
+      Format::Exr => ImageFormat::Exr,
     }
   }
-
-  objects.add(Box::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Box::new(Dielactric::new(1.5)))));
-  objects.add(Box::new(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.4, 0.2, 0.1))))))));
-  objects.add(Box::new(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, Box::new(Metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0)))));
-
-  // world.add(Box::new(objects));
-  let len = objects.objects.len();
-  world.add(Box::new(BvhNode::new(rng, objects, 0, len, 0.0, 0.0)));
-  world
 }
 
-fn two_spheres() -> HittableList {
-  let mut objects = HittableList::new();
+#[derive(Clone, Copy, ValueEnum)]
+enum Integrator {
+  /// The book's recursive ray_color
+  Recursive,
+  /// Iterative path tracing with Russian roulette
+  Path,
+  /// Path tracing with next-event estimation and multiple importance sampling
+  Mis,
+  /// Bidirectional path tracing
+  Bdpt,
+  /// Photon mapping
+  PhotonMap,
+  /// Stochastic progressive photon mapping; converges over the passes of a
+  /// progressive render
+  Sppm,
+  /// Metropolis light transport over the path tracer's random numbers
+  Mlt,
+}
 
-  let checker = Box::new(CheckerTexture::new(
-    Box::new(SolidColor::new(Vec3::new(0.2, 0.3, 0.1))),
-    Box::new(SolidColor::new(Vec3::new(0.9, 0.9, 0.9))),
-  ));
+impl From<Integrator> for IntegratorKind {
+  fn from(integrator: Integrator) -> IntegratorKind {
+    match integrator {
+      Integrator::Recursive => IntegratorKind::Recursive,
+      Integrator::Path => IntegratorKind::Path,
+      Integrator::Mis => IntegratorKind::Mis,
+      Integrator::Bdpt => IntegratorKind::Bdpt,
+      Integrator::PhotonMap => IntegratorKind::PhotonMap(PhotonSettings::default()),
+      Integrator::Sppm => IntegratorKind::Sppm(PhotonSettings::default()),
+      Integrator::Mlt => IntegratorKind::Mlt(MltSettings::default()),
+    }
+  }
+}
 
-  objects.add(
-    Box::new(Sphere::new(Vec3::new(0.0, -10.0, 0.0), 10.0, Box::new(Lambertian::new(checker.clone()))))
-  );
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ProjectionKind {
+  /// Pinhole or thin lens, with --vfov
+  Perspective,
+  /// Parallel rays, as tall as the perspective view at the focus distance
+  Orthographic,
+  /// 180 degree equidistant fisheye
+  Fisheye,
+  /// 360 degree panorama, for 2:1 images
+  Equirectangular,
+  /// The six faces of a cube in a 3x2 grid, for 3:2 images
+  CubeMap,
+}
 
-  objects.add(
-    Box::new(Sphere::new(Vec3::new(0.0, 10.0, 0.0), 10.0, Box::new(Lambertian::new(checker))))
-  );
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+  let parts = s.split(',')
+    .map(|v| v.trim().parse::<f64>().map_err(|e| format!("`{}`: {}", v, e)))
+    .collect::<Result<Vec<_>, _>>()?;
+  match parts[..] {
+    [x, y, z] => Ok(Vec3::new(x, y, z)),
+    _ => Err("expected three comma separated numbers".to_string()),
+  }
+}
 
-  objects
+fn parse_frames(s: &str) -> Result<(u32, u32), String> {
+  let frame = |v: &str| v.trim().parse::<u32>().map_err(|e| format!("`{}`: {}", v, e));
+  let (first, last) = match s.split_once('-') {
+    Some((first, last)) => (frame(first)?, frame(last)?),
+    None => (frame(s)?, frame(s)?),
+  };
+  if first > last {
+    return Err("the first frame comes after the last".to_string());
+  }
+  Ok((first, last))
 }
 
-fn two_perlin_spheres(rng: &mut ThreadRng) -> HittableList {
-  let mut objects = HittableList::new();
+fn invalid(message: &str) -> ! {
+  Args::command().error(ErrorKind::ValueValidation, message).exit()
+}
 
-  let pertext = Box::new(NoiseTexture::new(rng, 5.0));
+/// Loads the scene as it is in `frame` of its animation, or its first frame,
+/// and applies the command line overrides.
+fn load_scene(args: &Args, frame: Option<u32>) -> Scene {
+  let mut rng = StdRng::seed_from_u64(args.seed);
+  let scene = match builtin_scenes::load(&mut rng, &args.scene) {
+    Some(scene) => scene,
+    None if PathBuf::from(&args.scene).is_file() => match frame {
+      Some(frame) => Scene::load_frame(&mut rng, &args.scene, frame),
+      None => Scene::load(&mut rng, &args.scene),
+    },
+    None => invalid(&format!(
+      "unknown scene `{}`; expected a scene file or one of: {}",
+      args.scene,
+      builtin_scenes::NAMES.join(", ")
+    )),
+  };
+  let mut scene = scene.unwrap_or_else(|e| {
+    eprintln!("{}: {}", args.scene, e);
+    process::exit(1);
+  });
+
+  let settings = &mut scene.settings;
+  match (args.width, args.height) {
+    (Some(width), Some(height)) => {
+      settings.image_width = width;
+      settings.image_height = height;
+    }
+    (Some(width), None) => {
+      settings.image_height = ((width as f64) / settings.aspect_ratio()).max(2.0) as i32;
+      settings.image_width = width;
+    }
+    (None, Some(height)) => {
+      settings.image_width = ((height as f64) * settings.aspect_ratio()).max(2.0) as i32;
+      settings.image_height = height;
+    }
+    (None, None) => {}
+  }
+  if let Some(samples) = args.samples {
+    settings.samples_per_pixel = samples;
+  }
+  if let Some(depth) = args.depth {
+    settings.max_depth = depth;
+  }
+  if let Some(integrator) = args.integrator {
+    // Photon settings from the scene carry over between the photon mappers,
+    // and Metropolis settings if it stays mlt.
+    let photons = settings.integrator.photons_mut().copied();
+    let mlt = settings.integrator.mlt_mut().copied();
+    settings.integrator = integrator.into();
+    if let (Some(to), Some(from)) = (settings.integrator.photons_mut(), photons) {
+      *to = from;
+    }
+    if let (Some(to), Some(from)) = (settings.integrator.mlt_mut(), mlt) {
+      *to = from;
+    }
+  }
+  if args.photons.is_some() || args.photon_radius.is_some() {
+    let photons = settings.integrator.photons_mut()
+      .unwrap_or_else(|| invalid("--photons and --photon-radius need the photon-map or sppm integrator"));
+    if let Some(count) = args.photons {
+      if count == 0 {
+        invalid("--photons must be positive");
+      }
+      photons.photons = count;
+    }
+    if let Some(radius) = args.photon_radius {
+      if radius.is_nan() || radius < 0.0 {
+        invalid("--photon-radius must not be negative");
+      }
+      photons.radius = radius;
+    }
+  }
+  if args.bootstrap_paths.is_some()
+    || args.chains.is_some()
+    || args.large_step_probability.is_some()
+    || args.mutation_sigma.is_some()
+  {
+    let mlt = settings.integrator.mlt_mut().unwrap_or_else(|| {
+      invalid("--bootstrap-paths, --chains, --large-step-probability and --mutation-sigma need the mlt integrator")
+    });
+    if let Some(paths) = args.bootstrap_paths {
+      if paths == 0 {
+        invalid("--bootstrap-paths must be positive");
+      }
+      mlt.bootstrap = paths;
+    }
+    if let Some(chains) = args.chains {
+      if chains == 0 {
+        invalid("--chains must be positive");
+      }
+      mlt.chains = chains;
+    }
+    if let Some(p) = args.large_step_probability {
+      if !(0.0..=1.0).contains(&p) {
+        invalid("--large-step-probability must be between 0 and 1");
+      }
+      mlt.large_step = p;
+    }
+    if let Some(sigma) = args.mutation_sigma {
+      if sigma.is_nan() || sigma <= 0.0 {
+        invalid("--mutation-sigma must be positive");
+      }
+      mlt.sigma = sigma;
+    }
+  }
+  settings.seed = args.seed;
 
-  objects.add(
-    Box::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Box::new(Lambertian::new(pertext.clone()))))
-  );
+  let camera = &mut scene.camera;
+  // A moving camera keeps its motion relative to the new position.
+  if let Some(lookfrom) = args.lookfrom {
+    camera.lookfrom1 = camera.lookfrom1 + (lookfrom - camera.lookfrom);
+    camera.lookfrom = lookfrom;
+  }
+  if let Some(lookat) = args.lookat {
+    camera.lookat1 = camera.lookat1 + (lookat - camera.lookat);
+    camera.lookat = lookat;
+  }
+  if let Some(vfov) = args.vfov {
+    camera.vfov = vfov;
+  }
+  if let Some(aperture) = args.aperture {
+    camera.aperture = aperture;
+  }
+  if let Some(focus_dist) = args.focus_dist {
+    camera.focus_dist = focus_dist;
+  }
+  if let Some(kind) = args.projection {
+    camera.projection = match kind {
+      ProjectionKind::Perspective => Projection::Perspective,
+      ProjectionKind::Orthographic => Projection::Orthographic { height: camera.focus_height() },
+      ProjectionKind::Fisheye => Projection::Fisheye { fov: 180.0 },
+      ProjectionKind::Equirectangular => Projection::Equirectangular,
+      ProjectionKind::CubeMap => Projection::CubeMap,
+    };
+  }
+  if (camera.lookfrom - camera.lookat).length_squared() == 0.0
+    || (camera.lookfrom1 - camera.lookat1).length_squared() == 0.0 {
+    invalid("--lookfrom and --lookat must be different points");
+  }
+  if !(camera.vfov > 0.0 && camera.vfov < 180.0) {
+    invalid("--vfov must be between 0 and 180 degrees");
+  }
+  if camera.aperture < 0.0 {
+    invalid("--aperture must not be negative");
+  }
+  if camera.focus_dist <= 0.0 {
+    invalid("--focus-dist must be positive");
+  }
 
-  objects.add(
-    Box::new(Sphere::new(Vec3::new(0.0, 2.0, 0.0), 2.0, Box::new(Lambertian::new(pertext))))
-  );
+  scene
+}
 
-  objects
+fn output_format(args: &Args) -> ImageFormat {
+  match (args.format, &args.output) {
+    (Some(format), _) => format.into(),
+    (None, None) => ImageFormat::Ppm,
+    (None, Some(path)) => ImageFormat::from_path(path).unwrap_or_else(|| invalid(&format!(
+      "cannot tell the image format of `{}`; use --format",
+      path.display()
+    ))),
+  }
 }
 
-fn earth() -> HittableList {
-  let mut objects = HittableList::new();
+fn open_output(args: &Args) -> Box<dyn Write> {
+  match &args.output {
+    None => Box::new(BufWriter::new(io::stdout())),
+    Some(path) => match File::create(path) {
+      Ok(file) => Box::new(BufWriter::new(file)),
+      Err(e) => {
+        eprintln!("{}: {}", path.display(), e);
+        process::exit(1);
+      }
+    },
+  }
+}
 
-  let earth_texture = Box::new(ImageTexture::new("assets/earthmap.jpg"));
-  let earth_surface = Box::new(Lambertian::new(earth_texture));
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The first Ctrl-C sets `INTERRUPTED`; a second one exits at once.
+#[cfg(unix)]
+fn handle_interrupt() {
+  // SAFETY: the handler only touches an atomic and calls `_exit`, both of
+  // which are async-signal-safe.
+  let result = unsafe {
+    signal_hook_registry::register(libc::SIGINT, || {
+      if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        libc::_exit(130);
+      }
+    })
+  };
+  if let Err(e) = result {
+    eprintln!("cannot handle Ctrl-C: {}", e);
+  }
+}
 
-  objects.add(
-    Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 2.0, earth_surface))
-  );
+#[cfg(not(unix))]
+fn handle_interrupt() {}
 
-  objects
+fn progressive(args: &Args) -> Option<Progressive> {
+  if args.pass_samples.is_none() && args.time_limit.is_none() && args.checkpoint.is_none() {
+    return None;
+  }
+  if args.output.is_none() {
+    invalid("progressive rendering needs an --output file");
+  }
+  let time_limit = args.time_limit.map(|seconds| {
+    Duration::try_from_secs_f64(seconds)
+      .ok()
+      .filter(|limit| !limit.is_zero())
+      .unwrap_or_else(|| invalid("--time-limit must be a positive number of seconds"))
+  });
+  Some(Progressive {
+    pass_samples: args.pass_samples.unwrap_or(4),
+    time_limit,
+  })
 }
 
-fn simple_light() -> HittableList {
-  let mut objects = HittableList::new();
-
-  let pertext = Box::new(NoiseTexture::new(&mut rand::thread_rng(), 4.0));
-  objects.add(
-    Box::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Box::new(Lambertian::new(pertext.clone()))))
-  );
-  objects.add(
-    Box::new(Sphere::new(Vec3::new(0.0, 2.0, 0.0), 2.0, Box::new(Lambertian::new(pertext.clone())))
-  ));
+/// Identifies the scene for checkpoints: built-in scenes by name, files by
+/// their contents, without the OBJ and image files they refer to. The seed
+/// is checked separately.
+fn scene_hash(args: &Args) -> u64 {
+  if builtin_scenes::NAMES.contains(&args.scene.as_str()) {
+    return checkpoint::scene_hash(format!("builtin:{}", args.scene).as_bytes());
+  }
+  match std::fs::read(&args.scene) {
+    Ok(bytes) => checkpoint::scene_hash(&bytes),
+    Err(e) => {
+      eprintln!("{}: {}", args.scene, e);
+      process::exit(1);
+    }
+  }
+}
 
-  let difflight = Box::new(DiffuseLight::new(Box::new(SolidColor::new(Vec3::new(4.0, 4.0, 4.0)))));
-  objects.add(
-    Box::new(Sphere::new(Vec3::new(0.0, 7.0, 0.0), 2.0, difflight.clone()))
-  );
-  objects.add(
-    Box::new(XyRect::new(3.0, 5.0, 1.0, 3.0, -2.0, difflight))
+fn start_accumulator(args: &Args, scene: &Scene, scene_hash: u64) -> Accumulator {
+  let settings = &scene.settings;
+  let path = match (&args.checkpoint, args.resume) {
+    (Some(path), true) => path,
+    _ => return Accumulator::new(settings.image_width as usize, settings.image_height as usize),
+  };
+  let checkpoint = Checkpoint::load(path)
+    .and_then(|checkpoint| {
+      checkpoint.check_resumable(scene_hash, settings, &scene.camera)?;
+      Ok(checkpoint)
+    })
+    .unwrap_or_else(|e| {
+      eprintln!("{}: {}", path.display(), e);
+      process::exit(1);
+    });
+  eprintln!(
+    "Resuming from {} samples per pixel ({} passes)",
+    checkpoint.accumulator.samples,
+    checkpoint.accumulator.passes
   );
-
-  objects
+  checkpoint.accumulator
 }
 
-fn cornell_box() -> HittableList {
-    let mut objects = HittableList::new();
-
-    let red = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.65, 0.05, 0.05)))));
-    let white = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.73, 0.73, 0.73)))));
-    let green = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.12, 0.45, 0.15)))));
-    let light = Box::new(DiffuseLight::new(Box::new(SolidColor::new(Vec3::new(15.0, 15.0, 15.0)))));
-
-    objects.add(
-        Box::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green.clone()))
-    );
-    objects.add(
-        Box::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red.clone()))
-    );
-    objects.add(
-        Box::new(XzRect::new(213.0, 343.0, 227.0, 332.0, 554.0, light.clone()))
-    );
-    objects.add(
-        Box::new(XzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()))
-    );
-    objects.add(
-        Box::new(XzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()))
-    );
-    objects.add(
-        Box::new(XyRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()))
-    );
-
-    let box1 = Box::new(BoxModel::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(165.0, 330.0, 165.0), white.clone()));
-    let box1 = Box::new(RotateY::new(box1, 15.0));
-    let box1 = Box::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
-    objects.add(box1);
-
-    let box2 = Box::new(BoxModel::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(165.0, 165.0, 165.0), white.clone()));
-    let box2 = Box::new(RotateY::new(box2, -18.0));
-    let box2 = Box::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
-    objects.add(box2);
-
-    objects
+fn save_checkpoint(args: &Args, scene: &Scene, scene_hash: u64, accumulator: &Accumulator) {
+  let path = match &args.checkpoint {
+    Some(path) => path,
+    None => return,
+  };
+  let checkpoint = Checkpoint {
+    scene_hash,
+    settings: scene.settings,
+    camera: scene.camera,
+    accumulator: accumulator.clone(),
+  };
+  if let Err(e) = checkpoint.save(path) {
+    eprintln!("\nfailed to save checkpoint {}: {}", path.display(), e);
+  }
 }
 
-fn cornell_smoke() -> HittableList {
-    let mut objects = HittableList::new();
-
-    let red = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.65, 0.05, 0.05)))));
-    let white = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.73, 0.73, 0.73)))));
-    let green = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.12, 0.45, 0.15)))));
-    let light = Box::new(DiffuseLight::new(Box::new(SolidColor::new(Vec3::new(7.0, 7.0, 7.0)))));
-
-    objects.add(
-        Box::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green.clone()))
-    );
-    objects.add(
-        Box::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red.clone()))
-    );
-    objects.add(
-        Box::new(XzRect::new(113.0, 443.0, 127.0, 432.0, 554.0, light.clone()))
-    );
-    objects.add(
-        Box::new(XzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()))
-    );
-    objects.add(
-        Box::new(XzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()))
-    );
-    objects.add(
-        Box::new(XyRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()))
-    );
-    let box1 = Box::new(BoxModel::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(165.0, 330.0, 165.0), white.clone()));
-    let box1 = Box::new(RotateY::new(box1, 15.0));
-    let box1 = Box::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
-
-    let box2 = Box::new(BoxModel::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(165.0, 165.0, 165.0), white.clone()));
-    let box2 = Box::new(RotateY::new(box2, -18.0));
-    let box2 = Box::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
-
-    objects.add(Box::new(ConstantMedium::new(box1, 0.01, Box::new(IsoTropic::new(Box::new(SolidColor::new(Vec3::new(0.0, 0.0, 0.0))))))));
-    objects.add(Box::new(ConstantMedium::new(box2, 0.01, Box::new(IsoTropic::new(Box::new(SolidColor::new(Vec3::new(1.0, 1.0, 1.0))))))));
-
-    objects
-}
+fn render_animation(args: &Args, dir: &Path, first: &Scene) {
+  let timeline = first.timeline.as_ref().unwrap_or_else(|| {
+    invalid(&format!("`{}` has no [animation] table", args.scene))
+  });
+  let (first_frame, last_frame) = args.frames.unwrap_or((timeline.first_frame, timeline.last_frame));
+  let format = args.format.map_or(ImageFormat::Png, ImageFormat::from);
+  if let Err(e) = std::fs::create_dir_all(dir) {
+    eprintln!("{}: {}", dir.display(), e);
+    process::exit(1);
+  }
 
-fn final_scene(rng: &mut ThreadRng) -> HittableList {
-    let mut boxes1 = HittableList::new();
-    let ground = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.48, 0.83, 0.53)))));
-    const BOXES_PER_SIDE: usize = 20;
-
-    for i in 0 .. BOXES_PER_SIDE {
-        for j in 0 .. BOXES_PER_SIDE {
-            let w = 100.0;
-            boxes1.add(
-                Box::new(BoxModel::new(
-                    Vec3::new(
-                        -1000.0 + i as f64 * w,
-                        0.0,
-                        -1000.0 + j as f64 * w) ,
-                    Vec3::new(
-                        -1000.0 + i as f64 * w + w,
-                        rng.gen_range(1.0..101.0),
-                        -1000.0 + j as f64 * w + w),
-                    ground.clone()
-                ))
-            );
-        }
+  let start = Instant::now();
+  let (mut rendered, mut skipped) = (0, 0);
+  for frame in first_frame..=last_frame {
+    let path = dir.join(Timeline::file_name(frame, format.extension()));
+    if args.skip_existing && path.exists() {
+      skipped += 1;
+      continue;
     }
-
-    let mut objects = HittableList::new();
-    objects.add(Box::new(BvhNode::new(rng, boxes1, 0, BOXES_PER_SIDE*BOXES_PER_SIDE , 0.0, 1.0)));
-
-    let light = Box::new(DiffuseLight::new(Box::new(SolidColor::new(Vec3::new(7.0, 7.0, 7.0)))));
-    objects.add(Box::new(XzRect::new(123.0, 423.0, 147.0, 412.0, 554.0, light.clone())));
-
-    let center1 = Vec3::new(400.0, 400.0, 200.0);
-    let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
-    let moving_sphere_material = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.7, 0.3, 0.1)))));
-    objects.add(Box::new(MovingSphere::new(center1, center2, 0.0, 1.0, 50.0, moving_sphere_material)));
-
-    objects.add(Box::new(Sphere::new(Vec3::new(260.0, 150.0, 45.0), 50.0, Box::new(Dielactric::new(1.5)))));
-    objects.add(Box::new(Sphere::new(Vec3::new(0.0, 150.0, 145.0), 50.0, Box::new(Metal::new(Vec3::new(0.8, 0.8, 0.9), 10.0)))));
-
-    let boundary = Box::new(Sphere::new(Vec3::new(360.0, 150.0, 145.0), 70.0, Box::new(Dielactric::new(1.5))));
-    objects.add(boundary);
-    let boundary = Box::new(Sphere::new(Vec3::new(360.0, 150.0, 145.0), 70.0, Box::new(Dielactric::new(1.5))));
-    objects.add(Box::new(ConstantMedium::new(boundary, 0.2, Box::new(IsoTropic::new(Box::new(SolidColor::new(Vec3::new(0.2, 0.4, 0.9))))))));
-    let boundary = Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 5000.0, Box::new(Dielactric::new(1.5))));
-    objects.add(Box::new(ConstantMedium::new(boundary, 0.0001, Box::new(IsoTropic::new(Box::new(SolidColor::new(Vec3::new(1.0, 1.0, 1.0))))))));
-
-    let emat = Box::new(Lambertian::new(Box::new(ImageTexture::new("assets/earthmap.jpg"))));
-    objects.add(Box::new(Sphere::new(Vec3::new(400.0, 200.0, 400.0), 100.0, emat)));
-    let pertext = Box::new(NoiseTexture::new(rng, 0.1));
-    objects.add(Box::new(Sphere::new(Vec3::new(220.0, 280.0, 300.0), 80.0, Box::new(Lambertian::new(pertext)))));
-
-    let mut boxes2 = HittableList::new();
-    let white = Box::new(Lambertian::new(Box::new(SolidColor::new(Vec3::new(0.73, 0.73, 0.73)))));
-    let ns = 1000;
-    for _ in 0 .. ns {
-        boxes2.add(Box::new(Sphere::new(Vec3::random_range(rng, 0.0..165.0), 10.0, white.clone())));
+    let scene = load_scene(args, Some(frame));
+    let renderer = scene.renderer().with_progress(|done, total| {
+      eprint!("\rFrame {} of {}: {} %   ", frame, last_frame, done * 100 / total);
+    });
+    let fb = renderer.render(&scene.world, &*scene.camera());
+    // Written whole or not at all, so an interrupted run leaves no frame that
+    // --skip-existing would take for finished.
+    if let Err(e) = output::save_atomic(&fb, &path, format) {
+      eprintln!("\nfailed to write {}: {}", path.display(), e);
+      process::exit(1);
     }
+    rendered += 1;
+  }
+  eprint!(
+    "\rRendered {} frames in {:.1} s, skipped {} that existed          ",
+    rendered,
+    start.elapsed().as_secs_f64(),
+    skipped
+  );
+}
 
-    objects.add(Box::new(Translate::new(
-        Box::new(RotateY::new(
-            Box::new(BvhNode::new(rng, boxes2, 0, ns, 0.0, 1.0)),
-            15.0
-        )),
-        Vec3::new(-100.0, 270.0, 395.0)
-    )));
+fn render_once(args: &Args, renderer: Renderer, scene: &Scene, format: ImageFormat) {
+  let mut out = open_output(args);
+  let renderer = renderer.with_progress(|done, total| {
+    eprint!("\r{} %", done * 100 / total);
+  });
+  let fb = renderer.render(&scene.world, &*scene.camera());
 
-    objects
+  if let Err(e) = output::write(&fb, &mut out, format).and_then(|_| Ok(out.flush()?)) {
+    eprintln!("\nfailed to write image: {}", e);
+    process::exit(1);
+  }
 }
 
-pub fn format_ppm(pixel_color: &Color, samples_per_pixel: i32) -> String {
-  let scale = 1.0 / (samples_per_pixel as f64);
+fn render_progressive(args: &Args, renderer: Renderer, scene: &Scene, progressive: &Progressive, format: ImageFormat) {
+  let path = args.output.as_ref().unwrap();
+  let settings = renderer.settings;
+  let target = settings.samples_per_pixel;
+  let samples_done = AtomicU64::new(0);
+  let start = Instant::now();
+
+  handle_interrupt();
+  let renderer = renderer
+    .with_cancel(&INTERRUPTED)
+    .with_progress(|done, total| {
+      eprint!(
+        "\r{} / {} samples per pixel, next pass {} %   ",
+        samples_done.load(Ordering::Relaxed),
+        target,
+        done * 100 / total
+      );
+    });
 
-  let r = (scale * pixel_color.x).sqrt();
-  let g = (scale * pixel_color.y).sqrt();
-  let b = (scale * pixel_color.z).sqrt();
+  let checkpoint_interval = Duration::try_from_secs_f64(args.checkpoint_interval)
+    .unwrap_or_else(|_| invalid("--checkpoint-interval must be a number of seconds"));
+  let mut last_checkpoint = Instant::now();
+
+  let hash = if args.checkpoint.is_some() { scene_hash(args) } else { 0 };
+  let mut accumulator = start_accumulator(args, scene, hash);
+  samples_done.store(accumulator.samples, Ordering::Relaxed);
+  let camera = scene.camera();
+  let stopped = renderer.render_progressive(&scene.world, &*camera, progressive, &mut accumulator, |accumulator| {
+    samples_done.store(accumulator.samples, Ordering::Relaxed);
+    if let Err(e) = output::save_atomic(&accumulator.image(), path, format) {
+      eprintln!("\nfailed to write {}: {}", path.display(), e);
+      process::exit(1);
+    }
+    if last_checkpoint.elapsed() >= checkpoint_interval {
+      save_checkpoint(args, scene, hash, accumulator);
+      last_checkpoint = Instant::now();
+    }
+  });
 
-  format!(
-    "{} {} {}",
-    (256.0 * r.clamp(0.0, 0.999)) as i32,
-    (256.0 * g.clamp(0.0, 0.999)) as i32,
-    (256.0 * b.clamp(0.0, 0.999)) as i32
-  )
+  // Covers a render that stopped during its first pass, or one resumed from a
+  // checkpoint that already had enough samples.
+  if let Err(e) = output::save_atomic(&accumulator.image(), path, format) {
+    eprintln!("\nfailed to write {}: {}", path.display(), e);
+    process::exit(1);
+  }
+  save_checkpoint(args, scene, hash, &accumulator);
+  eprint!(
+    "\r{} / {} samples per pixel in {:.1} s ({})                ",
+    accumulator.samples,
+    target,
+    start.elapsed().as_secs_f64(),
+    stopped
+  );
 }
 
-#[tokio::main]
-async fn main() {
-
-  // let aspect_ratio = 16.0 / 9.0;
-  // let image_width = 400;
-  let aspect_ratio = 1.0;
-  let image_width = 600;
-
-  let image_height = ((image_width as f64) / aspect_ratio) as i32;
-  // let samples_per_pixel = 200;
-    let samples_per_pixel = 50;
-  let max_depth = 50;
+/// Loads the scene of a distributed render the way `load_scene` would have
+/// on the coordinator, minus the command line overrides the job carries.
+fn load_job(job: &Job) -> Result<Scene, String> {
+  let mut rng = StdRng::seed_from_u64(job.settings.seed);
+  match builtin_scenes::load(&mut rng, &job.scene) {
+    Some(scene) => scene.map_err(|e| e.to_string()),
+    None => Scene::load(&mut rng, &job.scene).map_err(|e| format!("{}: {}", job.scene, e)),
+  }
+}
 
-  let (tx, rx) = mpsc::channel();
-  let mtx = Mutex::new(tx);
+fn run_worker(addr: &str) {
+  let worker = Worker::connect(addr, load_job).unwrap_or_else(|e| {
+    eprintln!("{}: {}", addr, e);
+    process::exit(1);
+  });
+  eprintln!("Connected to {}", addr);
+  match worker.run() {
+    Ok(tiles) => eprint!("Rendered {} tiles", tiles),
+    Err(e) => {
+      eprintln!("{}: {}", addr, e);
+      process::exit(1);
+    }
+  }
+}
 
-  let image_generation_task = async move {
-    let world = {
-      // let mut rng = Box::new(rand::thread_rng());
-      // random_scene(&mut rng)
+fn render_distributed(args: &Args, addr: &str, scene: &Scene, format: ImageFormat) {
+  match scene.settings.integrator {
+    IntegratorKind::Bdpt => invalid("the bdpt integrator traces light onto the whole image and cannot render in tiles"),
+    IntegratorKind::Mlt(_) => invalid("the mlt integrator renders the whole image at once and cannot render in tiles"),
+    _ => {}
+  }
+  let coordinator = Coordinator::bind(addr).unwrap_or_else(|e| {
+    eprintln!("cannot listen on {}: {}", addr, e);
+    process::exit(1);
+  });
+  let mut coordinator = coordinator.with_tile_size(args.tile_size as usize);
+  if let Some(seconds) = args.tile_timeout {
+    let timeout = Duration::try_from_secs_f64(seconds)
+      .ok()
+      .filter(|timeout| !timeout.is_zero())
+      .unwrap_or_else(|| invalid("--tile-timeout must be a positive number of seconds"));
+    coordinator = coordinator.with_tile_timeout(timeout);
+  }
+  // Workers may run elsewhere on the machine, so they get an absolute path.
+  let name = match std::fs::canonicalize(&args.scene) {
+    Ok(path) if !builtin_scenes::NAMES.contains(&args.scene.as_str()) => path.to_string_lossy().into_owned(),
+    _ => args.scene.clone(),
+  };
+  let job = Job {
+    scene: name,
+    settings: scene.settings,
+    camera: scene.camera,
+  };
+
+  let mut out = open_output(args);
+  if let Ok(addr) = coordinator.local_addr() {
+    eprintln!("Waiting for workers on {}", addr);
+  }
+  let fb = coordinator.render(&job, |event| match event {
+    Event::WorkerJoined(addr) => eprintln!("\rWorker {} joined", addr),
+    Event::WorkerLeft { addr, reason } => eprintln!("\rWorker {} left: {}", addr, reason),
+    Event::TileDone { done, total } => eprint!("\r{} %", done * 100 / total),
+  });
+  let fb = fb.unwrap_or_else(|e| {
+    eprintln!("\ncannot accept workers: {}", e);
+    process::exit(1);
+  });
+
+  if let Err(e) = output::write(&fb, &mut out, format).and_then(|_| Ok(out.flush()?)) {
+    eprintln!("\nfailed to write image: {}", e);
+    process::exit(1);
+  }
+}
 
-      // two_spheres()
+fn main() {
+  let args = Args::parse();
 
-      // let mut rng = Box::new(rand::thread_rng());
-      // two_perlin_spheres(&mut rng)
+  if args.list_scenes {
+    for name in builtin_scenes::NAMES {
+      println!("{}", name);
+    }
+    return;
+  }
 
-      // earth()
+  if let Some(threads) = args.threads {
+    rayon::ThreadPoolBuilder::new()
+      .num_threads(threads as usize)
+      .build_global()
+      .unwrap();
+  }
 
-      // simple_light()
+  if let Some(addr) = &args.worker {
+    run_worker(addr);
+    eprintln!("\nDone.");
+    return;
+  }
 
-      // cornell_box()
+  let format = output_format(&args);
+  let progressive = progressive(&args);
+  let start = Instant::now();
+  let scene = load_scene(&args, None);
+  eprintln!("Scene built in {:.2} s", start.elapsed().as_secs_f64());
 
-        // cornell_smoke()
+  if let Some(dir) = &args.animation {
+    render_animation(&args, dir, &scene);
+    eprintln!("\nDone.");
+    return;
+  }
 
-        let mut rng = Box::new(rand::thread_rng());
-        final_scene(&mut rng)
-    };
-  
-    // let lookfrom = Vec3::new(26.0, 3.0, 6.0);
-    // let lookat = Vec3::new(0.0, 2.0, 0.0);
-    // let vup = Vec3::new(0.0, 1.0, 0.0);
-    // let vfov = 20.0;
-    // let aspect_ratio = (image_width as f64)/(image_height as f64);
-    // let dist_to_focus = 10.0;
-    // // let aperture = 0.1;
-    // let aperture = 0.0;
-    // let time0 = 0.0;
-    // let time1 = 1.0;
-
-    // let lookfrom = Vec3::new(278.0, 278.0, -800.0);
-    // let lookat = Vec3::new(278.0, 278.0, 0.0);
-    // let vup = Vec3::new(0.0, 1.0, 0.0);
-    // let vfov = 40.0;
-    // let aspect_ratio = (image_width as f64)/(image_height as f64);
-    // let dist_to_focus = 10.0;
-    // // let aperture = 0.1;
-    // let aperture = 0.0;
-    // let time0 = 0.0;
-    // let time1 = 1.0;
-
-      let lookfrom = Vec3::new(478.0, 278.0, -600.0);
-      let lookat = Vec3::new(278.0, 278.0, 0.0);
-      let vup = Vec3::new(0.0, 1.0, 0.0);
-      let vfov = 40.0;
-      let aspect_ratio = (image_width as f64)/(image_height as f64);
-      let dist_to_focus = 10.0;
-      // let aperture = 0.1;
-      let aperture = 0.0;
-      let time0 = 0.0;
-      let time1 = 1.0;
-
-
-    let cam = Camera::new(lookfrom, lookat, vup, vfov, aspect_ratio, aperture, dist_to_focus, time0, time1);
-  
-
-    (0 .. image_height).into_par_iter().for_each(|j| {
-      let mut rng = Box::new(rand::thread_rng());
-      for i in 0 .. image_width {
-        let mut pixel_color = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
-        for _ in 0 .. samples_per_pixel {
-          let u = (i as f64 + rng.gen::<f64>()) / (image_width-1) as f64;
-          let v = (j as f64 + rng.gen::<f64>()) / (image_height-1) as f64;
-          let r = cam.get_ray(&mut rng, u, v);
-          let background = Color::new(0.0, 0.0, 0.0);
-          pixel_color = pixel_color + ray_color(&mut rng, &r, &background, &world, max_depth);
-        }
-        let ppm = format_ppm(&pixel_color, samples_per_pixel);
-        mtx.lock().unwrap().send(((image_height-j, i), ppm)).unwrap();
-      }
-    });
-  };  
-
-  tokio::spawn(image_generation_task);
-
-  println!("P3");
-  println!("{} {}", image_width, image_height);
-  println!("255");
-
-  let mut list = Vec::new();
-  let mut counter = 0;
-  let num_of_pixcels = image_height * image_width;
-  for v in rx.iter() {
-    list.push(v);
-    counter = counter + 1;
-    eprint!("\r{} %", counter * 100 / num_of_pixcels);
-    if num_of_pixcels == list.len().try_into().unwrap() {
-      break;
-    }
+  if let Some(addr) = &args.listen {
+    render_distributed(&args, addr, &scene, format);
+    eprintln!("\nDone.");
+    return;
   }
-  list.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-  for (_, item) in list {
-    println!("{}", item);
+
+  let renderer = scene.renderer();
+  match &progressive {
+    None => render_once(&args, renderer, &scene, format),
+    Some(progressive) => render_progressive(&args, renderer, &scene, progressive, format),
   }
 
   eprintln!("\nDone.");
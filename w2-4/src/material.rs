@@ -1,43 +1,51 @@
-use rand::Rng;
-use rand::rngs::ThreadRng;
-
+use std::f64::consts::PI;
+use std::sync::Arc;
 use crate::ray::Ray;
 use crate::hittable::HitRecord;
+use crate::pdf::{CosinePdf, Pdf, SpherePdf};
+use crate::sampler::Sampler;
 use crate::texture::Texture;
 use crate::vec3::Vec3;
 use crate::vec3::Color;
 
-pub trait CloneMaterial {
-  fn clone_box(&self) -> Box<dyn Material>;
+/// The outcome of `Material::scatter`.
+pub enum ScatterRecord {
+  /// A mirror-like bounce traced as is, without going through a PDF.
+  Specular { attenuation: Color, ray: Ray },
+  /// A bounce whose direction is drawn from `pdf` (possibly mixed with light
+  /// sampling) and weighted by `Material::scattering_pdf`.
+  Diffuse { attenuation: Color, pdf: Box<dyn Pdf> },
 }
 
-pub trait Material: Sync + CloneMaterial {
-  fn scatter<'a>(&self, rng: &'a mut ThreadRng, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+pub trait Material: Send + Sync {
+  fn scatter(&self, sampler: &mut dyn Sampler, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord>;
+  /// Density of scattering from `r_in` into `scattered`; only used for
+  /// `ScatterRecord::Diffuse`.
+  fn scattering_pdf(&self, _: &Ray, _: &HitRecord, _: &Ray) -> f64 {
+    0.0
+  }
+  /// Density of a `ScatterRecord::Specular` bounce from `r_in` going in
+  /// `direction`, for materials that blur their specular rays over a lobe;
+  /// 0 for perfect mirrors and refraction.
+  fn specular_pdf(&self, _: &Ray, _: &HitRecord, _: &Vec3) -> f64 {
+    0.0
+  }
   fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Color;
-}
-
-impl<T> CloneMaterial for T
-  where
-      T: 'static + Material + Clone,
-{
-  fn clone_box(&self) -> Box<dyn Material> {
-    Box::new(self.clone())
+  /// Whether this is the phase function of a participating medium rather
+  /// than a surface.
+  fn is_volume(&self) -> bool {
+    false
   }
 }
 
-impl Clone for Box<dyn Material> {
-  fn clone(&self) -> Box<dyn Material> {
-    self.clone_box()
-  }
-}
 
 #[derive(Clone)]
 pub struct Lambertian {
-  albedo: Box<dyn Texture>
+  albedo: Arc<dyn Texture>
 }
 
 impl Lambertian {
-  pub fn new(albedo: Box<dyn Texture>) -> Lambertian {
+  pub fn new(albedo: Arc<dyn Texture>) -> Lambertian {
     Lambertian {
       albedo
     }
@@ -45,11 +53,14 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-  fn scatter<'a>(&self, rng: &'a mut ThreadRng, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-    let scatter_direction = rec.normal + Vec3::random_unit_vector(rng);
-    let scattered = Ray::new(rec.p, scatter_direction, r_in.time);
+  fn scatter(&self, _: &mut dyn Sampler, _: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
     let attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
-    Some((attenuation, scattered))
+    Some(ScatterRecord::Diffuse { attenuation, pdf: Box::new(CosinePdf::new(&rec.normal)) })
+  }
+
+  fn scattering_pdf(&self, _: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+    let cosine = rec.normal.dot(&scattered.direction.unit_vector());
+    (cosine / PI).max(0.0)
   }
 
   fn emitted(&self, _: f64, _: f64, _: &Vec3) -> Color {
@@ -73,17 +84,40 @@ impl Metal {
 }
 
 impl Material for Metal {
-  fn scatter<'a>(&self, rng: &'a mut ThreadRng, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+  fn scatter(&self, sampler: &mut dyn Sampler, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
     let reflected = r_in.direction.unit_vector().reflect(&rec.normal);
-    let scattered = Ray::new(rec.p, reflected+Vec3::random_in_unit_sphere(rng)*self.fuzz, r_in.time);
+    let scattered = Ray::new(rec.p, reflected+Vec3::random_in_unit_sphere(sampler)*self.fuzz, r_in.time);
     let attenuation = self.albedo;
     if scattered.direction.dot(&rec.normal) > 0.0 {
-      Some((attenuation, scattered))
+      Some(ScatterRecord::Specular { attenuation, ray: scattered })
     } else {
       None
     }
   }
 
+  /// The scattered direction is the reflection plus a uniform point in a
+  /// ball of radius `fuzz`, so its density is the ball's volume along the
+  /// direction, `(t1^3 - t0^3) / (4 pi fuzz^3)` between the distances where
+  /// the direction enters and leaves the ball.
+  fn specular_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+    let direction = direction.unit_vector();
+    if self.fuzz <= 0.0 || direction.dot(&rec.normal) <= 0.0 {
+      return 0.0;
+    }
+    let reflected = r_in.direction.unit_vector().reflect(&rec.normal);
+    let b = direction.dot(&reflected);
+    let discriminant = b*b - 1.0 + self.fuzz*self.fuzz;
+    if discriminant <= 0.0 {
+      return 0.0;
+    }
+    let t1 = b + discriminant.sqrt();
+    let t0 = (b - discriminant.sqrt()).max(0.0);
+    if t1 <= 0.0 {
+      return 0.0;
+    }
+    (t1.powi(3) - t0.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
+  }
+
   fn emitted(&self, _: f64, _: f64, _: &Vec3) -> Color {
     Color::black()
   }
@@ -103,7 +137,7 @@ impl Dielactric {
 }
 
 impl Material for Dielactric {
-  fn scatter<'a>(&self, rng: &'a mut ThreadRng, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+  fn scatter(&self, sampler: &mut dyn Sampler, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
     let attenuation = Vec3::new(1.0, 1.0, 1.0);
     let etai_over_etat = if rec.front_face {
       1.0 / self.ref_idx
@@ -117,18 +151,18 @@ impl Material for Dielactric {
     let sin_theta = (1.0 - cos_theta*cos_theta).sqrt();
     if etai_over_etat * sin_theta > 1.0 {
       let reflected = unit_direction.reflect(&rec.normal);
-      let scattered = Ray::new(rec.p, reflected, 0.0);
-      return Some((attenuation, scattered))
+      let scattered = Ray::new(rec.p, reflected, r_in.time);
+      return Some(ScatterRecord::Specular { attenuation, ray: scattered })
     }
     let reflect_prob = schlick(cos_theta, etai_over_etat);
-    if rng.gen::<f64>() < reflect_prob {
+    if sampler.next_f64() < reflect_prob {
       let reflected = unit_direction.reflect(&rec.normal);
-      let scattered = Ray::new(rec.p, reflected, 0.0);
-      return Some((attenuation, scattered));
+      let scattered = Ray::new(rec.p, reflected, r_in.time);
+      return Some(ScatterRecord::Specular { attenuation, ray: scattered });
     }
     let refracted = unit_direction.refract(&rec.normal, etai_over_etat);
-    let scattered = Ray::new(rec.p, refracted, 0.0);
-    Some((attenuation, scattered))
+    let scattered = Ray::new(rec.p, refracted, r_in.time);
+    Some(ScatterRecord::Specular { attenuation, ray: scattered })
   }
 
   fn emitted(&self, _: f64, _: f64, _: &Vec3) -> Color {
@@ -144,11 +178,11 @@ fn schlick(cosine: f64, ref_idx: f64) -> f64 {
 
 #[derive(Clone)]
 pub struct DiffuseLight {
-  emit: Box<dyn Texture>
+  emit: Arc<dyn Texture>
 }
 
 impl DiffuseLight {
-  pub fn new(emit: Box<dyn Texture>) -> DiffuseLight {
+  pub fn new(emit: Arc<dyn Texture>) -> DiffuseLight {
     DiffuseLight {
       emit
     }
@@ -158,7 +192,7 @@ impl DiffuseLight {
 }
 
 impl Material for DiffuseLight {
-  fn scatter<'a>(&self, _: &'a mut ThreadRng, _: &Ray, _: &HitRecord) -> Option<(Color, Ray)> {
+  fn scatter(&self, _: &mut dyn Sampler, _: &Ray, _: &HitRecord) -> Option<ScatterRecord> {
     None
   }
 
@@ -169,11 +203,11 @@ impl Material for DiffuseLight {
 
 #[derive(Clone)]
 pub struct IsoTropic {
-  albedo: Box<dyn Texture>
+  albedo: Arc<dyn Texture>
 }
 
 impl IsoTropic {
-  pub fn new(a: Box<dyn Texture>) -> IsoTropic {
+  pub fn new(a: Arc<dyn Texture>) -> IsoTropic {
     IsoTropic {
       albedo: a
     }
@@ -181,13 +215,20 @@ impl IsoTropic {
 }
 
 impl Material for IsoTropic {
-  fn scatter<'a>(&self, rng: &'a mut ThreadRng, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-    let scattered = Ray::new(rec.p, Vec3::random_in_unit_sphere(rng), r_in.time);
+  fn scatter(&self, _: &mut dyn Sampler, _: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
     let attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
-    Some((attenuation, scattered))
+    Some(ScatterRecord::Diffuse { attenuation, pdf: Box::new(SpherePdf) })
   }
 
-  fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Color {
+  fn scattering_pdf(&self, _: &Ray, _: &HitRecord, _: &Ray) -> f64 {
+    1.0 / (4.0 * PI)
+  }
+
+  fn is_volume(&self) -> bool {
+    true
+  }
+
+  fn emitted(&self, _: f64, _: f64, _: &Vec3) -> Color {
     Color::black()
   }
 }
\ No newline at end of file
@@ -0,0 +1,323 @@
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use crate::camera::Camera;
+use crate::framebuffer::Film;
+use crate::hittable::Hittable;
+use crate::path::PathIntegrator;
+use crate::ray::Ray;
+use crate::render::Integrator;
+use crate::sampler::{pass_sampler, PixelRng, Sampler};
+use crate::vec3::Color;
+
+/// Paths traced to estimate the brightness of the image, Markov chains run
+/// side by side, the probability that a mutation draws all of a path's
+/// numbers anew, and how far the other mutations move each number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MltSettings {
+    pub bootstrap: u32,
+    pub chains: u32,
+    pub large_step: f64,
+    /// Standard deviation of a small step, in the `[0, 1)` the numbers live
+    /// in.
+    pub sigma: f64,
+}
+
+impl Default for MltSettings {
+    fn default() -> Self {
+        MltSettings {
+            bootstrap: 100_000,
+            chains: 1000,
+            large_step: 0.3,
+            sigma: 0.01,
+        }
+    }
+}
+
+/// One of the numbers of the current path, and what it was before the
+/// mutation in progress.
+#[derive(Clone, Copy)]
+struct PrimarySample {
+    value: f64,
+    /// The iteration that last changed `value`.
+    modified: u64,
+    backup: f64,
+    backup_modified: u64,
+}
+
+/// Numbers the camera draws from, and those the path draws from after it, so
+/// that a path drawing more or fewer numbers than the last does not shift
+/// where on the image the next one starts.
+const CAMERA_STREAM: usize = 0;
+const PATH_STREAM: usize = 1;
+const STREAMS: usize = 2;
+
+/// A sampler that hands out the same numbers again until they are mutated,
+/// for Metropolis light transport in primary sample space. Each iteration
+/// either draws every number anew, with probability `large_step`, or moves
+/// each a little, wrapping around at 0 and 1; `reject` puts back the
+/// numbers the iteration changed. Mutations happen lazily, when a number is
+/// next asked for, so numbers a path stops drawing cost nothing.
+pub struct MltSampler {
+    rng: PixelRng,
+    sigma: f64,
+    large_step_probability: f64,
+    samples: Vec<PrimarySample>,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    stream: usize,
+    index: usize,
+}
+
+impl MltSampler {
+    /// Starts out on iteration 0, a large step, drawing from `rng`.
+    pub fn new(rng: PixelRng, sigma: f64, large_step_probability: f64) -> MltSampler {
+        MltSampler {
+            rng,
+            sigma,
+            large_step_probability,
+            samples: Vec::new(),
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            stream: 0,
+            index: 0,
+        }
+    }
+
+    /// Begins the next mutation, from the start of stream 0.
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.next_f64() < self.large_step_probability;
+        self.start_stream(0);
+    }
+
+    /// Makes the following numbers come from `stream`, from its start.
+    pub fn start_stream(&mut self, stream: usize) {
+        self.stream = stream;
+        self.index = 0;
+    }
+
+    /// Keeps the numbers of the current iteration.
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /// Puts back the numbers the current iteration changed.
+    pub fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.modified == self.iteration {
+                sample.value = sample.backup;
+                sample.modified = sample.backup_modified;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    /// Brings number `i` up to date with the mutations since it was last
+    /// drawn, backing it up first.
+    fn mutate(&mut self, i: usize) {
+        while self.samples.len() <= i {
+            // As the last large step would have drawn it.
+            let value = self.rng.next_f64();
+            self.samples.push(PrimarySample {
+                value,
+                modified: self.last_large_step,
+                backup: value,
+                backup_modified: self.last_large_step,
+            });
+        }
+        let sample = &mut self.samples[i];
+        if sample.modified < self.last_large_step {
+            sample.value = self.rng.next_f64();
+            sample.modified = self.last_large_step;
+        }
+        sample.backup = sample.value;
+        sample.backup_modified = sample.modified;
+        if self.large_step {
+            sample.value = self.rng.next_f64();
+        } else if sample.modified < self.iteration {
+            // Small steps since the number was last drawn add up to one
+            // with their variances summed.
+            let sigma = self.sigma * ((self.iteration - sample.modified) as f64).sqrt();
+            let (u1, u2) = (self.rng.next_f64(), self.rng.next_f64());
+            let normal = (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * PI * u2).cos();
+            let value = sample.value + sigma * normal;
+            sample.value = value - value.floor();
+            if sample.value >= 1.0 {
+                sample.value = 0.0;
+            }
+        }
+        sample.modified = self.iteration;
+    }
+}
+
+impl Sampler for MltSampler {
+    fn next_f64(&mut self) -> f64 {
+        let i = self.stream + STREAMS * self.index;
+        self.index += 1;
+        self.mutate(i);
+        self.samples[i].value
+    }
+}
+
+/// Where a path lands on the image, the light it carries and how bright
+/// that is.
+struct PathSample {
+    s: f64,
+    t: f64,
+    color: Color,
+    luminance: f64,
+}
+
+/// Bootstrap paths and chains draw from streams of a seed of their own.
+const MLT_SEED: u64 = 0x6d65_7472_6f70_6f6c;
+
+/// Metropolis light transport in primary sample space, after Kelemen et al.:
+/// Markov chains wander among the numbers `path` draws, including those
+/// picking where on the image it starts, proposing small and large changes
+/// to them and keeping each with the probability that spends time on paths
+/// in proportion to their brightness. Paths that are hard to find, like
+/// light through a gap or focused by glass, are then explored once found.
+///
+/// Brightness is normalised by the average of `MltSettings::bootstrap`
+/// independent paths, which also pick where the chains start. The chains run
+/// on the rayon thread pool and lay their light onto a film, so the
+/// integrator renders the whole image at once.
+pub struct MltIntegrator {
+    pub path: PathIntegrator,
+    settings: MltSettings,
+    seed: u64,
+}
+
+impl MltIntegrator {
+    pub fn new(path: PathIntegrator, settings: MltSettings) -> MltIntegrator {
+        MltIntegrator { path, settings, seed: 0 }
+    }
+
+    /// Seeds the chains and bootstrap paths, which draw from streams of
+    /// their own for each pass.
+    pub fn with_seed(mut self, seed: u64) -> MltIntegrator {
+        self.seed = seed;
+        self
+    }
+
+    fn sampler(&self, stream: u64, pass: u64) -> MltSampler {
+        MltSampler::new(pass_sampler(self.seed ^ MLT_SEED, stream, pass), self.settings.sigma, self.settings.large_step)
+    }
+
+    /// Traces the path the numbers of `sampler` make. `extent` is how far `s`
+    /// and `t` reach, past 1 for the last column and row of pixels.
+    fn trace(&self, sampler: &mut MltSampler, world: &dyn Hittable, camera: &dyn Camera, extent: (f64, f64)) -> PathSample {
+        sampler.start_stream(CAMERA_STREAM);
+        let s = sampler.next_f64() * extent.0;
+        let t = sampler.next_f64() * extent.1;
+        let r = camera.get_ray(sampler, s, t);
+        sampler.start_stream(PATH_STREAM);
+        let color = self.path.ray_color(sampler, &r, world);
+        PathSample { s, t, color, luminance: luminance(color) }
+    }
+}
+
+/// The luminance of `c`, or 0 if it is not finite.
+fn luminance(c: Color) -> f64 {
+    let y = 0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z;
+    if y.is_finite() && y > 0.0 {
+        y
+    } else {
+        0.0
+    }
+}
+
+impl Integrator for MltIntegrator {
+    /// The light along `r` as `path` finds it, since a single ray gives the
+    /// chains nowhere to go.
+    fn ray_color(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable) -> Color {
+        self.path.ray_color(sampler, r, world)
+    }
+
+    fn splats(&self) -> bool {
+        true
+    }
+
+    fn whole_image(&self) -> bool {
+        true
+    }
+
+    fn render_film(
+        &self,
+        world: &dyn Hittable,
+        camera: &dyn Camera,
+        film: &Film,
+        pass: u64,
+        paths: u64,
+        stop: &(dyn Fn() -> bool + Sync),
+        progress: &(dyn Fn(usize, usize) + Sync),
+    ) -> bool {
+        let (width, height) = (film.width() as f64, film.height() as f64);
+        let extent = (width / (width - 1.0), height / (height - 1.0));
+        // Streams 0 to `bootstrap` belong to the bootstrap paths, and the
+        // two after those to each chain.
+        let bootstrap = self.settings.bootstrap as u64;
+        let mut cdf: Vec<f64> = (0..bootstrap)
+            .into_par_iter()
+            .map(|i| self.trace(&mut self.sampler(i, pass), world, camera, extent).luminance)
+            .collect();
+        for i in 1..cdf.len() {
+            cdf[i] += cdf[i - 1];
+        }
+        let total = cdf.last().copied().unwrap_or(0.0);
+        if total <= 0.0 {
+            return !stop();
+        }
+        // Light per unit of image area for a path at luminance 1, such that
+        // paths spread in proportion to their luminance add up to the
+        // average the bootstrap found.
+        let scale = total / bootstrap as f64 * extent.0 * extent.1;
+
+        let chains = (self.settings.chains as u64).min(paths);
+        let done = AtomicUsize::new(0);
+        (0..chains).into_par_iter().all(|chain| {
+            let stream = bootstrap + 2 * chain;
+            let mut rng = pass_sampler(self.seed ^ MLT_SEED, stream, pass);
+            // Starting from a bootstrap path picked by its luminance leaves
+            // the chain no start-up bias to burn off.
+            let target = rng.next_f64() * total;
+            let start = cdf.partition_point(|&c| c <= target).min(cdf.len() - 1);
+            let mut sampler = self.sampler(start as u64, pass);
+            let mut current = self.trace(&mut sampler, world, camera, extent);
+            sampler.rng = pass_sampler(self.seed ^ MLT_SEED, stream + 1, pass);
+
+            let mutations = paths / chains + u64::from(chain < paths % chains);
+            for mutation in 0..mutations {
+                if mutation % 1024 == 0 && stop() {
+                    return false;
+                }
+                sampler.start_iteration();
+                let proposed = self.trace(&mut sampler, world, camera, extent);
+                let accept = (proposed.luminance / current.luminance).min(1.0);
+                // Both paths get the share of the light that their chances
+                // of being the next state give them.
+                if accept > 0.0 {
+                    film.splat(proposed.s, proposed.t, proposed.color * (accept * scale / proposed.luminance));
+                }
+                if accept < 1.0 {
+                    film.splat(current.s, current.t, current.color * ((1.0 - accept) * scale / current.luminance));
+                }
+                if rng.next_f64() < accept {
+                    current = proposed;
+                    sampler.accept();
+                } else {
+                    sampler.reject();
+                }
+            }
+            progress(done.fetch_add(1, Ordering::Relaxed) + 1, chains as usize);
+            true
+        })
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use std::sync::Arc;
+
+use image::ImageError;
+
+use crate::hittable_list::HittableList;
+use crate::material::{Dielactric, DiffuseLight, Lambertian, Material, Metal};
+use crate::texture::{ImageTexture, SolidColor, Texture};
+use crate::triangle::{Face, MeshData, TriangleMesh};
+use crate::vec3::{Color, Vec3};
+
+/// A Wavefront OBJ model: one vertex buffer shared by a mesh per `usemtl`
+/// group.
+pub struct Obj {
+    pub data: Arc<MeshData>,
+    /// The faces of each material group, with the group's material.
+    pub groups: Vec<(Arc<dyn Material>, Vec<usize>)>,
+}
+
+impl Obj {
+    /// Loads an OBJ file and the MTL libraries it names. Relative `mtllib` and
+    /// `map_Kd` paths are resolved against the directory containing the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Obj, ObjError> {
+        let path = path.as_ref();
+        let source = read(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Obj::parse(&source, base_dir)
+    }
+
+    pub fn parse(source: &str, base_dir: &Path) -> Result<Obj, ObjError> {
+        let mut data = MeshData::default();
+        let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+        let mut groups: Vec<(Option<String>, Vec<usize>)> = vec![(None, Vec::new())];
+
+        for (index, line) in source.lines().enumerate() {
+            let line_no = index + 1;
+            let err = |message: String| ObjError::Parse { line: line_no, message };
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => data.positions.push(read_vec3(&mut words).map_err(err)?),
+                Some("vn") => data.normals.push(read_vec3(&mut words).map_err(err)?),
+                Some("vt") => {
+                    let u = read_f64(&mut words).map_err(err)?;
+                    let v = match words.next() {
+                        Some(word) => parse_f64(word).map_err(err)?,
+                        None => 0.0,
+                    };
+                    data.uvs.push((u, v));
+                }
+                Some("f") => {
+                    let corners = words
+                        .map(|word| read_corner(word, &data))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(err)?;
+                    if corners.len() < 3 {
+                        return Err(err("a face needs at least three vertices".to_string()));
+                    }
+                    let faces = &mut groups.last_mut().unwrap().1;
+                    for i in 1..corners.len() - 1 {
+                        let corners = [corners[0], corners[i], corners[i + 1]];
+                        faces.push(data.faces.len());
+                        data.faces.push(Face {
+                            positions: corners.map(|c| c.0),
+                            uvs: all_some(corners.map(|c| c.1)),
+                            normals: all_some(corners.map(|c| c.2)),
+                        });
+                    }
+                }
+                Some("usemtl") => {
+                    let name = words.next().ok_or_else(|| err("usemtl needs a name".to_string()))?;
+                    if !materials.contains_key(name) {
+                        return Err(err(format!("unknown material `{}`", name)));
+                    }
+                    groups.push((Some(name.to_string()), Vec::new()));
+                }
+                Some("mtllib") => {
+                    for name in words {
+                        parse_mtl(&base_dir.join(name), &mut materials)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let groups = groups
+            .into_iter()
+            .filter(|(_, faces)| !faces.is_empty())
+            .map(|(name, faces)| {
+                let material = match name {
+                    Some(name) => materials[&name].clone(),
+                    None => default_material(),
+                };
+                (material, faces)
+            })
+            .collect();
+        Ok(Obj { data: Arc::new(data), groups })
+    }
+
+    /// One `TriangleMesh` per material group.
+    pub fn into_hittable(self) -> HittableList {
+        let mut list = HittableList::new();
+        for (material, faces) in self.groups {
+            list.add(Arc::new(TriangleMesh::from_faces(self.data.clone(), faces, material)));
+        }
+        list
+    }
+
+    /// The whole model as a single mesh, ignoring the MTL materials.
+    pub fn with_material(self, mp: Arc<dyn Material>) -> TriangleMesh {
+        TriangleMesh::new(self.data, mp)
+    }
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { line: usize, message: String },
+    Mtl { path: PathBuf, line: usize, message: String },
+    Texture { path: PathBuf, error: ImageError },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ObjError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ObjError::Mtl { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ObjError::Texture { path, error } => write!(f, "cannot load image `{}`: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+#[derive(Default)]
+struct MtlDesc {
+    kd: Option<Color>,
+    ks: Option<Color>,
+    ke: Option<Color>,
+    ns: Option<f64>,
+    ni: Option<f64>,
+    dissolve: Option<f64>,
+    illum: Option<i32>,
+    map_kd: Option<PathBuf>,
+}
+
+impl MtlDesc {
+    /// Picks the closest of our materials: emitters become `DiffuseLight`,
+    /// transparent or refractive ones `Dielactric` with index `Ni`, purely
+    /// specular ones `Metal` (rougher for a lower `Ns`) and everything else
+    /// `Lambertian` with `map_Kd` or `Kd`.
+    fn build(self) -> Result<Arc<dyn Material>, ObjError> {
+        let is_black = |c: Option<Color>| c.is_none_or(|c| c.x <= 0.0 && c.y <= 0.0 && c.z <= 0.0);
+        if !is_black(self.ke) {
+            return Ok(Arc::new(DiffuseLight::new(Arc::new(SolidColor::new(self.ke.unwrap())))));
+        }
+        if self.dissolve.is_some_and(|d| d < 1.0) || matches!(self.illum, Some(4) | Some(6) | Some(7)) {
+            return Ok(Arc::new(Dielactric::new(self.ni.unwrap_or(1.5))));
+        }
+        if !is_black(self.ks) && (is_black(self.kd) || self.illum == Some(3)) && self.map_kd.is_none() {
+            let fuzz = (1.0 - self.ns.unwrap_or(0.0) / 1000.0).clamp(0.0, 1.0);
+            return Ok(Arc::new(Metal::new(self.ks.unwrap(), fuzz)));
+        }
+        let albedo: Arc<dyn Texture> = match self.map_kd {
+            Some(path) => {
+                let texture = ImageTexture::open(&path).map_err(|error| ObjError::Texture { path, error })?;
+                Arc::new(texture)
+            }
+            None => Arc::new(SolidColor::new(self.kd.unwrap_or(Color::new(0.8, 0.8, 0.8)))),
+        };
+        Ok(Arc::new(Lambertian::new(albedo)))
+    }
+}
+
+fn parse_mtl(path: &Path, materials: &mut HashMap<String, Arc<dyn Material>>) -> Result<(), ObjError> {
+    let source = read(path)?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut current: Option<(String, MtlDesc)> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let err = |message: String| ObjError::Mtl {
+            path: path.to_path_buf(),
+            line: index + 1,
+            message,
+        };
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            None => continue,
+            Some(word) if word.starts_with('#') => continue,
+            Some(word) => word,
+        };
+        if keyword == "newmtl" {
+            let name = words.next().ok_or_else(|| err("newmtl needs a name".to_string()))?;
+            if let Some((name, desc)) = current.take() {
+                materials.insert(name, desc.build()?);
+            }
+            current = Some((name.to_string(), MtlDesc::default()));
+            continue;
+        }
+        let desc = match &mut current {
+            Some((_, desc)) => desc,
+            None => return Err(err(format!("`{}` before any newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => desc.kd = Some(read_vec3(&mut words).map_err(err)?),
+            "Ks" => desc.ks = Some(read_vec3(&mut words).map_err(err)?),
+            "Ke" => desc.ke = Some(read_vec3(&mut words).map_err(err)?),
+            "Ns" => desc.ns = Some(read_f64(&mut words).map_err(err)?),
+            "Ni" => desc.ni = Some(read_f64(&mut words).map_err(err)?),
+            "d" => desc.dissolve = Some(read_f64(&mut words).map_err(err)?),
+            "Tr" => desc.dissolve = Some(1.0 - read_f64(&mut words).map_err(err)?),
+            "illum" => desc.illum = Some(read_f64(&mut words).map_err(err)? as i32),
+            // Options such as `-bm 1` may precede the file name, which comes last.
+            "map_Kd" => {
+                let name = words.last().ok_or_else(|| err("map_Kd needs a file name".to_string()))?;
+                desc.map_kd = Some(base_dir.join(name));
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, desc)) = current {
+        materials.insert(name, desc.build()?);
+    }
+    Ok(())
+}
+
+fn default_material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(0.8, 0.8, 0.8)))))
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|error| ObjError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn parse_f64(word: &str) -> Result<f64, String> {
+    word.parse().map_err(|_| format!("expected a number, found `{}`", word))
+}
+
+fn read_f64(words: &mut SplitWhitespace) -> Result<f64, String> {
+    parse_f64(words.next().ok_or_else(|| "missing number".to_string())?)
+}
+
+fn read_vec3(words: &mut SplitWhitespace) -> Result<Vec3, String> {
+    Ok(Vec3::new(read_f64(words)?, read_f64(words)?, read_f64(words)?))
+}
+
+/// Parses a face corner `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero-based
+/// indices, resolving negative (relative) indices.
+fn read_corner(word: &str, data: &MeshData) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = word.split('/');
+    let position = resolve_index(parts.next().unwrap_or(""), data.positions.len(), "vertex")?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(part) => Some(resolve_index(part, data.uvs.len(), "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(part) => Some(resolve_index(part, data.normals.len(), "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex `{}`", word));
+    }
+    Ok((position, uv, normal))
+}
+
+fn resolve_index(part: &str, len: usize, what: &str) -> Result<usize, String> {
+    let index: i64 = part
+        .parse()
+        .map_err(|_| format!("expected a {} index, found `{}`", what, part))?;
+    let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!("{} index {} is out of range", what, index));
+    }
+    Ok(resolved as usize)
+}
+
+fn all_some(indices: [Option<usize>; 3]) -> Option<[usize; 3]> {
+    match indices {
+        [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+        _ => None,
+    }
+}
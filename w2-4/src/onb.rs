@@ -0,0 +1,26 @@
+use crate::vec3::Vec3;
+
+/// An orthonormal basis with `w` along a given direction.
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: &Vec3) -> Onb {
+        let w = n.unit_vector();
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).unit_vector();
+        let u = w.cross(&v);
+        Onb { u, v, w }
+    }
+
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        self.u * a.x + self.v * a.y + self.w * a.z
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::Path;
+
+use image::codecs::hdr::HdrEncoder;
+use image::{DynamicImage, ImageBuffer, ImageError, ImageOutputFormat, Rgb};
+
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Color;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    /// ASCII PPM (P3)
+    Ppm,
+    /// Binary PPM (P6)
+    PpmBinary,
+    /// 8-bit PNG
+    Png,
+    /// 16-bit PNG
+    Png16,
+    /// Radiance RGBE, linear
+    Hdr,
+    /// OpenEXR with 32-bit float channels, linear
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "hdr" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    /// The file extension `from_path` recognises the format by.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm | ImageFormat::PpmBinary => "ppm",
+            ImageFormat::Png | ImageFormat::Png16 => "png",
+            ImageFormat::Hdr => "hdr",
+            ImageFormat::Exr => "exr",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    Io(io::Error),
+    Encode(ImageError),
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputError::Io(e) => write!(f, "{}", e),
+            OutputError::Encode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+impl From<io::Error> for OutputError {
+    fn from(e: io::Error) -> Self {
+        OutputError::Io(e)
+    }
+}
+
+impl From<ImageError> for OutputError {
+    fn from(e: ImageError) -> Self {
+        OutputError::Encode(e)
+    }
+}
+
+pub fn save<P: AsRef<Path>>(fb: &Framebuffer, path: P, format: ImageFormat) -> Result<(), OutputError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(fb, &mut out, format)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Like `save`, but writes a temporary file next to `path` and renames it
+/// over `path`, so the file at `path` is always a complete image.
+pub fn save_atomic<P: AsRef<Path>>(fb: &Framebuffer, path: P, format: ImageFormat) -> Result<(), OutputError> {
+    let path = path.as_ref();
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    save(fb, &partial, format)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Encodes the framebuffer. The low dynamic range formats are gamma corrected
+/// with gamma 2 and clamped; HDR and EXR keep the linear values.
+pub fn write<W: Write>(fb: &Framebuffer, out: &mut W, format: ImageFormat) -> Result<(), OutputError> {
+    let (width, height) = (fb.width as u32, fb.height as u32);
+    match format {
+        ImageFormat::Ppm => {
+            writeln!(out, "P3")?;
+            writeln!(out, "{} {}", width, height)?;
+            writeln!(out, "255")?;
+            for pixel in fb.pixels.iter() {
+                let [r, g, b] = to_u8(pixel);
+                writeln!(out, "{} {} {}", r, g, b)?;
+            }
+        }
+        ImageFormat::PpmBinary => {
+            write!(out, "P6\n{} {}\n255\n", width, height)?;
+            let data: Vec<u8> = fb.pixels.iter().flat_map(to_u8).collect();
+            out.write_all(&data)?;
+        }
+        ImageFormat::Png => {
+            let data = fb.pixels.iter().flat_map(to_u8).collect();
+            let img = ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, data).unwrap();
+            encode(DynamicImage::ImageRgb8(img), out, ImageOutputFormat::Png)?;
+        }
+        ImageFormat::Png16 => {
+            let data = fb.pixels.iter().flat_map(to_u16).collect();
+            let img = ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, data).unwrap();
+            encode(DynamicImage::ImageRgb16(img), out, ImageOutputFormat::Png)?;
+        }
+        ImageFormat::Hdr => {
+            let data: Vec<Rgb<f32>> = fb.pixels.iter().map(|p| Rgb(to_f32(p))).collect();
+            HdrEncoder::new(out).encode(&data, fb.width, fb.height)?;
+        }
+        ImageFormat::Exr => {
+            let data = fb.pixels.iter().flat_map(to_f32).collect();
+            let img = ImageBuffer::<Rgb<f32>, _>::from_raw(width, height, data).unwrap();
+            encode(DynamicImage::ImageRgb32F(img), out, ImageOutputFormat::OpenExr)?;
+        }
+    }
+    Ok(())
+}
+
+// Some encoders need to seek, so they go through a buffer in memory.
+fn encode<W: Write>(img: DynamicImage, out: &mut W, format: ImageOutputFormat) -> Result<(), OutputError> {
+    let mut buf = Cursor::new(Vec::new());
+    img.write_to(&mut buf, format)?;
+    out.write_all(buf.get_ref())?;
+    Ok(())
+}
+
+fn gamma(c: f64) -> f64 {
+    c.max(0.0).sqrt()
+}
+
+fn to_u8(pixel: &Color) -> [u8; 3] {
+    let f = |c: f64| (256.0 * gamma(c).clamp(0.0, 0.999)) as u8;
+    [f(pixel.x), f(pixel.y), f(pixel.z)]
+}
+
+fn to_u16(pixel: &Color) -> [u16; 3] {
+    let f = |c: f64| (65536.0 * gamma(c).clamp(0.0, 0.99999)) as u16;
+    [f(pixel.x), f(pixel.y), f(pixel.z)]
+}
+
+fn to_f32(pixel: &Color) -> [f32; 3] {
+    [pixel.x as f32, pixel.y as f32, pixel.z as f32]
+}
@@ -0,0 +1,229 @@
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::ScatterRecord;
+use crate::pdf::{HittablePdf, MixturePdf, Pdf};
+use crate::ray::Ray;
+use crate::render::Integrator;
+use crate::sampler::Sampler;
+use crate::vec3::{Color, Point3, Vec3};
+
+/// Bounces a path may make of each kind of scattering. Volume bounces are
+/// those off a material whose `Material::is_volume` is set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bounces {
+    pub diffuse: i32,
+    pub specular: i32,
+    pub volume: i32,
+}
+
+/// `RecursiveIntegrator` as a loop: the path carries the product of its
+/// attenuations and sampling weights as it goes, and once it has bounced
+/// `roulette_depth` times it survives each further bounce with a probability
+/// given by that throughput, being reweighted to make up for the paths that
+/// stopped. The result matches `RecursiveIntegrator` in expectation, but
+/// dim paths end early and `max_depth` can be set high without the cost.
+///
+/// By default diffuse bounces mix light sampling into their PDF like
+/// `RecursiveIntegrator` does. With `next_event` they, and glossy bounces
+/// (see `Material::specular_pdf`), instead send a shadow ray towards a point
+/// picked on `lights` as well as sampling the material, and weigh what each
+/// finds of the lights against the other with the power heuristic.
+pub struct PathIntegrator {
+    pub background: Color,
+    /// Ray segments per path, counted as `RecursiveIntegrator` counts them.
+    pub max_depth: i32,
+    pub bounces: Bounces,
+    pub roulette_depth: i32,
+    pub lights: HittableList,
+    pub next_event: bool,
+}
+
+impl PathIntegrator {
+    /// Limits only the total depth, with roulette from the third bounce on.
+    pub fn new(background: Color, max_depth: i32) -> PathIntegrator {
+        PathIntegrator {
+            background,
+            max_depth,
+            bounces: Bounces {
+                diffuse: max_depth,
+                specular: max_depth,
+                volume: max_depth,
+            },
+            roulette_depth: 3,
+            lights: HittableList::new(),
+            next_event: false,
+        }
+    }
+
+    pub fn with_lights(mut self, lights: HittableList) -> PathIntegrator {
+        self.lights = lights;
+        self
+    }
+
+    pub fn with_bounces(mut self, bounces: Bounces) -> PathIntegrator {
+        self.bounces = bounces;
+        self
+    }
+
+    /// Starts the roulette after `depth` bounces; `max_depth` or more turns
+    /// it off.
+    pub fn with_roulette_depth(mut self, depth: i32) -> PathIntegrator {
+        self.roulette_depth = depth;
+        self
+    }
+
+    /// Turns on next-event estimation with multiple importance sampling.
+    pub fn with_next_event(mut self, next_event: bool) -> PathIntegrator {
+        self.next_event = next_event;
+        self
+    }
+
+    /// The light reaching `rec` from a point picked on `lights`, reflected by
+    /// `bsdf`, which gives the material's reflectance times cosine in a
+    /// direction and its density of sampling it. Weighted for MIS against the
+    /// material's own sampling.
+    fn light_sample<F>(&self, sampler: &mut dyn Sampler, world: &dyn Hittable, r_in: &Ray, rec: &HitRecord, bsdf: F) -> Color
+    where
+        F: Fn(&Vec3) -> (Color, f64),
+    {
+        let direction = self.lights.random(sampler, &rec.p);
+        let light_pdf = self.lights.pdf_value(&rec.p, &direction);
+        if light_pdf <= 0.0 {
+            return Color::black();
+        }
+        let (f, bsdf_pdf) = bsdf(&direction);
+        if f.length_squared() <= 0.0 {
+            return Color::black();
+        }
+        // Whatever emitter the shadow ray reaches first counts, the same as
+        // if the material had sampled the direction.
+        match world.hit(sampler, &Ray::new(rec.p, direction, r_in.time), 0.001, f64::INFINITY) {
+            None => Color::black(),
+            Some(light) => {
+                f * light.mat_ptr.emitted(light.u, light.v, &light.p) * power_heuristic(light_pdf, bsdf_pdf) / light_pdf
+            }
+        }
+    }
+}
+
+/// The MIS weight of a sample drawn with density `a` against another
+/// strategy with density `b`.
+pub fn power_heuristic(a: f64, b: f64) -> f64 {
+    let (a2, b2) = (a * a, b * b);
+    if a2 + b2 > 0.0 {
+        a2 / (a2 + b2)
+    } else {
+        0.0
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn ray_color(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable) -> Color {
+        let mut radiance = Color::black();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::new(r.origin, r.direction, r.time);
+        let mut bounced = Bounces { diffuse: 0, specular: 0, volume: 0 };
+        // Where the last bounce was and the material's density of sampling
+        // the ray, if next-event estimation also saw the lights from there.
+        let mut sampled_from: Option<(Point3, f64)> = None;
+
+        for depth in 1..=self.max_depth {
+            let rec = match world.hit(sampler, &ray, 0.001, f64::INFINITY) {
+                None => {
+                    radiance = radiance + throughput * self.background;
+                    break;
+                }
+                Some(rec) => rec,
+            };
+            let emitted = rec.mat_ptr.emitted(rec.u, rec.v, &rec.p);
+            if emitted.length_squared() > 0.0 {
+                let weight = sampled_from.map_or(1.0, |(origin, bsdf_pdf)| {
+                    power_heuristic(bsdf_pdf, self.lights.pdf_value(&origin, &ray.direction))
+                });
+                radiance = radiance + throughput * emitted * weight;
+            }
+
+            let (weight, scattered) = match rec.mat_ptr.scatter(sampler, &ray, &rec) {
+                None => break,
+                Some(ScatterRecord::Specular { attenuation, ray: scattered }) => {
+                    bounced.specular += 1;
+                    if bounced.specular > self.bounces.specular {
+                        break;
+                    }
+                    let mat = rec.mat_ptr;
+                    let glossy_pdf = if self.next_event {
+                        mat.specular_pdf(&ray, &rec, &scattered.direction)
+                    } else {
+                        0.0
+                    };
+                    sampled_from = None;
+                    if glossy_pdf > 0.0 {
+                        let direct = self.light_sample(sampler, world, &ray, &rec, |d| {
+                            let pdf = mat.specular_pdf(&ray, &rec, d);
+                            (attenuation * pdf, pdf)
+                        });
+                        radiance = radiance + throughput * direct;
+                        sampled_from = Some((rec.p, glossy_pdf));
+                    }
+                    (attenuation, scattered)
+                }
+                Some(ScatterRecord::Diffuse { attenuation, pdf }) => {
+                    let (count, limit) = if rec.mat_ptr.is_volume() {
+                        (&mut bounced.volume, self.bounces.volume)
+                    } else {
+                        (&mut bounced.diffuse, self.bounces.diffuse)
+                    };
+                    *count += 1;
+                    if *count > limit {
+                        break;
+                    }
+                    if self.next_event {
+                        let mat = rec.mat_ptr;
+                        let direct = self.light_sample(sampler, world, &ray, &rec, |d| {
+                            let scattering_pdf = mat.scattering_pdf(&ray, &rec, &Ray::new(rec.p, *d, ray.time));
+                            (attenuation * scattering_pdf, pdf.value(d))
+                        });
+                        radiance = radiance + throughput * direct;
+
+                        let direction = pdf.generate(sampler);
+                        let pdf_val = pdf.value(&direction);
+                        if pdf_val <= 0.0 {
+                            break;
+                        }
+                        let scattered = Ray::new(rec.p, direction, ray.time);
+                        let scattering_pdf = mat.scattering_pdf(&ray, &rec, &scattered);
+                        sampled_from = Some((rec.p, pdf_val));
+                        (attenuation * scattering_pdf / pdf_val, scattered)
+                    } else {
+                        let (direction, pdf_val) = if self.lights.objects.is_empty() {
+                            let direction = pdf.generate(sampler);
+                            (direction, pdf.value(&direction))
+                        } else {
+                            let light_pdf = HittablePdf::new(&self.lights, rec.p);
+                            let mixed_pdf = MixturePdf::new(&light_pdf, &*pdf);
+                            let direction = mixed_pdf.generate(sampler);
+                            (direction, mixed_pdf.value(&direction))
+                        };
+                        if pdf_val <= 0.0 {
+                            break;
+                        }
+                        let scattered = Ray::new(rec.p, direction, ray.time);
+                        let scattering_pdf = rec.mat_ptr.scattering_pdf(&ray, &rec, &scattered);
+                        (attenuation * scattering_pdf / pdf_val, scattered)
+                    }
+                }
+            };
+            throughput = throughput * weight;
+
+            if depth > self.roulette_depth {
+                let survival = throughput.x.max(throughput.y).max(throughput.z).min(1.0);
+                if survival <= 0.0 || sampler.next_f64() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+            ray = scattered;
+        }
+        radiance
+    }
+}
@@ -0,0 +1,94 @@
+use std::f64::consts::PI;
+
+use crate::hittable::Hittable;
+use crate::onb::Onb;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
+
+/// A distribution of directions that can be sampled and evaluated.
+pub trait Pdf {
+    fn value(&self, direction: &Vec3) -> f64;
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3;
+}
+
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(w: &Vec3) -> CosinePdf {
+        CosinePdf {
+            uvw: Onb::build_from_w(w),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cosine = direction.unit_vector().dot(&self.uvw.w);
+        (cosine / PI).max(0.0)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.uvw.local(&Vec3::random_cosine_direction(sampler))
+    }
+}
+
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::random_unit_vector(sampler)
+    }
+}
+
+/// Directions from `origin` towards a hittable, usually the lights.
+pub struct HittablePdf<'a> {
+    origin: Point3,
+    ptr: &'a dyn Hittable,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(ptr: &'a dyn Hittable, origin: Point3) -> HittablePdf<'a> {
+        HittablePdf { origin, ptr }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.ptr.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.ptr.random(sampler, &self.origin)
+    }
+}
+
+/// Picks either of two distributions with equal probability.
+pub struct MixturePdf<'a> {
+    p: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> MixturePdf<'a> {
+        MixturePdf { p: [p0, p1] }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        if sampler.next_f64() < 0.5 {
+            self.p[0].generate(sampler)
+        } else {
+            self.p[1].generate(sampler)
+        }
+    }
+}
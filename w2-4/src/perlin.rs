@@ -1,5 +1,4 @@
-use rand::prelude::ThreadRng;
-use rand::Rng;
+use crate::sampler::Sampler;
 use crate::vec3::{Point3, Vec3};
 
 #[derive(Clone)]
@@ -12,20 +11,20 @@ pub struct Perlin {
 
 const PERLIN_POINT_COUNT: usize = 256;
 impl Perlin {
-    pub fn new(rng: & mut ThreadRng) -> Perlin {
+    pub fn new<S: Sampler + ?Sized>(sampler: &mut S) -> Perlin {
         let mut random_vec: [Vec3; PERLIN_POINT_COUNT] = [Vec3::zero(); PERLIN_POINT_COUNT];
-        for i in 0..PERLIN_POINT_COUNT {
-            random_vec[i] = Point3::random_range(rng, -1.0..1.0).unit_vector();
+        for v in random_vec.iter_mut() {
+            *v = Point3::random_range(sampler, -1.0..1.0).unit_vector();
         }
 
-        let perm_x = perlin_generate_perm(rng);
-        let perm_y = perlin_generate_perm(rng);
-        let perm_z = perlin_generate_perm(rng);
+        let perm_x = perlin_generate_perm(sampler);
+        let perm_y = perlin_generate_perm(sampler);
+        let perm_z = perlin_generate_perm(sampler);
         Perlin {
-            random_vec: random_vec,
-            perm_x: perm_x,
-            perm_y: perm_y,
-            perm_z: perm_z
+            random_vec,
+            perm_x,
+            perm_y,
+            perm_z
         }
     }
 
@@ -78,18 +77,18 @@ impl Perlin {
     }
 }
 
-fn perlin_generate_perm(rng: &mut ThreadRng) -> [i32; PERLIN_POINT_COUNT] {
+fn perlin_generate_perm<S: Sampler + ?Sized>(sampler: &mut S) -> [i32; PERLIN_POINT_COUNT] {
     let mut p = [0; PERLIN_POINT_COUNT];
-    for i in 0..PERLIN_POINT_COUNT {
-        p[i] = i as i32;
+    for (i, v) in p.iter_mut().enumerate() {
+        *v = i as i32;
     }
-    permute(rng, &mut p, PERLIN_POINT_COUNT);
+    permute(sampler, &mut p, PERLIN_POINT_COUNT);
     p
 }
 
-fn permute(rng: &mut ThreadRng, p: &mut [i32], n: usize) {
+fn permute<S: Sampler + ?Sized>(sampler: &mut S, p: &mut [i32], n: usize) {
     for i in (1..n).rev() {
-        let target = rng.gen_range(0..=i);
+        let target = sampler.next_index(i + 1);
         p.swap(i, target);
     }
 }
@@ -101,9 +100,9 @@ fn trilinear_interp(c: [[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
     let ww = w*w*(3.0-2.0*w);
     let mut accum = 0.0;
 
-    for i in 0..=1 {
-        for j in 0..=1 {
-            for k in 0..=1 {
+    for (i, ci) in c.iter().enumerate() {
+        for (j, cij) in ci.iter().enumerate() {
+            for (k, cijk) in cij.iter().enumerate() {
 
                 let i_f = i as f64;
                 let j_f = j as f64;
@@ -113,7 +112,7 @@ fn trilinear_interp(c: [[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
                 accum +=
                     (i_f * uu + (1.0 - i_f) * (1.0 - uu)) *
                     (j_f * vv + (1.0 - j_f) * (1.0 - vv)) *
-                    (k_f * ww + (1.0 - k_f) * (1.0 - ww)) * c[i][j][k].dot(&weight_v)
+                    (k_f * ww + (1.0 - k_f) * (1.0 - ww)) * cijk.dot(&weight_v)
             }
         }
     }
@@ -0,0 +1,420 @@
+use std::collections::BinaryHeap;
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::material::ScatterRecord;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::render::Integrator;
+use crate::sampler::{pass_sampler, Sampler};
+use crate::vec3::{Color, Point3, Vec3};
+
+/// Photons traced per pass and the radius they are gathered from; a radius
+/// of 0 picks one from how closely the photons of the first pass landed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhotonSettings {
+    pub photons: u32,
+    pub radius: f64,
+}
+
+impl Default for PhotonSettings {
+    fn default() -> Self {
+        PhotonSettings {
+            photons: 200_000,
+            radius: 0.0,
+        }
+    }
+}
+
+/// Power arriving at a point of a diffuse surface.
+#[derive(Clone, Copy)]
+pub struct Photon {
+    pub p: Point3,
+    /// Unit vector back towards where the photon came from.
+    pub direction: Vec3,
+    pub power: Color,
+}
+
+/// Photons in a kd-tree: each slice of `photons` holds its median along
+/// `axes` at its middle index, with the photons below it on the left.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<u8>,
+}
+
+/// Photons per random stream, so that tracing does not depend on threads.
+const CHUNK: usize = 4096;
+/// Photon streams use a seed of their own so they never repeat a pixel's.
+const PHOTON_SEED: u64 = 0x7068_6f74_6f6e_7321;
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> PhotonMap {
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+        PhotonMap { photons, axes }
+    }
+
+    /// Traces `count` photons from points picked on `lights`, each carrying
+    /// an equal share of their power, and keeps those landing on diffuse
+    /// surfaces of `world`. Photons pass through specular bounces and media,
+    /// are stored at every diffuse bounce, and are stopped by Russian
+    /// roulette or after `max_depth` bounces. They draw from the streams of
+    /// `seed` and `pass` and leave at a time picked in `shutter`.
+    pub fn trace(
+        world: &dyn Hittable,
+        lights: &HittableList,
+        count: usize,
+        max_depth: i32,
+        shutter: (f64, f64),
+        seed: u64,
+        pass: u64,
+    ) -> PhotonMap {
+        let emitters: Vec<&dyn Hittable> =
+            lights.objects.iter().map(|light| &**light).filter(|light| light.area() > 0.0).collect();
+        if emitters.is_empty() || count == 0 {
+            return PhotonMap::new(Vec::new());
+        }
+        let chunks: Vec<Vec<Photon>> = (0..count.div_ceil(CHUNK))
+            .into_par_iter()
+            .map(|chunk| {
+                let mut sampler = pass_sampler(seed ^ PHOTON_SEED, chunk as u64, pass);
+                let mut photons = Vec::new();
+                for _ in chunk * CHUNK..((chunk + 1) * CHUNK).min(count) {
+                    trace_photon(&mut sampler, world, &emitters, count, max_depth, shutter, &mut photons);
+                }
+                photons
+            })
+            .collect();
+        PhotonMap::new(chunks.into_iter().flatten().collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// Calls `f` with every photon within `radius` of `p`.
+    pub fn within<F: FnMut(&Photon)>(&self, p: &Point3, radius: f64, mut f: F) {
+        within(&self.photons, &self.axes, p, radius * radius, &mut f);
+    }
+
+    /// The distance from `p` to its `k`th nearest photon, or to the
+    /// furthest if there are fewer.
+    pub fn nearest_distance(&self, p: &Point3, k: usize) -> f64 {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        nearest(&self.photons, &self.axes, p, k.max(1), &mut heap);
+        heap.peek().map_or(0.0, |&bits| f64::from_bits(bits).sqrt())
+    }
+
+    /// A gathering radius that would find about `k` photons around a typical
+    /// photon: the median distance from a sample of photons to their `k`th
+    /// nearest neighbour.
+    pub fn typical_radius(&self, k: usize) -> f64 {
+        if self.photons.is_empty() {
+            return 0.0;
+        }
+        let step = (self.photons.len() / 256).max(1);
+        let mut distances: Vec<f64> =
+            self.photons.iter().step_by(step).map(|photon| self.nearest_distance(&photon.p, k)).collect();
+        let mid = distances.len() / 2;
+        *distances.select_nth_unstable_by(mid, f64::total_cmp).1
+    }
+}
+
+fn build(photons: &mut [Photon], axes: &mut [u8]) {
+    if photons.is_empty() {
+        return;
+    }
+    let (mut min, mut max) = (photons[0].p, photons[0].p);
+    for photon in photons.iter() {
+        min = Vec3::new(min.x.min(photon.p.x), min.y.min(photon.p.y), min.z.min(photon.p.z));
+        max = Vec3::new(max.x.max(photon.p.x), max.y.max(photon.p.y), max.z.max(photon.p.z));
+    }
+    let extent = max - min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| a.p.d(axis).total_cmp(&b.p.d(axis)));
+    axes[mid] = axis as u8;
+    let (left, right) = photons.split_at_mut(mid);
+    let (left_axes, right_axes) = axes.split_at_mut(mid);
+    build(left, left_axes);
+    build(&mut right[1..], &mut right_axes[1..]);
+}
+
+fn within<F: FnMut(&Photon)>(photons: &[Photon], axes: &[u8], p: &Point3, radius_squared: f64, f: &mut F) {
+    if photons.is_empty() {
+        return;
+    }
+    let mid = photons.len() / 2;
+    let photon = &photons[mid];
+    if (photon.p - *p).length_squared() <= radius_squared {
+        f(photon);
+    }
+    let axis = axes[mid] as i32;
+    let d = p.d(axis) - photon.p.d(axis);
+    let (near, far) = if d < 0.0 { (0..mid, mid + 1..photons.len()) } else { (mid + 1..photons.len(), 0..mid) };
+    within(&photons[near.clone()], &axes[near], p, radius_squared, f);
+    if d * d <= radius_squared {
+        within(&photons[far.clone()], &axes[far], p, radius_squared, f);
+    }
+}
+
+/// Keeps the squared distances of the `k` nearest photons in `heap`, as bit
+/// patterns, which order like the non-negative floats they hold.
+fn nearest(photons: &[Photon], axes: &[u8], p: &Point3, k: usize, heap: &mut BinaryHeap<u64>) {
+    if photons.is_empty() {
+        return;
+    }
+    let mid = photons.len() / 2;
+    let photon = &photons[mid];
+    heap.push((photon.p - *p).length_squared().to_bits());
+    if heap.len() > k {
+        heap.pop();
+    }
+    let axis = axes[mid] as i32;
+    let d = p.d(axis) - photon.p.d(axis);
+    let (near, far) = if d < 0.0 { (0..mid, mid + 1..photons.len()) } else { (mid + 1..photons.len(), 0..mid) };
+    nearest(&photons[near.clone()], &axes[near], p, k, heap);
+    let furthest = heap.peek().map_or(f64::INFINITY, |&bits| f64::from_bits(bits));
+    if heap.len() < k || d * d <= furthest {
+        nearest(&photons[far.clone()], &axes[far], p, k, heap);
+    }
+}
+
+/// Emits one photon from a point picked on `emitters`, in a cosine-weighted
+/// direction to either side since lights emit from both.
+fn trace_photon(
+    sampler: &mut dyn Sampler,
+    world: &dyn Hittable,
+    emitters: &[&dyn Hittable],
+    count: usize,
+    max_depth: i32,
+    shutter: (f64, f64),
+    photons: &mut Vec<Photon>,
+) {
+    let light = emitters[sampler.next_index(emitters.len())];
+    let rec = match light.sample_surface(sampler) {
+        None => return,
+        Some(rec) => rec,
+    };
+    let pdf_pos = 1.0 / (emitters.len() as f64 * light.area());
+    let local = Vec3::random_cosine_direction(sampler);
+    let side = if sampler.next_f64() < 0.5 { 1.0 } else { -1.0 };
+    let direction = Onb::build_from_w(&rec.normal).local(&local) * side;
+    let time = if shutter.0 < shutter.1 { sampler.next_range(shutter.0..shutter.1) } else { shutter.0 };
+    // Cosine over the density of the point and the direction.
+    let mut power = rec.mat_ptr.emitted(rec.u, rec.v, &rec.p) * (2.0 * PI / (pdf_pos * count as f64));
+    let mut ray = Ray::new(rec.p, direction, time);
+
+    for _ in 0..max_depth {
+        let rec = match world.hit(sampler, &ray, 0.001, f64::INFINITY) {
+            None => break,
+            Some(rec) => rec,
+        };
+        let (attenuation, scattered) = match rec.mat_ptr.scatter(sampler, &ray, &rec) {
+            None => break,
+            Some(ScatterRecord::Specular { attenuation, ray }) => (attenuation, ray),
+            Some(ScatterRecord::Diffuse { attenuation, pdf }) => {
+                if !rec.mat_ptr.is_volume() {
+                    photons.push(Photon {
+                        p: rec.p,
+                        direction: -ray.direction.unit_vector(),
+                        power,
+                    });
+                }
+                let direction = pdf.generate(sampler);
+                let pdf_val = pdf.value(&direction);
+                if pdf_val <= 0.0 {
+                    break;
+                }
+                let scattered = Ray::new(rec.p, direction, ray.time);
+                let scattering_pdf = rec.mat_ptr.scattering_pdf(&ray, &rec, &scattered);
+                (attenuation * scattering_pdf / pdf_val, scattered)
+            }
+        };
+        // Survive with the fraction of power the bounce keeps, so surviving
+        // photons keep their power.
+        let survival = max_component(attenuation).min(1.0);
+        if survival <= 0.0 || sampler.next_f64() >= survival {
+            break;
+        }
+        power = power * attenuation / survival;
+        ray = scattered;
+    }
+}
+
+fn max_component(c: Color) -> f64 {
+    c.x.max(c.y).max(c.z)
+}
+
+/// Photon mapping: photons traced from `lights` are gathered at the first
+/// diffuse surface a camera path reaches after following specular bounces,
+/// estimating the light reflected there from their density within a
+/// radius. Caustics, which path tracing only finds by chance, come out as
+/// readily as the rest of the light. The estimate is blurred by the radius.
+///
+/// Progressive, it is stochastic progressive photon mapping in the form of
+/// Knaus and Zwicker: every pass of a progressive render traces fresh
+/// photons and gathers them with a smaller radius, so the average of the
+/// passes converges to the right image as passes are added.
+///
+/// Only lights that can pick points on their surface emit photons, and
+/// light from the background is only seen directly or through specular
+/// bounces.
+pub struct PhotonMapIntegrator {
+    pub background: Color,
+    pub max_depth: i32,
+    lights: HittableList,
+    settings: PhotonSettings,
+    shutter: (f64, f64),
+    seed: u64,
+    progressive: bool,
+    radius: f64,
+    map: PhotonMap,
+}
+
+impl PhotonMapIntegrator {
+    /// No photons are traced until `trace`.
+    pub fn new(background: Color, max_depth: i32, settings: PhotonSettings) -> PhotonMapIntegrator {
+        PhotonMapIntegrator {
+            background,
+            max_depth,
+            lights: HittableList::new(),
+            settings,
+            shutter: (0.0, 0.0),
+            seed: 0,
+            progressive: false,
+            radius: settings.radius,
+            map: PhotonMap::new(Vec::new()),
+        }
+    }
+
+    pub fn with_lights(mut self, lights: HittableList) -> PhotonMapIntegrator {
+        self.lights = lights;
+        self
+    }
+
+    /// Spreads the photons over the camera's shutter interval.
+    pub fn with_shutter(mut self, time0: f64, time1: f64) -> PhotonMapIntegrator {
+        self.shutter = (time0, time1);
+        self
+    }
+
+    /// Seeds the photons' random streams, as `RenderSettings::seed` does the
+    /// pixels'.
+    pub fn with_seed(mut self, seed: u64) -> PhotonMapIntegrator {
+        self.seed = seed;
+        self
+    }
+
+    /// Traces new photons with a smaller radius for every pass.
+    pub fn with_progressive(mut self, progressive: bool) -> PhotonMapIntegrator {
+        self.progressive = progressive;
+        self
+    }
+
+    /// Traces the first pass's photons into `world`, and picks the radius if
+    /// the settings leave it to the integrator.
+    pub fn trace(mut self, world: &dyn Hittable) -> PhotonMapIntegrator {
+        self.map = self.trace_pass(world, 0);
+        if self.settings.radius <= 0.0 {
+            self.radius = self.map.typical_radius(50);
+        }
+        self
+    }
+
+    pub fn photon_map(&self) -> &PhotonMap {
+        &self.map
+    }
+
+    /// The radius photons are gathered from in pass `pass`, shrinking so
+    /// that its square follows `r(i+1)^2 = r(i)^2 (i + a) / (i + 1)` with
+    /// `a` of 2/3 over passes counted from 1; always the first pass's unless
+    /// progressive.
+    pub fn radius(&self, pass: u64) -> f64 {
+        const ALPHA: f64 = 2.0 / 3.0;
+        if !self.progressive {
+            return self.radius;
+        }
+        let scale: f64 = (1..=pass).map(|i| (i as f64 + ALPHA) / (i as f64 + 1.0)).product();
+        self.radius * scale.sqrt()
+    }
+
+    fn trace_pass(&self, world: &dyn Hittable, pass: u64) -> PhotonMap {
+        let count = self.settings.photons as usize;
+        PhotonMap::trace(world, &self.lights, count, self.max_depth, self.shutter, self.seed, pass)
+    }
+}
+
+impl Integrator for PhotonMapIntegrator {
+    fn ray_color(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable) -> Color {
+        let mut radiance = Color::black();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::new(r.origin, r.direction, r.time);
+
+        for _ in 0..self.max_depth {
+            let rec = match world.hit(sampler, &ray, 0.001, f64::INFINITY) {
+                None => return radiance + throughput * self.background,
+                Some(rec) => rec,
+            };
+            radiance = radiance + throughput * rec.mat_ptr.emitted(rec.u, rec.v, &rec.p);
+            let (weight, scattered) = match rec.mat_ptr.scatter(sampler, &ray, &rec) {
+                None => break,
+                Some(ScatterRecord::Specular { attenuation, ray }) => (attenuation, ray),
+                Some(ScatterRecord::Diffuse { attenuation, pdf }) if rec.mat_ptr.is_volume() => {
+                    let direction = pdf.generate(sampler);
+                    let pdf_val = pdf.value(&direction);
+                    if pdf_val <= 0.0 {
+                        break;
+                    }
+                    let scattered = Ray::new(rec.p, direction, ray.time);
+                    (attenuation * rec.mat_ptr.scattering_pdf(&ray, &rec, &scattered) / pdf_val, scattered)
+                }
+                Some(ScatterRecord::Diffuse { attenuation, .. }) => {
+                    if self.radius <= 0.0 {
+                        break;
+                    }
+                    let mut reflected = Color::black();
+                    self.map.within(&rec.p, self.radius, |photon| {
+                        let cosine = rec.normal.dot(&photon.direction);
+                        if cosine > 0.0 {
+                            let towards = Ray::new(rec.p, photon.direction, ray.time);
+                            let brdf = attenuation * rec.mat_ptr.scattering_pdf(&ray, &rec, &towards) / cosine;
+                            reflected = reflected + brdf * photon.power;
+                        }
+                    });
+                    radiance = radiance + throughput * reflected / (PI * self.radius * self.radius);
+                    break;
+                }
+            };
+            throughput = throughput * weight;
+            ray = scattered;
+        }
+        radiance
+    }
+
+    fn for_pass(&self, world: &dyn Hittable, pass: u64) -> Option<Box<dyn Integrator + '_>> {
+        if !self.progressive || pass == 0 {
+            return None;
+        }
+        Some(Box::new(PhotonMapIntegrator {
+            lights: self.lights.clone(),
+            radius: self.radius(pass),
+            map: self.trace_pass(world, pass),
+            progressive: false,
+            ..*self
+        }))
+    }
+}
@@ -0,0 +1,230 @@
+use std::f64::consts::PI;
+
+use crate::camera::Camera;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
+
+/// How a camera maps the image onto directions. Only the perspective one has
+/// a lens; the others are pinholes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// The usual camera, with `vfov` and a thin lens.
+    Perspective,
+    /// Parallel rays from a view `height` scene units tall, for technical
+    /// views.
+    Orthographic { height: f64 },
+    /// An equidistant fisheye whose field of view across the height of the
+    /// image is `fov` degrees; the corners see further round, up to straight
+    /// behind.
+    Fisheye { fov: f64 },
+    /// The whole sphere of directions, longitude along the width and
+    /// latitude along the height, centred on `lookat`. Meant for 2:1 images.
+    Equirectangular,
+    /// The six 90 degree faces of a cube in a 3x2 grid: right, left and up
+    /// along the top row, down, front and back along the bottom one. Meant
+    /// for 3:2 images.
+    CubeMap,
+}
+
+/// Where a camera stands and looks over its shutter interval.
+#[derive(Clone, Copy)]
+pub struct Pose {
+    lookfrom: Point3,
+    lookat: Point3,
+    vup: Vec3,
+    /// Where the camera is and looks at `time1`, if it moves.
+    motion: Option<(Point3, Point3)>,
+    time0: f64,
+    time1: f64,
+}
+
+/// The camera's position and axes at one time; it looks along `-w`.
+struct Basis {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Pose {
+    pub fn new(lookfrom: Point3, lookat: Point3, vup: Vec3, time0: f64, time1: f64) -> Pose {
+        Pose { lookfrom, lookat, vup, motion: None, time0, time1 }
+    }
+
+    /// Moves the camera in a straight line to `lookfrom1`, looking at
+    /// `lookat1`, by `time1`.
+    pub fn with_motion(mut self, lookfrom1: Point3, lookat1: Point3) -> Pose {
+        let moves = (lookfrom1 - self.lookfrom).length_squared() > 0.0 || (lookat1 - self.lookat).length_squared() > 0.0;
+        self.motion = (moves && self.time0 < self.time1).then_some((lookfrom1, lookat1));
+        self
+    }
+
+    fn time(&self, sampler: &mut dyn Sampler) -> f64 {
+        if self.time0 < self.time1 {
+            sampler.next_range(self.time0..self.time1)
+        } else {
+            self.time0
+        }
+    }
+
+    fn basis(&self, time: f64) -> Basis {
+        let (lookfrom, lookat) = match self.motion {
+            None => (self.lookfrom, self.lookat),
+            Some((lookfrom1, lookat1)) => {
+                let t = (time - self.time0) / (self.time1 - self.time0);
+                (self.lookfrom + (lookfrom1 - self.lookfrom) * t, self.lookat + (lookat1 - self.lookat) * t)
+            }
+        };
+        let w = (lookfrom - lookat).unit_vector();
+        let u = self.vup.cross(&w).unit_vector();
+        let v = w.cross(&u);
+        Basis { origin: lookfrom, u, v, w }
+    }
+
+    /// A ray from the camera at a sampled time, along `direction` given in
+    /// the camera's axes.
+    fn ray(&self, sampler: &mut dyn Sampler, direction: impl Fn(&Basis) -> Vec3) -> Ray {
+        let time = self.time(sampler);
+        let basis = self.basis(time);
+        Ray::new(basis.origin, direction(&basis), time)
+    }
+}
+
+pub struct OrthographicCamera {
+    pose: Pose,
+    width: f64,
+    height: f64,
+    exposure: f64,
+}
+
+impl OrthographicCamera {
+    pub fn new(pose: Pose, height: f64, aspect_ratio: f64) -> OrthographicCamera {
+        OrthographicCamera { pose, width: height * aspect_ratio, height, exposure: 1.0 }
+    }
+
+    pub fn with_exposure(mut self, exposure: f64) -> OrthographicCamera {
+        self.exposure = exposure;
+        self
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, sampler: &mut dyn Sampler, s: f64, t: f64) -> Ray {
+        let time = self.pose.time(sampler);
+        let b = self.pose.basis(time);
+        let origin = b.origin + b.u * ((s - 0.5) * self.width) + b.v * ((t - 0.5) * self.height);
+        Ray::new(origin, -b.w, time)
+    }
+
+    fn exposure(&self) -> f64 {
+        self.exposure
+    }
+}
+
+pub struct FisheyeCamera {
+    pose: Pose,
+    /// Radians from the axis per unit of distance from the image centre,
+    /// measured in half image heights.
+    radians_per_unit: f64,
+    aspect_ratio: f64,
+    exposure: f64,
+}
+
+impl FisheyeCamera {
+    pub fn new(pose: Pose, fov: f64, aspect_ratio: f64) -> FisheyeCamera {
+        FisheyeCamera { pose, radians_per_unit: fov.to_radians() / 2.0, aspect_ratio, exposure: 1.0 }
+    }
+
+    pub fn with_exposure(mut self, exposure: f64) -> FisheyeCamera {
+        self.exposure = exposure;
+        self
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, sampler: &mut dyn Sampler, s: f64, t: f64) -> Ray {
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let theta = (x.hypot(y) * self.radians_per_unit).min(PI);
+        let phi = y.atan2(x);
+        self.pose.ray(sampler, |b| {
+            (b.u * phi.cos() + b.v * phi.sin()) * theta.sin() - b.w * theta.cos()
+        })
+    }
+
+    fn exposure(&self) -> f64 {
+        self.exposure
+    }
+}
+
+pub struct EquirectangularCamera {
+    pose: Pose,
+    exposure: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new(pose: Pose) -> EquirectangularCamera {
+        EquirectangularCamera { pose, exposure: 1.0 }
+    }
+
+    pub fn with_exposure(mut self, exposure: f64) -> EquirectangularCamera {
+        self.exposure = exposure;
+        self
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, sampler: &mut dyn Sampler, s: f64, t: f64) -> Ray {
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (t - 0.5) * PI;
+        self.pose.ray(sampler, |b| {
+            (b.u * longitude.sin() - b.w * longitude.cos()) * latitude.cos() + b.v * latitude.sin()
+        })
+    }
+
+    fn exposure(&self) -> f64 {
+        self.exposure
+    }
+}
+
+pub struct CubeMapCamera {
+    pose: Pose,
+    exposure: f64,
+}
+
+impl CubeMapCamera {
+    pub fn new(pose: Pose) -> CubeMapCamera {
+        CubeMapCamera { pose, exposure: 1.0 }
+    }
+
+    pub fn with_exposure(mut self, exposure: f64) -> CubeMapCamera {
+        self.exposure = exposure;
+        self
+    }
+}
+
+impl Camera for CubeMapCamera {
+    fn get_ray(&self, sampler: &mut dyn Sampler, s: f64, t: f64) -> Ray {
+        let column = ((s * 3.0) as usize).min(2);
+        let row = ((t * 2.0) as usize).min(1);
+        let x = 2.0 * (s * 3.0 - column as f64) - 1.0;
+        let y = 2.0 * (t * 2.0 - row as f64) - 1.0;
+        self.pose.ray(sampler, |b| {
+            // Each face's forward, right and up directions.
+            let (forward, right, up) = match (row, column) {
+                (1, 0) => (b.u, b.w, b.v),
+                (1, 1) => (-b.u, -b.w, b.v),
+                (1, _) => (b.v, b.u, b.w),
+                (_, 0) => (-b.v, b.u, -b.w),
+                (_, 1) => (-b.w, b.u, b.v),
+                _ => (b.w, -b.u, b.v),
+            };
+            forward + right * x + up * y
+        })
+    }
+
+    fn exposure(&self) -> f64 {
+        self.exposure
+    }
+}
@@ -1,8 +1,10 @@
+use std::sync::Arc;
 use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::vec3::Vec3;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
 
 #[derive(Clone)]
 pub struct XyRect {
@@ -11,11 +13,11 @@ pub struct XyRect {
     y0: f64,
     y1: f64,
     k: f64,
-    mp: Box<dyn Material>,
+    mp: Arc<dyn Material>,
 }
 
 impl XyRect {
-    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, mp: Box<dyn Material>) -> XyRect {
+    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, mp: Arc<dyn Material>) -> XyRect {
         XyRect {
             x0,
             x1,
@@ -25,10 +27,8 @@ impl XyRect {
             mp,
         }
     }
-}
 
-impl Hittable for XyRect {
-    fn hit(&self, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord> {
+    fn intersect(&self, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord<'_>> {
         let t = (self.k-r.origin.z) / r.direction.z;
         if t < t0 || t > t1 {
             return None;
@@ -42,14 +42,20 @@ impl Hittable for XyRect {
             p: r.at(t),
             normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
             mat_ptr: &*self.mp,
-            t: t,
+            t,
             u: (x-self.x0)/(self.x1-self.x0),
             v: (y-self.y0)/(self.y1-self.y0),
             front_face: false
         };
-        rec.set_face_normal(&r, &Vec3::new(0.0, 0.0, 1.0));
+        rec.set_face_normal(r, &Vec3::new(0.0, 0.0, 1.0));
         Some(rec)
     }
+}
+
+impl Hittable for XyRect {
+    fn hit(&self, _: &mut dyn Sampler, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord<'_>> {
+        self.intersect(r, t0, t1)
+    }
 
     fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
         Some(Aabb::new(
@@ -57,6 +63,38 @@ impl Hittable for XyRect {
             Vec3::new(self.x1, self.y1, self.k + 0.0001),
         ))
     }
+
+    fn pdf_value(&self, o: &Point3, v: &Vec3) -> f64 {
+        let hit = self.intersect(&Ray::new(*o, *v, 0.0), 0.001, f64::INFINITY);
+        rect_pdf_value(hit, (self.x1 - self.x0) * (self.y1 - self.y0), v)
+    }
+
+    fn random(&self, sampler: &mut dyn Sampler, o: &Point3) -> Vec3 {
+        let random_point = Point3::new(
+            sampler.next_range(self.x0..self.x1),
+            sampler.next_range(self.y0..self.y1),
+            self.k,
+        );
+        random_point - *o
+    }
+
+    fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        let x = sampler.next_range(self.x0..self.x1);
+        let y = sampler.next_range(self.y0..self.y1);
+        Some(HitRecord {
+            p: Point3::new(x, y, self.k),
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            mat_ptr: &*self.mp,
+            t: 0.0,
+            u: (x - self.x0) / (self.x1 - self.x0),
+            v: (y - self.y0) / (self.y1 - self.y0),
+            front_face: true,
+        })
+    }
+
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
 }
 
 #[derive(Clone)]
@@ -66,11 +104,11 @@ pub struct XzRect {
     z0: f64,
     z1: f64,
     k: f64,
-    mp: Box<dyn Material>,
+    mp: Arc<dyn Material>,
 }
 
 impl XzRect {
-    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, mp: Box<dyn Material>) -> XzRect {
+    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, mp: Arc<dyn Material>) -> XzRect {
         XzRect {
             x0,
             x1,
@@ -80,37 +118,73 @@ impl XzRect {
             mp,
         }
     }
+
+    fn intersect(&self, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord<'_>> {
+        let t = (self.k - r.origin.y) / r.direction.y;
+        if t < t0 || t > t1 {
+            return None;
+        }
+        let x = r.origin.x + t * r.direction.x;
+        let z = r.origin.z + t * r.direction.z;
+        if x < self.x0 || x > self.x1 || z < self.z0 || z > self.z1 {
+            return None
+        }
+        let mut rec = HitRecord {
+            p: r.at(t),
+            normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            mat_ptr: &*self.mp,
+            t,
+            u: (x - self.x0) / (self.x1 - self.x0),
+            v: (z - self.z0) / (self.z1 - self.z0),
+            front_face: false
+        };
+        rec.set_face_normal(r, &Vec3::new(0.0, 1.0, 0.0));
+        Some(rec)
+    }
 }
 
 impl Hittable for XzRect {
-    fn hit(&self, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord> {
-            let t = (self.k - r.origin.y) / r.direction.y;
-            if t < t0 || t > t1 {
-                return None;
-            }
-            let x = r.origin.x + t * r.direction.x;
-            let z = r.origin.z + t * r.direction.z;
-            if x < self.x0 || x > self.x1 || z < self.z0 || z > self.z1 {
-                return None
-            }
-            let mut rec = HitRecord {
-                p: r.at(t),
-                normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
-                mat_ptr: &*self.mp,
-                t: t,
-                u: (x - self.x0) / (self.x1 - self.x0),
-                v: (z - self.z0) / (self.z1 - self.z0),
-                front_face: false
-            };
-            rec.set_face_normal(&r, &Vec3::new(0.0, 1.0, 0.0));
-            Some(rec)
-        }
+    fn hit(&self, _: &mut dyn Sampler, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord<'_>> {
+        self.intersect(r, t0, t1)
+    }
     fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
         Some(Aabb::new(
             Vec3::new(self.x0, self.k - 0.0001, self.z0),
             Vec3::new(self.x1, self.k + 0.0001, self.z1),
         ))
     }
+
+    fn pdf_value(&self, o: &Point3, v: &Vec3) -> f64 {
+        let hit = self.intersect(&Ray::new(*o, *v, 0.0), 0.001, f64::INFINITY);
+        rect_pdf_value(hit, (self.x1 - self.x0) * (self.z1 - self.z0), v)
+    }
+
+    fn random(&self, sampler: &mut dyn Sampler, o: &Point3) -> Vec3 {
+        let random_point = Point3::new(
+            sampler.next_range(self.x0..self.x1),
+            self.k,
+            sampler.next_range(self.z0..self.z1),
+        );
+        random_point - *o
+    }
+
+    fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        let x = sampler.next_range(self.x0..self.x1);
+        let z = sampler.next_range(self.z0..self.z1);
+        Some(HitRecord {
+            p: Point3::new(x, self.k, z),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            mat_ptr: &*self.mp,
+            t: 0.0,
+            u: (x - self.x0) / (self.x1 - self.x0),
+            v: (z - self.z0) / (self.z1 - self.z0),
+            front_face: true,
+        })
+    }
+
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.z1 - self.z0)
+    }
 }
 
 #[derive(Clone)]
@@ -120,11 +194,11 @@ pub struct YzRect {
     z0: f64,
     z1: f64,
     k: f64,
-    mp: Box<dyn Material>,
+    mp: Arc<dyn Material>,
 }
 
 impl YzRect {
-    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, mp: Box<dyn Material>) -> YzRect {
+    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, mp: Arc<dyn Material>) -> YzRect {
         YzRect {
             y0,
             y1,
@@ -134,10 +208,8 @@ impl YzRect {
             mp,
         }
     }
-}
 
-impl Hittable for YzRect {
-    fn hit(&self, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord> {
+    fn intersect(&self, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord<'_>> {
         let t = (self.k - r.origin.x) / r.direction.x;
         if t < t0 || t > t1 {
             return None;
@@ -151,18 +223,69 @@ impl Hittable for YzRect {
             p: r.at(t),
             normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
             mat_ptr: &*self.mp,
-            t: t,
+            t,
             u: (y - self.y0) / (self.y1 - self.y0),
             v: (z - self.z0) / (self.z1 - self.z0),
             front_face: false
         };
-        rec.set_face_normal(&r, &Vec3::new(1.0, 0.0, 0.0));
+        rec.set_face_normal(r, &Vec3::new(1.0, 0.0, 0.0));
         Some(rec)
     }
+}
+
+impl Hittable for YzRect {
+    fn hit(&self, _: &mut dyn Sampler, r: &Ray, t0: f64, t1: f64) -> Option<HitRecord<'_>> {
+        self.intersect(r, t0, t1)
+    }
     fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
         Some(Aabb::new(
             Vec3::new(self.k - 0.0001, self.y0, self.z0),
             Vec3::new(self.k + 0.0001, self.y1, self.z1),
         ))
     }
+
+    fn pdf_value(&self, o: &Point3, v: &Vec3) -> f64 {
+        let hit = self.intersect(&Ray::new(*o, *v, 0.0), 0.001, f64::INFINITY);
+        rect_pdf_value(hit, (self.y1 - self.y0) * (self.z1 - self.z0), v)
+    }
+
+    fn random(&self, sampler: &mut dyn Sampler, o: &Point3) -> Vec3 {
+        let random_point = Point3::new(
+            self.k,
+            sampler.next_range(self.y0..self.y1),
+            sampler.next_range(self.z0..self.z1),
+        );
+        random_point - *o
+    }
+
+    fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        let y = sampler.next_range(self.y0..self.y1);
+        let z = sampler.next_range(self.z0..self.z1);
+        Some(HitRecord {
+            p: Point3::new(self.k, y, z),
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            mat_ptr: &*self.mp,
+            t: 0.0,
+            u: (y - self.y0) / (self.y1 - self.y0),
+            v: (z - self.z0) / (self.z1 - self.z0),
+            front_face: true,
+        })
+    }
+
+    fn area(&self) -> f64 {
+        (self.y1 - self.y0) * (self.z1 - self.z0)
+    }
+}
+
+/// Converts the uniform area density of a rectangle into a solid-angle
+/// density in direction `v`, given where `v` hits the rectangle.
+fn rect_pdf_value(hit: Option<HitRecord>, area: f64, v: &Vec3) -> f64 {
+    match hit {
+        None => 0.0,
+        Some(rec) => {
+            let distance_squared = rec.t * rec.t * v.length_squared();
+            let cosine = (v.dot(&rec.normal) / v.length()).abs();
+            distance_squared / (cosine * area)
+        }
+    }
 }
@@ -0,0 +1,461 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::camera::Camera;
+use crate::framebuffer::{Accumulator, Film, Framebuffer};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::material::ScatterRecord;
+use crate::mlt::MltSettings;
+use crate::pdf::{HittablePdf, MixturePdf, Pdf};
+use crate::photon::PhotonSettings;
+use crate::ray::Ray;
+use crate::sampler::{pass_sampler, Sampler};
+use crate::vec3::Color;
+
+#[derive(Clone, Copy)]
+pub struct RenderSettings {
+    pub image_width: i32,
+    pub image_height: i32,
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub background: Color,
+    /// Seeds the per-pixel random streams; the same seed gives the same image.
+    pub seed: u64,
+    pub integrator: IntegratorKind,
+}
+
+/// Which integrator `Scene::renderer` estimates radiance with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntegratorKind {
+    /// `RecursiveIntegrator`.
+    Recursive,
+    /// `PathIntegrator`, which mixes light sampling into the material's PDF.
+    Path,
+    /// `PathIntegrator` with next-event estimation and multiple importance
+    /// sampling.
+    Mis,
+    /// `BdptIntegrator`.
+    Bdpt,
+    /// `PhotonMapIntegrator`.
+    PhotonMap(PhotonSettings),
+    /// `PhotonMapIntegrator`, progressive.
+    Sppm(PhotonSettings),
+    /// `MltIntegrator` on top of the `Mis` path tracer.
+    Mlt(MltSettings),
+}
+
+impl IntegratorKind {
+    /// The photon settings of the photon mapping integrators.
+    pub fn photons_mut(&mut self) -> Option<&mut PhotonSettings> {
+        match self {
+            IntegratorKind::PhotonMap(photons) | IntegratorKind::Sppm(photons) => Some(photons),
+            _ => None,
+        }
+    }
+
+    /// The settings of the Metropolis integrator.
+    pub fn mlt_mut(&mut self) -> Option<&mut MltSettings> {
+        match self {
+            IntegratorKind::Mlt(mlt) => Some(mlt),
+            _ => None,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+}
+
+/// Estimates the radiance arriving along a camera ray.
+pub trait Integrator: Sync {
+    fn ray_color(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable) -> Color;
+
+    /// Whether the integrator also traces light from the lights towards the
+    /// camera, which lands anywhere on the image; the renderer then calls
+    /// `sample` instead of `ray_color`.
+    fn splats(&self) -> bool {
+        false
+    }
+
+    /// `ray_color` for integrators that splat: light reaching the image
+    /// elsewhere than along `r` goes onto `film`.
+    fn sample(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable, _camera: &dyn Camera, _film: &Film) -> Color {
+        self.ray_color(sampler, r, world)
+    }
+
+    /// The integrator to render pass `pass` of a progressive render with, for
+    /// integrators that change from pass to pass; `None` to use this one.
+    fn for_pass(&self, _world: &dyn Hittable, _pass: u64) -> Option<Box<dyn Integrator + '_>> {
+        None
+    }
+
+    /// Whether the integrator renders the whole image at once with
+    /// `render_film` rather than pixel by pixel, as integrators running
+    /// Markov chains over the image must.
+    fn whole_image(&self) -> bool {
+        false
+    }
+
+    /// Lays `paths` camera paths' worth of light from the streams of pass
+    /// `pass` onto `film`, calling `progress(done, total)` as it goes.
+    /// Returns false if it gave up because `stop` returned true.
+    #[allow(clippy::too_many_arguments)]
+    fn render_film(
+        &self,
+        _world: &dyn Hittable,
+        _camera: &dyn Camera,
+        _film: &Film,
+        _pass: u64,
+        _paths: u64,
+        _stop: &(dyn Fn() -> bool + Sync),
+        _progress: &(dyn Fn(usize, usize) + Sync),
+    ) -> bool {
+        true
+    }
+}
+
+/// The book's `ray_color`: follows `Material::scatter` until the ray escapes,
+/// hits a light or runs out of bounces. Diffuse bounces are importance sampled
+/// from the material's PDF, mixed half and half with directions towards
+/// `lights` when there are any.
+pub struct RecursiveIntegrator {
+    pub background: Color,
+    pub max_depth: i32,
+    pub lights: HittableList,
+}
+
+impl RecursiveIntegrator {
+    pub fn new(background: Color, max_depth: i32) -> RecursiveIntegrator {
+        RecursiveIntegrator {
+            background,
+            max_depth,
+            lights: HittableList::new(),
+        }
+    }
+
+    pub fn with_lights(mut self, lights: HittableList) -> RecursiveIntegrator {
+        self.lights = lights;
+        self
+    }
+
+    fn trace(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable, depth: i32) -> Color {
+        if depth <= 0 {
+            return Color::black();
+        }
+
+        let rec = match world.hit(sampler, r, 0.001, f64::INFINITY) {
+            None => return self.background,
+            Some(rec) => rec,
+        };
+        let emitted = rec.mat_ptr.emitted(rec.u, rec.v, &rec.p);
+        match rec.mat_ptr.scatter(sampler, r, &rec) {
+            None => emitted,
+            Some(ScatterRecord::Specular { attenuation, ray }) => {
+                emitted + attenuation * self.trace(sampler, &ray, world, depth - 1)
+            }
+            Some(ScatterRecord::Diffuse { attenuation, pdf }) => {
+                let (direction, pdf_val) = if self.lights.objects.is_empty() {
+                    let direction = pdf.generate(sampler);
+                    (direction, pdf.value(&direction))
+                } else {
+                    let light_pdf = HittablePdf::new(&self.lights, rec.p);
+                    let mixed_pdf = MixturePdf::new(&light_pdf, &*pdf);
+                    let direction = mixed_pdf.generate(sampler);
+                    (direction, mixed_pdf.value(&direction))
+                };
+                if pdf_val <= 0.0 {
+                    return emitted;
+                }
+                let scattered = Ray::new(rec.p, direction, r.time);
+                let scattering_pdf = rec.mat_ptr.scattering_pdf(r, &rec, &scattered);
+                emitted
+                    + attenuation * scattering_pdf * self.trace(sampler, &scattered, world, depth - 1)
+                        / pdf_val
+            }
+        }
+    }
+}
+
+impl Integrator for RecursiveIntegrator {
+    fn ray_color(&self, sampler: &mut dyn Sampler, r: &Ray, world: &dyn Hittable) -> Color {
+        self.trace(sampler, r, world, self.max_depth)
+    }
+}
+
+/// How `Renderer::render_progressive` splits up the samples and when it may
+/// stop early.
+#[derive(Clone, Copy)]
+pub struct Progressive {
+    /// Samples per pixel added by each pass.
+    pub pass_samples: i32,
+    /// Stops once this much time has passed, abandoning the pass in flight.
+    /// The first pass always completes.
+    pub time_limit: Option<Duration>,
+}
+
+/// Why `Renderer::render_progressive` stopped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stopped {
+    /// `settings.samples_per_pixel` were reached.
+    TargetReached,
+    /// The time limit ran out first.
+    TimeLimit,
+    /// The renderer was cancelled, e.g. by Ctrl-C.
+    Cancelled,
+}
+
+impl fmt::Display for Stopped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stopped::TargetReached => write!(f, "target reached"),
+            Stopped::TimeLimit => write!(f, "time limit reached"),
+            Stopped::Cancelled => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// A rectangle of pixels, with rows counted from the top of the image.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Covers a `width` by `height` image with tiles of at most `size` pixels
+    /// square, row by row.
+    pub fn grid(width: usize, height: usize, size: usize) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(size) {
+            for x in (0..width).step_by(size) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                });
+            }
+        }
+        tiles
+    }
+}
+
+type Progress<'a> = Box<dyn Fn(usize, usize) + Sync + 'a>;
+
+pub struct Renderer<'a> {
+    pub settings: RenderSettings,
+    integrator: Box<dyn Integrator + 'a>,
+    progress: Option<Progress<'a>>,
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl<'a> Renderer<'a> {
+    /// Creates a renderer using `RecursiveIntegrator` with the background and
+    /// depth from `settings`.
+    pub fn new(settings: RenderSettings) -> Renderer<'a> {
+        Renderer {
+            settings,
+            integrator: Box::new(RecursiveIntegrator::new(settings.background, settings.max_depth)),
+            progress: None,
+            cancel: None,
+        }
+    }
+
+    pub fn with_integrator<I: Integrator + 'a>(mut self, integrator: I) -> Renderer<'a> {
+        self.integrator = Box::new(integrator);
+        self
+    }
+
+    /// Calls `f(rows_done, rows_total)` each time a row of the image finishes,
+    /// or, for integrators that render the whole image at once, whatever
+    /// part of it they count in.
+    pub fn with_progress<F: Fn(usize, usize) + Sync + 'a>(mut self, f: F) -> Renderer<'a> {
+        self.progress = Some(Box::new(f));
+        self
+    }
+
+    /// Makes `render_pass` and `render_progressive` give up as soon as `flag`
+    /// is set, e.g. from a Ctrl-C handler.
+    pub fn with_cancel(mut self, flag: &'a AtomicBool) -> Renderer<'a> {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Renders the image on the rayon thread pool, one row per task. Each pixel
+    /// draws from its own stream seeded by `settings.seed`.
+    pub fn render(&self, world: &dyn Hittable, camera: &dyn Camera) -> Framebuffer {
+        self.render_tile(world, camera, &self.full_image())
+    }
+
+    /// Renders part of the image into a framebuffer the size of `tile`. Pixels
+    /// get the same streams as in `render`, so tiles rendered anywhere
+    /// assemble into the same image. That does not hold for integrators that
+    /// splat, whose light from other tiles' samples is missing.
+    pub fn render_tile(&self, world: &dyn Hittable, camera: &dyn Camera, tile: &Tile) -> Framebuffer {
+        self.pass(world, camera, tile, 0, self.settings.samples_per_pixel, &|| false)
+            .unwrap()
+    }
+
+    /// Renders `samples` samples per pixel from the streams of pass `pass`.
+    /// Returns `None` if cancelled before the pass finished.
+    pub fn render_pass(&self, world: &dyn Hittable, camera: &dyn Camera, pass: u64, samples: i32) -> Option<Framebuffer> {
+        self.pass(world, camera, &self.full_image(), pass, samples, &|| self.cancelled())
+    }
+
+    /// Renders passes until `settings.samples_per_pixel` is reached, the time
+    /// limit runs out or the renderer is cancelled, calling `on_pass` with the
+    /// running total after every completed pass. Continues from `accumulator`,
+    /// which must have the image's dimensions. Returns why it stopped.
+    pub fn render_progressive<F: FnMut(&Accumulator)>(
+        &self,
+        world: &dyn Hittable,
+        camera: &dyn Camera,
+        progressive: &Progressive,
+        accumulator: &mut Accumulator,
+        mut on_pass: F,
+    ) -> Stopped {
+        let deadline = progressive.time_limit.map(|limit| Instant::now() + limit);
+        let target = self.settings.samples_per_pixel as u64;
+        while accumulator.samples < target {
+            let first = accumulator.passes == 0;
+            let stop = || {
+                self.cancelled() || (!first && deadline.is_some_and(|deadline| Instant::now() >= deadline))
+            };
+            if stop() {
+                break;
+            }
+            let samples = (progressive.pass_samples as u64).min(target - accumulator.samples);
+            match self.pass(world, camera, &self.full_image(), accumulator.passes, samples as i32, &stop) {
+                Some(fb) => accumulator.add(&fb, samples),
+                None => break,
+            }
+            on_pass(accumulator);
+        }
+        if self.cancelled() {
+            Stopped::Cancelled
+        } else if accumulator.samples < target {
+            Stopped::TimeLimit
+        } else {
+            Stopped::TargetReached
+        }
+    }
+
+    fn full_image(&self) -> Tile {
+        Tile {
+            x: 0,
+            y: 0,
+            width: self.settings.image_width as usize,
+            height: self.settings.image_height as usize,
+        }
+    }
+
+    /// Whether the integrator splats light onto the image, so that tiles do
+    /// not render on their own.
+    pub fn splats(&self) -> bool {
+        self.integrator.splats()
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    fn pass(
+        &self,
+        world: &dyn Hittable,
+        camera: &dyn Camera,
+        tile: &Tile,
+        pass: u64,
+        samples: i32,
+        stop: &(dyn Fn() -> bool + Sync),
+    ) -> Option<Framebuffer> {
+        let width = self.settings.image_width as usize;
+        let rows_done = AtomicUsize::new(0);
+        let for_pass = self.integrator.for_pass(world, pass);
+        let integrator = for_pass.as_deref().unwrap_or(&*self.integrator);
+        let height = self.settings.image_height as usize;
+        let film = integrator.splats().then(|| Film::new(width, height));
+
+        let mut fb = Framebuffer::new(tile.width, tile.height);
+        if let (true, Some(film)) = (integrator.whole_image(), &film) {
+            let progress = |done, total| {
+                if let Some(progress) = &self.progress {
+                    progress(done, total);
+                }
+            };
+            let paths = samples as u64 * (width * height) as u64;
+            if !integrator.render_film(world, camera, film, pass, paths, stop, &progress) {
+                return None;
+            }
+        } else {
+            let rows: Vec<Vec<Color>> = (tile.y..tile.y + tile.height).into_par_iter().map(|y| {
+                if stop() {
+                    return None;
+                }
+                let row = (tile.x..tile.x + tile.width)
+                    .map(|x| {
+                        let mut sampler = pass_sampler(self.settings.seed, (x + y * width) as u64, pass);
+                        self.sample_pixel(&mut sampler, integrator, world, camera, film.as_ref(), x, y, samples)
+                    })
+                    .collect();
+                if let Some(progress) = &self.progress {
+                    progress(rows_done.fetch_add(1, Ordering::Relaxed) + 1, tile.height);
+                }
+                Some(row)
+            }).collect::<Option<_>>()?;
+            fb.pixels = rows.into_iter().flatten().collect();
+        }
+        if let Some(film) = film {
+            let scale = camera.exposure() / samples as f64;
+            for y in 0..tile.height {
+                for x in 0..tile.width {
+                    fb.set(x, y, fb.get(x, y) + film.get(tile.x + x, tile.y + y) * scale);
+                }
+            }
+        }
+        Some(fb)
+    }
+
+    /// Averages `samples_per_pixel` samples for the pixel at column `x` and row
+    /// `y`, counting rows from the top of the image. Light that integrators
+    /// splat is left out.
+    pub fn render_pixel(&self, sampler: &mut dyn Sampler, world: &dyn Hittable, camera: &dyn Camera, x: usize, y: usize) -> Color {
+        self.sample_pixel(sampler, &*self.integrator, world, camera, None, x, y, self.settings.samples_per_pixel)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn sample_pixel(
+        &self,
+        sampler: &mut dyn Sampler,
+        integrator: &dyn Integrator,
+        world: &dyn Hittable,
+        camera: &dyn Camera,
+        film: Option<&Film>,
+        x: usize,
+        y: usize,
+        samples: i32,
+    ) -> Color {
+        let width = self.settings.image_width;
+        let height = self.settings.image_height;
+        let j = height as usize - 1 - y;
+
+        let mut pixel_color = Color::black();
+        for _ in 0..samples {
+            let u = (x as f64 + sampler.next_f64()) / (width - 1) as f64;
+            let v = (j as f64 + sampler.next_f64()) / (height - 1) as f64;
+            let r = camera.get_ray(sampler, u, v);
+            pixel_color = pixel_color + match film {
+                None => integrator.ray_color(sampler, &r, world),
+                Some(film) => integrator.sample(sampler, &r, world, camera, film),
+            };
+        }
+        pixel_color / samples as f64 * camera.exposure()
+    }
+}
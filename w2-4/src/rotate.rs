@@ -1,18 +1,20 @@
+use std::sync::Arc;
 use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
+use crate::sampler::Sampler;
 
 #[derive(Clone)]
 pub struct RotateY {
-    pub ptr: Box<dyn Hittable>,
+    pub ptr: Arc<dyn Hittable>,
     pub sin_theta: f64,
     pub cos_theta: f64,
     pub bbox: Option<Aabb>,
 }
 
 impl RotateY {
-    pub fn new(p: Box<dyn Hittable>, angle: f64) -> RotateY {
+    pub fn new(p: Arc<dyn Hittable>, angle: f64) -> RotateY {
         let radians = angle.to_radians();
         let sin_theta = radians.sin();
         let cos_theta = radians.cos();
@@ -55,7 +57,7 @@ impl RotateY {
 }
 
 impl Hittable for RotateY {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let origin = Vec3::new(
             self.cos_theta * r.origin.x - self.sin_theta * r.origin.z,
             r.origin.y,
@@ -67,7 +69,7 @@ impl Hittable for RotateY {
             self.sin_theta * r.direction.x + self.cos_theta * r.direction.z
         );
         let rotated_r = Ray::new(origin, direction, r.time);
-        self.ptr.hit(&rotated_r, t_min, t_max).map(|rec| {
+        self.ptr.hit(sampler, &rotated_r, t_min, t_max).map(|rec| {
             let p = Vec3::new(
                 self.cos_theta * rec.p.x + self.sin_theta * rec.p.z,
                 rec.p.y,
@@ -92,7 +94,7 @@ impl Hittable for RotateY {
         })
     }
 
-    fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
         self.bbox
     }
 }
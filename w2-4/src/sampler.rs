@@ -0,0 +1,45 @@
+use std::ops::Range;
+
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Source of the uniform random numbers used while building scenes and
+/// tracing rays.
+pub trait Sampler {
+    /// Returns a number in `[0, 1)`.
+    fn next_f64(&mut self) -> f64;
+
+    fn next_range(&mut self, range: Range<f64>) -> f64 {
+        range.start + (range.end - range.start) * self.next_f64()
+    }
+
+    /// Returns an index in `0..n`.
+    fn next_index(&mut self, n: usize) -> usize {
+        ((self.next_f64() * n as f64) as usize).min(n - 1)
+    }
+}
+
+impl<R: RngCore + ?Sized> Sampler for R {
+    fn next_f64(&mut self) -> f64 {
+        self.gen()
+    }
+}
+
+pub type PixelRng = ChaCha8Rng;
+
+/// Returns the random stream for one pixel of a render. Streams depend only on
+/// `seed` and the pixel index, so the image does not depend on which thread
+/// rendered which pixel.
+pub fn pixel_sampler(seed: u64, pixel: u64) -> PixelRng {
+    pass_sampler(seed, pixel, 0)
+}
+
+/// Like `pixel_sampler`, for pass `pass` of a progressive render. Each pass
+/// starts 2^40 words further along the pixel's stream, so passes never
+/// reuse numbers and pass 0 matches `pixel_sampler`.
+pub fn pass_sampler(seed: u64, pixel: u64, pass: u64) -> PixelRng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(pixel);
+    rng.set_word_pos((pass as u128) << 40);
+    rng
+}
@@ -0,0 +1,1059 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::animated::Animated;
+use crate::box_model::BoxModel;
+use crate::bvh::Bvh;
+use crate::aperture::{Aperture, ApertureImage};
+use crate::camera::{Camera, CameraSettings, Exposure, PhysicalCamera};
+use crate::constant_medium::ConstantMedium;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::instance::Instance;
+use crate::material::{Dielactric, DiffuseLight, IsoTropic, Lambertian, Material, Metal};
+use crate::obj::Obj;
+use crate::projection::Projection;
+use crate::rect::{XyRect, XzRect, YzRect};
+use crate::rotate::RotateY;
+use crate::sampler::Sampler;
+use crate::sphere::{MovingSphere, Sphere};
+use crate::timeline::{CameraTrack, Timeline, Track};
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+use crate::transform::{AnimatedTransform, Keyframe, Quaternion, Transform};
+use crate::transformed::Transformed;
+use crate::translate::Translate;
+use crate::triangle::{Face, MeshData, Triangle, TriangleMesh};
+use crate::path::PathIntegrator;
+use crate::bdpt::BdptIntegrator;
+use crate::photon::{PhotonMapIntegrator, PhotonSettings};
+use crate::mlt::{MltIntegrator, MltSettings};
+use crate::render::{IntegratorKind, RecursiveIntegrator, RenderSettings, Renderer};
+use crate::vec3::Vec3;
+
+pub struct Scene {
+    pub world: HittableList,
+    /// Emitters that can be sampled directly. Scene files collect spheres and
+    /// rectangles with a `diffuse_light` material here automatically.
+    pub lights: HittableList,
+    pub camera: CameraSettings,
+    pub settings: RenderSettings,
+    /// The frames to render, for scene files with an `[animation]` table.
+    pub timeline: Option<Timeline>,
+    /// The shape of the lens opening, if the scene gives it as an image.
+    pub aperture_image: Option<Arc<ApertureImage>>,
+}
+
+impl Scene {
+    pub fn camera(&self) -> Box<dyn Camera> {
+        let aspect_ratio = self.settings.aspect_ratio();
+        match (&self.aperture_image, self.camera.projection) {
+            (Some(image), Projection::Perspective) => {
+                Box::new(self.camera.perspective(aspect_ratio).with_aperture(Aperture::Image(image.clone())))
+            }
+            _ => self.camera.build(aspect_ratio),
+        }
+    }
+
+    /// A renderer for the scene's settings whose integrator, the one
+    /// `settings.integrator` picks, samples the scene's lights.
+    pub fn renderer<'a>(&self) -> Renderer<'a> {
+        let (background, max_depth) = (self.settings.background, self.settings.max_depth);
+        let lights = self.lights.clone();
+        let renderer = Renderer::new(self.settings);
+        match self.settings.integrator {
+            IntegratorKind::Recursive => {
+                renderer.with_integrator(RecursiveIntegrator::new(background, max_depth).with_lights(lights))
+            }
+            IntegratorKind::Path => renderer.with_integrator(PathIntegrator::new(background, max_depth).with_lights(lights)),
+            IntegratorKind::Mis => renderer.with_integrator(
+                PathIntegrator::new(background, max_depth).with_lights(lights).with_next_event(true),
+            ),
+            IntegratorKind::Bdpt => renderer.with_integrator(BdptIntegrator::new(background, max_depth).with_lights(lights)),
+            IntegratorKind::PhotonMap(photons) | IntegratorKind::Sppm(photons) => renderer.with_integrator(
+                PhotonMapIntegrator::new(background, max_depth, photons)
+                    .with_lights(lights)
+                    .with_shutter(self.camera.time0, self.camera.time1)
+                    .with_seed(self.settings.seed)
+                    .with_progressive(matches!(self.settings.integrator, IntegratorKind::Sppm(_)))
+                    .trace(&self.world),
+            ),
+            IntegratorKind::Mlt(mlt) => renderer.with_integrator(
+                MltIntegrator::new(PathIntegrator::new(background, max_depth).with_lights(lights).with_next_event(true), mlt)
+                    .with_seed(self.settings.seed),
+            ),
+        }
+    }
+
+    /// Loads a TOML scene file. Relative texture paths are resolved against the
+    /// directory containing the file.
+    pub fn load<S: Sampler + ?Sized, P: AsRef<Path>>(sampler: &mut S, path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(SceneError::Io)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Scene::parse(sampler, &source, base_dir)
+    }
+
+    /// Loads the scene as it is in `frame` of its animation, see
+    /// `parse_frame`.
+    pub fn load_frame<S: Sampler + ?Sized, P: AsRef<Path>>(sampler: &mut S, path: P, frame: u32) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(SceneError::Io)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Scene::parse_frame(sampler, &source, base_dir, Some(frame))
+    }
+
+    /// Parses the scene as it is in its first frame, if it is animated.
+    pub fn parse<S: Sampler + ?Sized>(sampler: &mut S, source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        Scene::parse_frame(sampler, source, base_dir, None)
+    }
+
+    /// Parses the scene with the camera and keyed material parameters as
+    /// they are in `frame`, or the first frame if that is `None`. The camera's
+    /// shutter interval is the frame's, so objects are blurred by their motion
+    /// over it. Scenes without an `[animation]` table are the same in every
+    /// frame.
+    pub fn parse_frame<S: Sampler + ?Sized>(
+        sampler: &mut S,
+        source: &str,
+        base_dir: &Path,
+        frame: Option<u32>,
+    ) -> Result<Scene, SceneError> {
+        let file: SceneFile = toml::from_str(source).map_err(|e| SceneError::Parse {
+            line: e.span().map_or(0, |span| line_of(source, span.start)),
+            message: e.message().to_string(),
+        })?;
+
+        let textures: BTreeMap<String, Spanned<TextureDesc>> = entries(source, file.textures)?;
+        let materials: BTreeMap<String, Spanned<MaterialDesc>> = entries(source, file.materials)?;
+        let shapes: BTreeMap<String, Spanned<ObjectDesc>> = entries(source, file.shapes)?;
+        let objects = file.objects.into_iter().map(|object| entry(source, object)).collect::<Result<Vec<_>, _>>()?;
+
+        let (camera, aperture_image) = camera(&file.camera, source, base_dir)?;
+        let timeline = match &file.animation {
+            Some(spanned) => Some(timeline(spanned.get_ref(), line_of(source, spanned.span().start))?),
+            None => None,
+        };
+        let camera = match &timeline {
+            Some(timeline) => {
+                let (time0, time1) = timeline.shutter_interval(frame.unwrap_or(timeline.first_frame));
+                timeline.camera.at(&camera, time0, time1)
+            }
+            None => camera,
+        };
+
+        let render = file.render.as_ref().map(|r| r.get_ref()).unwrap_or(&RenderDesc::DEFAULT);
+        let image_height = ((render.image_width as f64) / render.aspect_ratio) as i32;
+        if let Some(spanned) = &file.render {
+            let line = line_of(source, spanned.span().start);
+            // Light tracing and Metropolis paths spread over the image between
+            // pixel centres, so no integrator gets fewer than two each way.
+            check(
+                render.image_width >= 2 && image_height >= 2,
+                line,
+                "image_width and aspect_ratio must give an image at least 2 pixels wide and high",
+            )?;
+            check(
+                render.samples_per_pixel > 0 && render.max_depth > 0,
+                line,
+                "samples_per_pixel and max_depth must be positive",
+            )?;
+            check(
+                render.photons.is_none_or(|photons| photons > 0) && render.photon_radius.is_none_or(|r| r >= 0.0),
+                line,
+                "photons must be positive and photon_radius must not be negative",
+            )?;
+            check(
+                matches!(render.integrator, IntegratorDesc::PhotonMap | IntegratorDesc::Sppm)
+                    || (render.photons.is_none() && render.photon_radius.is_none()),
+                line,
+                "photons and photon_radius only apply to the photon_map and sppm integrators",
+            )?;
+            check(
+                render.bootstrap_paths.is_none_or(|paths| paths > 0)
+                    && render.chains.is_none_or(|chains| chains > 0)
+                    && render.large_step_probability.is_none_or(|p| (0.0..=1.0).contains(&p))
+                    && render.mutation_sigma.is_none_or(|sigma| sigma > 0.0),
+                line,
+                "bootstrap_paths, chains and mutation_sigma must be positive and large_step_probability between 0 and 1",
+            )?;
+            check(
+                matches!(render.integrator, IntegratorDesc::Mlt)
+                    || (render.bootstrap_paths.is_none()
+                        && render.chains.is_none()
+                        && render.large_step_probability.is_none()
+                        && render.mutation_sigma.is_none()),
+                line,
+                "bootstrap_paths, chains, large_step_probability and mutation_sigma only apply to the mlt integrator",
+            )?;
+        }
+        let defaults = PhotonSettings::default();
+        let photons = PhotonSettings {
+            photons: render.photons.unwrap_or(defaults.photons),
+            radius: render.photon_radius.unwrap_or(defaults.radius),
+        };
+        let defaults = MltSettings::default();
+        let mlt = MltSettings {
+            bootstrap: render.bootstrap_paths.unwrap_or(defaults.bootstrap),
+            chains: render.chains.unwrap_or(defaults.chains),
+            large_step: render.large_step_probability.unwrap_or(defaults.large_step),
+            sigma: render.mutation_sigma.unwrap_or(defaults.sigma),
+        };
+        let settings = RenderSettings {
+            image_width: render.image_width,
+            image_height,
+            samples_per_pixel: render.samples_per_pixel,
+            max_depth: render.max_depth,
+            background: vec3(file.background),
+            seed: 0,
+            integrator: match render.integrator {
+                IntegratorDesc::Recursive => IntegratorKind::Recursive,
+                IntegratorDesc::Path => IntegratorKind::Path,
+                IntegratorDesc::Mis => IntegratorKind::Mis,
+                IntegratorDesc::Bdpt => IntegratorKind::Bdpt,
+                IntegratorDesc::PhotonMap => IntegratorKind::PhotonMap(photons),
+                IntegratorDesc::Sppm => IntegratorKind::Sppm(photons),
+                IntegratorDesc::Mlt => IntegratorKind::Mlt(mlt),
+            },
+        };
+
+        let mut builder = Builder {
+            source,
+            base_dir,
+            sampler,
+            time0: camera.time0,
+            time1: camera.time1,
+            texture_descs: &textures,
+            material_descs: &materials,
+            shape_descs: &shapes,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            shapes: HashMap::new(),
+            resolving: Vec::new(),
+        };
+
+        let mut world = HittableList::new();
+        let mut lights = HittableList::new();
+        // Objects come in file order, so count their lines incrementally;
+        // counting from the top for each one is quadratic in the file size.
+        let (mut line, mut counted) = (1, 0);
+        for object in objects.iter() {
+            let start = object.span().start.clamp(counted, source.len());
+            line += source[counted..start].matches('\n').count();
+            counted = start;
+            let hittable = builder.object(object.get_ref(), line)?;
+            if builder.is_light(object.get_ref()) {
+                lights.add(hittable.clone());
+            }
+            world.add(hittable);
+        }
+
+        Ok(Scene {
+            world,
+            lights,
+            camera,
+            settings,
+            timeline,
+            aperture_image,
+        })
+    }
+}
+
+/// The camera settings and aperture mask of the `[camera]` table, which
+/// gives the lens either by `vfov` and `aperture` or as a `[camera.lens]`.
+fn camera(desc: &Spanned<CameraDesc>, source: &str, base_dir: &Path) -> Result<(CameraSettings, Option<Arc<ApertureImage>>), SceneError> {
+    let line = line_of(source, desc.span().start);
+    let desc = desc.get_ref();
+    let (lookfrom, lookat) = (vec3(desc.lookfrom), vec3(desc.lookat));
+    let blades = desc.blades.unwrap_or(0);
+    check(blades == 0 || blades >= 3, line, "an aperture needs at least 3 blades")?;
+    let mut camera = match &desc.lens {
+        None => CameraSettings {
+            lookfrom,
+            lookat,
+            vup: vec3(desc.vup),
+            vfov: desc.vfov.unwrap_or(40.0),
+            aperture: desc.aperture.unwrap_or(0.0),
+            focus_dist: desc.focus_dist.unwrap_or(10.0),
+            time0: desc.time0,
+            time1: desc.time1,
+            lookfrom1: lookfrom,
+            lookat1: lookat,
+            blades,
+            exposure: 1.0,
+            projection: Projection::Perspective,
+        },
+        Some(lens) => {
+            check(
+                desc.vfov.is_none() && desc.aperture.is_none(),
+                line,
+                "a camera with a lens cannot also set vfov or aperture",
+            )?;
+            let [sensor_width, sensor_height] = lens.sensor;
+            check(
+                sensor_width > 0.0 && sensor_height > 0.0 && lens.focal_length > 0.0 && lens.f_number > 0.0,
+                line,
+                "lens sensor, focal_length and f_number must be positive",
+            )?;
+            check(
+                lens.units_per_metre > 0.0 && lens.shutter_time.is_none_or(|t| t > 0.0) && lens.iso.is_none_or(|iso| iso > 0.0),
+                line,
+                "lens units_per_metre, shutter_time and iso must be positive",
+            )?;
+            let exposure = (lens.shutter_time.is_some() || lens.iso.is_some()).then(|| Exposure {
+                shutter_time: lens.shutter_time.unwrap_or(1.0),
+                iso: lens.iso.unwrap_or(100.0),
+            });
+            let physical = PhysicalCamera {
+                vup: vec3(desc.vup),
+                sensor_width,
+                sensor_height,
+                focal_length: lens.focal_length,
+                f_number: lens.f_number,
+                focus_dist: desc.focus_dist.unwrap_or((lookat - lookfrom).length()),
+                units_per_metre: lens.units_per_metre,
+                exposure,
+                time0: desc.time0,
+                time1: desc.time1,
+                ..PhysicalCamera::new(lookfrom, lookat)
+            };
+            CameraSettings { blades, ..physical.settings() }
+        }
+    };
+    camera.lookfrom1 = desc.lookfrom1.map_or(lookfrom, vec3);
+    camera.lookat1 = desc.lookat1.map_or(lookat, vec3);
+    camera.projection = match desc.projection {
+        None | Some(ProjectionDesc::Perspective) => Projection::Perspective,
+        Some(ProjectionDesc::Orthographic { height }) => Projection::Orthographic {
+            height: height.unwrap_or_else(|| camera.focus_height()),
+        },
+        Some(ProjectionDesc::Fisheye { fov }) => Projection::Fisheye { fov: fov.unwrap_or(180.0) },
+        Some(ProjectionDesc::Equirectangular) => Projection::Equirectangular,
+        Some(ProjectionDesc::CubeMap) => Projection::CubeMap,
+    };
+    if camera.projection != Projection::Perspective {
+        check(
+            desc.aperture.is_none() && desc.lens.is_none() && blades == 0 && desc.aperture_image.is_none(),
+            line,
+            "only a perspective camera can have an aperture, blades or a lens",
+        )?;
+    }
+    match camera.projection {
+        Projection::Orthographic { height } => check(height > 0.0, line, "orthographic height must be positive")?,
+        Projection::Fisheye { fov } => check(fov > 0.0 && fov <= 360.0, line, "fisheye fov must be between 0 and 360 degrees")?,
+        _ => {}
+    }
+
+    let aperture_image = match &desc.aperture_image {
+        None => None,
+        Some(path) => {
+            check(blades == 0, line, "a camera cannot have both blades and an aperture_image")?;
+            let path = base_dir.join(path);
+            let image = ApertureImage::open(&path).map_err(|e| SceneError::Invalid {
+                line,
+                message: format!("cannot load aperture image `{}`: {}", path.display(), e),
+            })?;
+            let image = image.ok_or_else(|| SceneError::Invalid {
+                line,
+                message: format!("aperture image `{}` is black", path.display()),
+            })?;
+            Some(Arc::new(image))
+        }
+    };
+    Ok((camera, aperture_image))
+}
+
+fn timeline(desc: &AnimationDesc, line: usize) -> Result<Timeline, SceneError> {
+    let [first_frame, last_frame] = desc.frames;
+    check(first_frame <= last_frame, line, "animation frames must be [first, last] with first <= last")?;
+    check(desc.fps > 0.0, line, "animation fps must be positive")?;
+    check((0.0..=1.0).contains(&desc.shutter), line, "animation shutter must be between 0 and 1")?;
+    let keys = &desc.camera;
+    let vec3_track = |f: fn(&CameraKeyDesc) -> Option<[f64; 3]>| {
+        Track::new(keys.iter().filter_map(|key| Some((key.time, vec3(f(key)?)))).collect())
+    };
+    let f64_track = |f: fn(&CameraKeyDesc) -> Option<f64>| {
+        Track::new(keys.iter().filter_map(|key| Some((key.time, f(key)?))).collect())
+    };
+    Ok(Timeline {
+        first_frame,
+        last_frame,
+        fps: desc.fps,
+        shutter: desc.shutter,
+        camera: CameraTrack {
+            lookfrom: vec3_track(|key| key.lookfrom),
+            lookat: vec3_track(|key| key.lookat),
+            vfov: f64_track(|key| key.vfov),
+            aperture: f64_track(|key| key.aperture),
+            focus_dist: f64_track(|key| key.focus_dist),
+        },
+    })
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "{}", e),
+            SceneError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            SceneError::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    camera: Spanned<CameraDesc>,
+    render: Option<Spanned<RenderDesc>>,
+    #[serde(default)]
+    background: [f64; 3],
+    // Entries are deserialized one at a time by `entry`.
+    #[serde(default)]
+    textures: BTreeMap<String, Spanned<toml::Value>>,
+    #[serde(default)]
+    materials: BTreeMap<String, Spanned<toml::Value>>,
+    /// Objects built once and placed any number of times with `instance`.
+    #[serde(default)]
+    shapes: BTreeMap<String, Spanned<toml::Value>>,
+    #[serde(default)]
+    objects: Vec<Spanned<toml::Value>>,
+    animation: Option<Spanned<AnimationDesc>>,
+}
+
+/// The frames to render and the camera keyframes. Times are in seconds;
+/// frame `n` starts at `n / fps`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnimationDesc {
+    /// The first and last frame, both included.
+    frames: [u32; 2],
+    #[serde(default = "default_fps")]
+    fps: f64,
+    /// How much of each frame the shutter is open for.
+    #[serde(default)]
+    shutter: f64,
+    #[serde(default)]
+    camera: Vec<CameraKeyDesc>,
+}
+
+/// Camera parameters at `time`; each is interpolated between the keys that
+/// set it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraKeyDesc {
+    time: f64,
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    vfov: Option<f64>,
+    aperture: Option<f64>,
+    focus_dist: Option<f64>,
+}
+
+/// A material parameter that is either fixed or keyed over time as
+/// `[[time, value], ...]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Param<T> {
+    Fixed(T),
+    Keyed(Vec<(f64, T)>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    lookfrom: [f64; 3],
+    lookat: [f64; 3],
+    #[serde(default = "default_vup")]
+    vup: [f64; 3],
+    /// 40 degrees by default.
+    vfov: Option<f64>,
+    /// Pinhole by default.
+    aperture: Option<f64>,
+    /// 10 by default, or the distance to `lookat` with a lens.
+    focus_dist: Option<f64>,
+    #[serde(default)]
+    time0: f64,
+    #[serde(default = "default_time1")]
+    time1: f64,
+    /// The camera moves to these by `time1`; by default it stands still.
+    lookfrom1: Option<[f64; 3]>,
+    lookat1: Option<[f64; 3]>,
+    /// Shapes the opening into a polygon; round by default.
+    blades: Option<u32>,
+    /// A greyscale image of the opening, for custom bokeh.
+    aperture_image: Option<PathBuf>,
+    lens: Option<LensDesc>,
+    /// Perspective by default.
+    projection: Option<ProjectionDesc>,
+}
+
+/// See `Projection`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ProjectionDesc {
+    Perspective,
+    /// As tall as the perspective view is at `focus_dist` by default.
+    Orthographic { height: Option<f64> },
+    /// 180 degrees by default.
+    Fisheye { fov: Option<f64> },
+    Equirectangular,
+    CubeMap,
+}
+
+/// A real camera's lens and sensor, see `PhysicalCamera`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LensDesc {
+    /// Width and height in millimetres, full frame by default.
+    #[serde(default = "default_sensor")]
+    sensor: [f64; 2],
+    focal_length: f64,
+    f_number: f64,
+    #[serde(default = "default_scale")]
+    units_per_metre: f64,
+    /// Exposure in seconds and ISO; radiance is left as it is without them.
+    shutter_time: Option<f64>,
+    iso: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct RenderDesc {
+    image_width: i32,
+    aspect_ratio: f64,
+    samples_per_pixel: i32,
+    max_depth: i32,
+    integrator: IntegratorDesc,
+    /// Photons per pass for the photon mapping integrators.
+    photons: Option<u32>,
+    /// 0 or absent to pick one from the photons.
+    photon_radius: Option<f64>,
+    /// Settings of the mlt integrator; see `MltSettings`.
+    bootstrap_paths: Option<u32>,
+    chains: Option<u32>,
+    large_step_probability: Option<f64>,
+    mutation_sigma: Option<f64>,
+}
+
+impl RenderDesc {
+    const DEFAULT: RenderDesc = RenderDesc {
+        image_width: 600,
+        aspect_ratio: 1.0,
+        samples_per_pixel: 50,
+        max_depth: 50,
+        integrator: IntegratorDesc::Recursive,
+        photons: None,
+        photon_radius: None,
+        bootstrap_paths: None,
+        chains: None,
+        large_step_probability: None,
+        mutation_sigma: None,
+    };
+}
+
+/// See `IntegratorKind`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum IntegratorDesc {
+    Recursive,
+    Path,
+    Mis,
+    Bdpt,
+    PhotonMap,
+    Sppm,
+    Mlt,
+}
+
+impl Default for RenderDesc {
+    fn default() -> Self {
+        RenderDesc::DEFAULT
+    }
+}
+
+fn default_vup() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_sensor() -> [f64; 2] {
+    [36.0, 24.0]
+}
+
+fn default_time1() -> f64 {
+    1.0
+}
+
+fn default_fuzz() -> Param<f64> {
+    Param::Fixed(0.0)
+}
+
+fn default_fps() -> f64 {
+    24.0
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+fn default_scale3() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid { color: Param<[f64; 3]> },
+    Checker { even: TextureRef, odd: TextureRef },
+    Noise { scale: f64 },
+    Image { path: PathBuf },
+}
+
+/// A texture given as a plain colour, the name of an entry in `[textures]`
+/// or an inline table.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Color([f64; 3]),
+    Name(String),
+    Inline(Box<TextureDesc>),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: TextureRef },
+    Metal { albedo: Param<[f64; 3]>, #[serde(default = "default_fuzz")] fuzz: Param<f64> },
+    Dielectric { ref_idx: Param<f64> },
+    DiffuseLight { emit: TextureRef },
+    Isotropic { albedo: TextureRef },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaterialRef {
+    Name(String),
+    Inline(MaterialDesc),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere { center: [f64; 3], radius: f64, material: MaterialRef },
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: MaterialRef,
+    },
+    XyRect { x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: MaterialRef },
+    XzRect { x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: MaterialRef },
+    YzRect { y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: MaterialRef },
+    #[serde(rename = "box")]
+    BoxModel { min: [f64; 3], max: [f64; 3], material: MaterialRef },
+    Triangle { v0: [f64; 3], v1: [f64; 3], v2: [f64; 3], material: MaterialRef },
+    Mesh { positions: Vec<[f64; 3]>, faces: Vec<[usize; 3]>, material: MaterialRef },
+    /// A Wavefront OBJ model; `material` replaces the ones from its MTL files.
+    Obj { path: PathBuf, material: Option<MaterialRef> },
+    /// The entry `shape` of `[shapes]`, scaled, rotated about y and moved.
+    Instance {
+        shape: String,
+        #[serde(default)]
+        offset: [f64; 3],
+        #[serde(default)]
+        rotate_y: f64,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        material: Option<MaterialRef>,
+    },
+    Translate { offset: [f64; 3], object: Box<ObjectDesc> },
+    RotateY { angle: f64, object: Box<ObjectDesc> },
+    /// Scaled per axis, rotated by Euler angles in degrees (about x, then y,
+    /// then z) and moved, in that order.
+    Transform {
+        #[serde(default = "default_scale3")]
+        scale: [f64; 3],
+        #[serde(default)]
+        rotate: [f64; 3],
+        #[serde(default)]
+        translate: [f64; 3],
+        object: Box<ObjectDesc>,
+    },
+    /// Moved through `keys` over time, which blurs it over the shutter.
+    Animated { keys: Vec<KeyframeDesc>, object: Box<ObjectDesc> },
+    ConstantMedium { density: f64, albedo: TextureRef, boundary: Box<ObjectDesc> },
+    List { objects: Vec<ObjectDesc> },
+    Bvh { objects: Vec<ObjectDesc> },
+}
+
+/// An `animated` pose: scaled, rotated by Euler angles in degrees and moved,
+/// like `transform`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDesc {
+    time: f64,
+    #[serde(default = "default_scale3")]
+    scale: [f64; 3],
+    #[serde(default)]
+    rotate: [f64; 3],
+    #[serde(default)]
+    translate: [f64; 3],
+}
+
+struct Builder<'a, S: ?Sized> {
+    source: &'a str,
+    base_dir: &'a Path,
+    sampler: &'a mut S,
+    time0: f64,
+    time1: f64,
+    texture_descs: &'a BTreeMap<String, Spanned<TextureDesc>>,
+    material_descs: &'a BTreeMap<String, Spanned<MaterialDesc>>,
+    shape_descs: &'a BTreeMap<String, Spanned<ObjectDesc>>,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    shapes: HashMap<String, Arc<dyn Hittable>>,
+    resolving: Vec<String>,
+}
+
+impl<S: Sampler + ?Sized> Builder<'_, S> {
+    fn texture(&mut self, desc: &TextureDesc, line: usize) -> Result<Arc<dyn Texture>, SceneError> {
+        Ok(match desc {
+            TextureDesc::Solid { color } => Arc::new(SolidColor::new(self.param3(color, line)?)),
+            TextureDesc::Checker { even, odd } => Arc::new(CheckerTexture::new(
+                self.texture_ref(even, line)?,
+                self.texture_ref(odd, line)?,
+            )),
+            TextureDesc::Noise { scale } => Arc::new(NoiseTexture::new(self.sampler, *scale)),
+            TextureDesc::Image { path } => {
+                let path = self.base_dir.join(path);
+                let texture = ImageTexture::open(&path).map_err(|e| SceneError::Invalid {
+                    line,
+                    message: format!("cannot load image `{}`: {}", path.display(), e),
+                })?;
+                Arc::new(texture)
+            }
+        })
+    }
+
+    fn texture_ref(&mut self, r: &TextureRef, line: usize) -> Result<Arc<dyn Texture>, SceneError> {
+        match r {
+            TextureRef::Color(color) => Ok(Arc::new(SolidColor::new(vec3(*color)))),
+            TextureRef::Inline(desc) => self.texture(desc, line),
+            TextureRef::Name(name) => {
+                if let Some(texture) = self.textures.get(name) {
+                    return Ok(texture.clone());
+                }
+                let descs = self.texture_descs;
+                let desc = descs.get(name).ok_or_else(|| SceneError::Invalid {
+                    line,
+                    message: format!("unknown texture `{}`", name),
+                })?;
+                self.enter("texture", name, line)?;
+                let texture = self.texture(desc.get_ref(), line_of(self.source, desc.span().start));
+                self.resolving.pop();
+                let texture = texture?;
+                self.textures.insert(name.clone(), texture.clone());
+                Ok(texture)
+            }
+        }
+    }
+
+    fn material(&mut self, desc: &MaterialDesc, line: usize) -> Result<Arc<dyn Material>, SceneError> {
+        Ok(match desc {
+            MaterialDesc::Lambertian { albedo } => Arc::new(Lambertian::new(self.texture_ref(albedo, line)?)),
+            MaterialDesc::Metal { albedo, fuzz } => {
+                Arc::new(Metal::new(self.param3(albedo, line)?, self.param(fuzz, line)?))
+            }
+            MaterialDesc::Dielectric { ref_idx } => Arc::new(Dielactric::new(self.param(ref_idx, line)?)),
+            MaterialDesc::DiffuseLight { emit } => Arc::new(DiffuseLight::new(self.texture_ref(emit, line)?)),
+            MaterialDesc::Isotropic { albedo } => Arc::new(IsoTropic::new(self.texture_ref(albedo, line)?)),
+        })
+    }
+
+    fn material_ref(&mut self, r: &MaterialRef, line: usize) -> Result<Arc<dyn Material>, SceneError> {
+        match r {
+            MaterialRef::Inline(desc) => self.material(desc, line),
+            MaterialRef::Name(name) => {
+                if let Some(material) = self.materials.get(name) {
+                    return Ok(material.clone());
+                }
+                let descs = self.material_descs;
+                let desc = descs.get(name).ok_or_else(|| SceneError::Invalid {
+                    line,
+                    message: format!("unknown material `{}`", name),
+                })?;
+                let material = self.material(desc.get_ref(), line_of(self.source, desc.span().start))?;
+                self.materials.insert(name.clone(), material.clone());
+                Ok(material)
+            }
+        }
+    }
+
+    /// The value of a keyed parameter when the shutter opens.
+    fn param(&self, param: &Param<f64>, line: usize) -> Result<f64, SceneError> {
+        match param {
+            Param::Fixed(value) => Ok(*value),
+            Param::Keyed(keys) => Track::new(keys.clone())
+                .map(|track| track.at(self.time0))
+                .ok_or_else(|| keyless(line)),
+        }
+    }
+
+    fn param3(&self, param: &Param<[f64; 3]>, line: usize) -> Result<Vec3, SceneError> {
+        match param {
+            Param::Fixed(value) => Ok(vec3(*value)),
+            Param::Keyed(keys) => Track::new(keys.iter().map(|&(time, value)| (time, vec3(value))).collect())
+                .map(|track| track.at(self.time0))
+                .ok_or_else(|| keyless(line)),
+        }
+    }
+
+    /// Whether `desc` is a primitive that supports light sampling and emits.
+    fn is_light(&self, desc: &ObjectDesc) -> bool {
+        let material = match desc {
+            ObjectDesc::Sphere { material, .. }
+            | ObjectDesc::XyRect { material, .. }
+            | ObjectDesc::XzRect { material, .. }
+            | ObjectDesc::YzRect { material, .. } => material,
+            _ => return false,
+        };
+        let desc = match material {
+            MaterialRef::Inline(desc) => desc,
+            MaterialRef::Name(name) => match self.material_descs.get(name) {
+                Some(desc) => desc.get_ref(),
+                None => return false,
+            },
+        };
+        matches!(desc, MaterialDesc::DiffuseLight { .. })
+    }
+
+    fn object(&mut self, desc: &ObjectDesc, line: usize) -> Result<Arc<dyn Hittable>, SceneError> {
+        Ok(match desc {
+            ObjectDesc::Sphere { center, radius, material } => {
+                check(*radius > 0.0, line, "sphere radius must be positive")?;
+                Arc::new(Sphere::new(vec3(*center), *radius, self.material_ref(material, line)?))
+            }
+            ObjectDesc::MovingSphere { center0, center1, time0, time1, radius, material } => {
+                check(*radius > 0.0, line, "sphere radius must be positive")?;
+                check(time0 < time1, line, "moving_sphere needs time0 < time1")?;
+                Arc::new(MovingSphere::new(
+                    vec3(*center0),
+                    vec3(*center1),
+                    *time0,
+                    *time1,
+                    *radius,
+                    self.material_ref(material, line)?,
+                ))
+            }
+            ObjectDesc::XyRect { x0, x1, y0, y1, k, material } => {
+                check(x0 < x1 && y0 < y1, line, "xy_rect needs x0 < x1 and y0 < y1")?;
+                Arc::new(XyRect::new(*x0, *x1, *y0, *y1, *k, self.material_ref(material, line)?))
+            }
+            ObjectDesc::XzRect { x0, x1, z0, z1, k, material } => {
+                check(x0 < x1 && z0 < z1, line, "xz_rect needs x0 < x1 and z0 < z1")?;
+                Arc::new(XzRect::new(*x0, *x1, *z0, *z1, *k, self.material_ref(material, line)?))
+            }
+            ObjectDesc::YzRect { y0, y1, z0, z1, k, material } => {
+                check(y0 < y1 && z0 < z1, line, "yz_rect needs y0 < y1 and z0 < z1")?;
+                Arc::new(YzRect::new(*y0, *y1, *z0, *z1, *k, self.material_ref(material, line)?))
+            }
+            ObjectDesc::BoxModel { min, max, material } => {
+                check(
+                    min[0] < max[0] && min[1] < max[1] && min[2] < max[2],
+                    line,
+                    "box needs min < max on every axis",
+                )?;
+                Arc::new(BoxModel::new(vec3(*min), vec3(*max), self.material_ref(material, line)?))
+            }
+            ObjectDesc::Triangle { v0, v1, v2, material } => {
+                Arc::new(Triangle::new(vec3(*v0), vec3(*v1), vec3(*v2), self.material_ref(material, line)?))
+            }
+            ObjectDesc::Mesh { positions, faces, material } => {
+                check(
+                    faces.iter().flatten().all(|&i| i < positions.len()),
+                    line,
+                    "mesh face refers to a missing position",
+                )?;
+                let data = MeshData {
+                    positions: positions.iter().map(|p| vec3(*p)).collect(),
+                    faces: faces
+                        .iter()
+                        .map(|&positions| Face { positions, normals: None, uvs: None })
+                        .collect(),
+                    ..MeshData::default()
+                };
+                Arc::new(TriangleMesh::new(Arc::new(data), self.material_ref(material, line)?))
+            }
+            ObjectDesc::Obj { path, material } => {
+                let path = self.base_dir.join(path);
+                let obj = Obj::load(&path).map_err(|e| SceneError::Invalid {
+                    line,
+                    message: format!("cannot load model `{}`: {}", path.display(), e),
+                })?;
+                match material {
+                    Some(material) => Arc::new(obj.with_material(self.material_ref(material, line)?)),
+                    None => Arc::new(obj.into_hittable()),
+                }
+            }
+            ObjectDesc::Instance { shape, offset, rotate_y, scale, material } => {
+                check(*scale > 0.0, line, "instance scale must be positive")?;
+                let mut instance = Instance::new(self.shape(shape, line)?)
+                    .with_scale(*scale)
+                    .with_rotation_y(*rotate_y)
+                    .with_offset(vec3(*offset));
+                if let Some(material) = material {
+                    instance = instance.with_material(self.material_ref(material, line)?);
+                }
+                Arc::new(instance)
+            }
+            ObjectDesc::Translate { offset, object } => {
+                Arc::new(Translate::new(self.object(object, line)?, vec3(*offset)))
+            }
+            ObjectDesc::RotateY { angle, object } => {
+                let object = self.bounded_object(object, line, "rotate_y")?;
+                Arc::new(RotateY::new(object, *angle))
+            }
+            ObjectDesc::Transform { scale, rotate, translate, object } => {
+                check(scale.iter().all(|&s| s != 0.0), line, "transform scale cannot be zero")?;
+                let [x, y, z] = *rotate;
+                let transform =
+                    Transform::translate(vec3(*translate)) * Transform::euler(x, y, z) * Transform::scale(vec3(*scale));
+                Arc::new(Transformed::new(self.object(object, line)?, transform))
+            }
+            ObjectDesc::Animated { keys, object } => {
+                check(!keys.is_empty(), line, "animated needs at least one keyframe")?;
+                let mut keyframes = Vec::new();
+                for key in keys.iter() {
+                    check(key.scale.iter().all(|&s| s != 0.0), line, "keyframe scale cannot be zero")?;
+                    let [x, y, z] = key.rotate;
+                    keyframes.push(
+                        Keyframe::new(key.time)
+                            .with_translation(vec3(key.translate))
+                            .with_rotation(Quaternion::from_euler(x, y, z))
+                            .with_scale(vec3(key.scale)),
+                    );
+                }
+                let object = self.bounded_object(object, line, "animated")?;
+                Arc::new(Animated::new(object, AnimatedTransform::new(keyframes)))
+            }
+            ObjectDesc::ConstantMedium { density, albedo, boundary } => {
+                check(*density > 0.0, line, "constant_medium density must be positive")?;
+                let boundary = self.object(boundary, line)?;
+                let phase_function = Arc::new(IsoTropic::new(self.texture_ref(albedo, line)?));
+                Arc::new(ConstantMedium::new(boundary, *density, phase_function))
+            }
+            ObjectDesc::List { objects } => {
+                let mut list = HittableList::new();
+                for object in objects.iter() {
+                    list.add(self.object(object, line)?);
+                }
+                Arc::new(list)
+            }
+            ObjectDesc::Bvh { objects } => {
+                check(!objects.is_empty(), line, "bvh needs at least one object")?;
+                let mut list = HittableList::new();
+                for object in objects.iter() {
+                    list.add(self.bounded_object(object, line, "bvh")?);
+                }
+                Arc::new(Bvh::new(list, self.time0, self.time1))
+            }
+        })
+    }
+
+    /// The shared object built from `[shapes]` entry `name`.
+    fn shape(&mut self, name: &str, line: usize) -> Result<Arc<dyn Hittable>, SceneError> {
+        if let Some(shape) = self.shapes.get(name) {
+            return Ok(shape.clone());
+        }
+        let descs = self.shape_descs;
+        let desc = descs.get(name).ok_or_else(|| SceneError::Invalid {
+            line,
+            message: format!("unknown shape `{}`", name),
+        })?;
+        self.enter("shape", name, line)?;
+        let shape = self.bounded_object(desc.get_ref(), line_of(self.source, desc.span().start), "shape");
+        self.resolving.pop();
+        let shape = shape?;
+        self.shapes.insert(name.to_string(), shape.clone());
+        Ok(shape)
+    }
+
+    fn bounded_object(&mut self, desc: &ObjectDesc, line: usize, parent: &str) -> Result<Arc<dyn Hittable>, SceneError> {
+        let object = self.object(desc, line)?;
+        check(
+            object.bounding_box(self.time0, self.time1).is_some(),
+            line,
+            &format!("{} cannot contain an empty list", parent),
+        )?;
+        Ok(object)
+    }
+
+    fn enter(&mut self, kind: &str, name: &str, line: usize) -> Result<(), SceneError> {
+        let key = format!("{} {}", kind, name);
+        if self.resolving.contains(&key) {
+            return Err(SceneError::Invalid {
+                line,
+                message: format!("{} `{}` refers to itself", kind, name),
+            });
+        }
+        self.resolving.push(key);
+        Ok(())
+    }
+}
+
+fn check(condition: bool, line: usize, message: &str) -> Result<(), SceneError> {
+    if condition {
+        Ok(())
+    } else {
+        Err(SceneError::Invalid { line, message: message.to_string() })
+    }
+}
+
+/// Deserializes an entry of `textures`, `materials`, `shapes` or `objects`.
+/// Deserialized with the rest of the file, an error inside an entry would
+/// point at the first entry of its table.
+fn entry<T: DeserializeOwned>(source: &str, entry: Spanned<toml::Value>) -> Result<Spanned<T>, SceneError> {
+    let span = entry.span();
+    match entry.into_inner().try_into() {
+        Ok(value) => Ok(Spanned::new(span, value)),
+        Err(e) => Err(SceneError::Parse {
+            line: line_of(source, span.start),
+            message: e.message().to_string(),
+        }),
+    }
+}
+
+fn entries<T: DeserializeOwned>(
+    source: &str,
+    entries: BTreeMap<String, Spanned<toml::Value>>,
+) -> Result<BTreeMap<String, Spanned<T>>, SceneError> {
+    entries.into_iter().map(|(name, value)| Ok((name, entry(source, value)?))).collect()
+}
+
+fn keyless(line: usize) -> SceneError {
+    SceneError::Invalid { line, message: "keyed values need at least one key".to_string() }
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+fn vec3(v: [f64; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
@@ -1,4 +1,5 @@
 use std::f64::consts::PI;
+use std::sync::Arc;
 use crate::aabb::{Aabb, surrounding_box};
 
 use crate::hittable::Hittable;
@@ -7,27 +8,26 @@ use crate::ray::Ray;
 use crate::vec3::Vec3;
 use crate::vec3::Point3;
 use crate::material::Material;
+use crate::onb::Onb;
+use crate::sampler::Sampler;
 
 #[derive(Clone)]
 pub struct Sphere {
   pub center: Point3,
   pub radius: f64,
-  pub mat_ptr: Box<dyn Material>
+  pub mat_ptr: Arc<dyn Material>
 }
 
 impl Sphere {
-  pub fn new(cen: Point3, r: f64, m: Box<dyn Material>) -> Sphere {
+  pub fn new(cen: Point3, r: f64, m: Arc<dyn Material>) -> Sphere {
     Sphere {
       center: cen,
       radius: r,
       mat_ptr: m
     }
   }
-}
-
-impl Hittable for Sphere {
 
-  fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+  fn intersect(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
     let oc = r.origin - self.center;
     let a = r.direction.length_squared();
     let half_b = oc.dot(&r.direction);
@@ -40,12 +40,12 @@ impl Hittable for Sphere {
         let p = r.at(temp);
         let (u, v) = get_sphere_uv(&((p - self.center) / self.radius) );
         let mut hit_rec = HitRecord {
-          p: p,
+          p,
           normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
           mat_ptr: &*self.mat_ptr,
           t: temp,
-          u: u,
-          v: v,
+          u,
+          v,
           front_face: false
         };
         let outward_normal = (hit_rec.p - self.center) / self.radius;
@@ -57,12 +57,12 @@ impl Hittable for Sphere {
         let p = r.at(temp2);
         let (u, v) = get_sphere_uv(&p);
         let mut hit_rec = HitRecord {
-          p: p,
+          p,
           normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
           mat_ptr: &*self.mat_ptr,
           t: temp2,
-          u: u,
-          v: v,
+          u,
+          v,
           front_face: false
         };
         let outward_normal = (hit_rec.p - self.center) / self.radius;
@@ -72,6 +72,13 @@ impl Hittable for Sphere {
     }
     None
   }
+}
+
+impl Hittable for Sphere {
+
+  fn hit(&self, _: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+    self.intersect(r, t_min, t_max)
+  }
 
   fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
     let output_box = Aabb::new(
@@ -80,6 +87,47 @@ impl Hittable for Sphere {
     );
     Some(output_box)
   }
+
+  fn pdf_value(&self, o: &Point3, v: &Vec3) -> f64 {
+    if self.intersect(&Ray::new(*o, *v, 0.0), 0.001, f64::INFINITY).is_none() {
+      return 0.0;
+    }
+    let distance_squared = (self.center - *o).length_squared();
+    if distance_squared <= self.radius*self.radius {
+      return 0.0;
+    }
+    let cos_theta_max = (1.0 - self.radius*self.radius/distance_squared).sqrt();
+    let solid_angle = 2.0*PI*(1.0-cos_theta_max);
+    1.0 / solid_angle
+  }
+
+  fn random(&self, sampler: &mut dyn Sampler, o: &Point3) -> Vec3 {
+    let direction = self.center - *o;
+    let distance_squared = direction.length_squared();
+    if distance_squared <= self.radius*self.radius {
+      return Vec3::random_unit_vector(sampler);
+    }
+    let uvw = Onb::build_from_w(&direction);
+    uvw.local(&Vec3::random_to_sphere(sampler, self.radius, distance_squared))
+  }
+  fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+    let normal = Vec3::random_unit_vector(sampler);
+    let (u, v) = get_sphere_uv(&normal);
+    Some(HitRecord {
+      p: self.center + normal * self.radius,
+      normal,
+      mat_ptr: &*self.mat_ptr,
+      t: 0.0,
+      u,
+      v,
+      front_face: true
+    })
+  }
+
+  fn area(&self) -> f64 {
+    4.0 * PI * self.radius * self.radius
+  }
+
 }
 
 #[derive(Clone)]
@@ -89,11 +137,11 @@ pub struct MovingSphere {
   pub time0: f64,
   pub time1: f64,
   pub radius: f64,
-  pub mat_ptr: Box<dyn Material>
+  pub mat_ptr: Arc<dyn Material>
 }
 
 impl MovingSphere {
-  pub fn new(cen0: Point3, cen1: Point3, t0: f64, t1: f64, r: f64, m: Box<dyn Material>) -> MovingSphere {
+  pub fn new(cen0: Point3, cen1: Point3, t0: f64, t1: f64, r: f64, m: Arc<dyn Material>) -> MovingSphere {
     MovingSphere {
       center0: cen0,
       center1: cen1,
@@ -109,7 +157,7 @@ impl MovingSphere {
 }
 
 impl Hittable for MovingSphere {
-  fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+  fn hit(&self, _: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
     let oc = r.origin - self.center(r.time);
     let a = r.direction.length_squared();
     let half_b = oc.dot(&r.direction);
@@ -122,12 +170,12 @@ impl Hittable for MovingSphere {
         let p = r.at(temp);
         let (u, v) = get_sphere_uv(&p);
         let mut hit_rec = HitRecord {
-          p: p,
+          p,
           normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
           mat_ptr: &*self.mat_ptr,
           t: temp,
-          u: u,
-          v: v,
+          u,
+          v,
           front_face: false
         };
         let outward_normal = (hit_rec.p - self.center(r.time)) / self.radius;
@@ -143,8 +191,8 @@ impl Hittable for MovingSphere {
           normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
           mat_ptr: &*self.mat_ptr,
           t: temp2,
-          u: u,
-          v: v,
+          u,
+          v,
           front_face: false
         };
         let outward_normal = (hit_rec.p - self.center(r.time)) / self.radius;
@@ -1,31 +1,15 @@
-use image::GenericImageView;
-use rand::prelude::ThreadRng;
+use std::path::Path;
+use std::sync::Arc;
+use image::{GenericImageView, ImageResult};
 use crate::perlin::Perlin;
+use crate::sampler::Sampler;
 use crate::vec3::{Color, Point3};
 
 
-pub trait CloneTexture {
-    fn clone_box(&self) -> Box<dyn Texture>;
-}
-pub trait Texture : Sync + CloneTexture {
+pub trait Texture : Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
 }
 
-impl<T> CloneTexture for T
-    where
-        T: 'static + Texture + Clone,
-{
-    fn clone_box(&self) -> Box<dyn Texture> {
-        Box::new(self.clone())
-    }
-}
-
-impl Clone for Box<dyn Texture> {
-    fn clone(&self) -> Box<dyn Texture> {
-        self.clone_box()
-    }
-}
-
 #[derive(Clone)]
 pub struct SolidColor {
     color_value: Color
@@ -48,15 +32,15 @@ impl Texture for SolidColor {
 
 #[derive(Clone)]
 pub struct CheckerTexture {
-    even: Box<dyn Texture>,
-    odd: Box<dyn Texture>
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>
 }
 
 impl CheckerTexture {
-    pub fn new(even: Box<dyn Texture>, odd: Box<dyn Texture>) -> CheckerTexture {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> CheckerTexture {
         CheckerTexture{
-            even: even,
-            odd: odd
+            even,
+            odd
         }
     }
 }
@@ -78,9 +62,9 @@ pub struct NoiseTexture {
     scale: f64
 }
 impl NoiseTexture {
-    pub fn new(rng: &mut ThreadRng, scale: f64) -> NoiseTexture {
+    pub fn new<S: Sampler + ?Sized>(sampler: &mut S, scale: f64) -> NoiseTexture {
         NoiseTexture {
-            noise: Perlin::new(rng),
+            noise: Perlin::new(sampler),
             scale
         }
     }
@@ -100,8 +84,12 @@ pub struct ImageTexture {
 }
 
 impl ImageTexture {
-    pub fn new(file_path: &'static str) -> ImageTexture {
-        let img = image::open(file_path).unwrap();
+    pub fn new(file_path: &str) -> ImageTexture {
+        ImageTexture::open(file_path).unwrap()
+    }
+
+    pub fn open<P: AsRef<Path>>(file_path: P) -> ImageResult<ImageTexture> {
+        let img = image::open(file_path)?;
 
         let (width, height) = img.dimensions();
 
@@ -118,11 +106,11 @@ impl ImageTexture {
             }
         }
 
-        ImageTexture {
+        Ok(ImageTexture {
             data,
             width,
             height
-        }
+        })
     }
 }
 
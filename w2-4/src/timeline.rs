@@ -0,0 +1,116 @@
+use std::ops::RangeInclusive;
+
+use crate::camera::CameraSettings;
+use crate::vec3::Vec3;
+
+/// Values that can be blended between keyframes.
+pub trait Lerp: Copy {
+    /// `self` at `t = 0`, `other` at `t = 1`.
+    fn lerp(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Lerp for f64 {
+    fn lerp(&self, other: &f64, t: f64) -> f64 {
+        self + t * (other - self)
+    }
+}
+
+impl Lerp for Vec3 {
+    fn lerp(&self, other: &Vec3, t: f64) -> Vec3 {
+        *self + t * (*other - *self)
+    }
+}
+
+/// A value keyed at times in seconds, interpolated linearly between keys and
+/// held before the first and after the last.
+#[derive(Clone)]
+pub struct Track<T> {
+    keys: Vec<(f64, T)>,
+}
+
+impl<T: Lerp> Track<T> {
+    /// Sorts the keys by time; `None` if there are none.
+    pub fn new(mut keys: Vec<(f64, T)>) -> Option<Track<T>> {
+        if keys.is_empty() {
+            return None;
+        }
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Some(Track { keys })
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let next = self.keys.partition_point(|k| k.0 <= time);
+        if next == 0 {
+            return self.keys[0].1;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].1;
+        }
+        let ((t0, a), (t1, b)) = (&self.keys[next - 1], &self.keys[next]);
+        a.lerp(b, (time - t0) / (t1 - t0))
+    }
+}
+
+/// Keyframed camera parameters. Those without a track keep the value the
+/// scene's camera has.
+#[derive(Clone, Default)]
+pub struct CameraTrack {
+    pub lookfrom: Option<Track<Vec3>>,
+    pub lookat: Option<Track<Vec3>>,
+    pub vfov: Option<Track<f64>>,
+    pub aperture: Option<Track<f64>>,
+    pub focus_dist: Option<Track<f64>>,
+}
+
+impl CameraTrack {
+    /// `base` with the shutter open from `time0` to `time1`. The camera moves
+    /// in a straight line between where the tracks put it at the two times;
+    /// the lens is set as it is at `time0`.
+    pub fn at(&self, base: &CameraSettings, time0: f64, time1: f64) -> CameraSettings {
+        let vec3_at = |track: &Option<Track<Vec3>>, value: Vec3, time: f64| {
+            track.as_ref().map_or(value, |track| track.at(time))
+        };
+        let f64_at = |track: &Option<Track<f64>>, value: f64| track.as_ref().map_or(value, |track| track.at(time0));
+        CameraSettings {
+            lookfrom: vec3_at(&self.lookfrom, base.lookfrom, time0),
+            lookat: vec3_at(&self.lookat, base.lookat, time0),
+            lookfrom1: vec3_at(&self.lookfrom, base.lookfrom, time1),
+            lookat1: vec3_at(&self.lookat, base.lookat, time1),
+            vfov: f64_at(&self.vfov, base.vfov),
+            aperture: f64_at(&self.aperture, base.aperture),
+            focus_dist: f64_at(&self.focus_dist, base.focus_dist),
+            time0,
+            time1,
+            ..*base
+        }
+    }
+}
+
+/// The frames of an animation and how they map to scene time: frame `n`
+/// opens its shutter at `n / fps` seconds and keeps it open for `shutter`
+/// of a frame.
+#[derive(Clone)]
+pub struct Timeline {
+    pub first_frame: u32,
+    pub last_frame: u32,
+    pub fps: f64,
+    pub shutter: f64,
+    pub camera: CameraTrack,
+}
+
+impl Timeline {
+    pub fn frames(&self) -> RangeInclusive<u32> {
+        self.first_frame..=self.last_frame
+    }
+
+    /// The times the shutter opens and closes for `frame`.
+    pub fn shutter_interval(&self, frame: u32) -> (f64, f64) {
+        let open = frame as f64 / self.fps;
+        (open, open + self.shutter / self.fps)
+    }
+
+    /// `frame_0001.png` and so on, padded to four digits.
+    pub fn file_name(frame: u32, extension: &str) -> String {
+        format!("frame_{:04}.{}", frame, extension)
+    }
+}
@@ -0,0 +1,460 @@
+use std::ops::Mul;
+
+use crate::aabb::{surrounding_box, Aabb};
+use crate::vec3::{Point3, Vec3};
+
+const IDENTITY_3: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// A 4x4 matrix acting on column vectors, stored by rows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    /// The affine map `p -> linear * p + translation`, with `linear` given by
+    /// rows.
+    pub fn affine(linear: [[f64; 3]; 3], translation: Vec3) -> Matrix4 {
+        let [a, b, c] = linear;
+        Matrix4::new([
+            [a[0], a[1], a[2], translation.x],
+            [b[0], b[1], b[2], translation.y],
+            [c[0], c[1], c[2], translation.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        Matrix4::new(std::array::from_fn(|i| std::array::from_fn(|j| self.m[j][i])))
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting; `None` if the matrix
+    /// is singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = Matrix4::IDENTITY.m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs())).unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                let factor = a[row][col];
+                if row == col || factor == 0.0 {
+                    continue;
+                }
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+        Some(Matrix4::new(inv))
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        Matrix4::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum())
+        }))
+    }
+}
+
+/// A rotation as a unit quaternion `w + xi + yj + zk`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+
+    /// Normalises its arguments, so any non-zero quaternion may be given.
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        let len = (w * w + x * x + y * y + z * z).sqrt();
+        Quaternion { w: w / len, x: x / len, y: y / len, z: z / len }
+    }
+
+    /// Rotation by `angle` degrees counterclockwise about `axis`, looking
+    /// against it.
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Quaternion {
+        let axis = axis.unit_vector();
+        let (s, c) = (angle.to_radians() / 2.0).sin_cos();
+        Quaternion::new(c, axis.x * s, axis.y * s, axis.z * s)
+    }
+
+    /// Rotates about x, then y, then z, by angles in degrees.
+    pub fn from_euler(x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), z)
+            * Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), y)
+            * Quaternion::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), x)
+    }
+
+    pub fn dot(&self, q: &Quaternion) -> f64 {
+        self.w * q.w + self.x * q.x + self.y * q.y + self.z * q.z
+    }
+
+    /// Spherical interpolation from `self` at `t = 0` to `q` at `t = 1`, the
+    /// short way round at constant angular speed.
+    pub fn slerp(&self, q: &Quaternion, t: f64) -> Quaternion {
+        let mut cos_theta = self.dot(q);
+        // `q` and `-q` are the same rotation; pick the nearer one.
+        let q = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quaternion { w: -q.w, x: -q.x, y: -q.y, z: -q.z }
+        } else {
+            *q
+        };
+        let (a, b) = if cos_theta > 0.9995 {
+            // Nearly parallel: lerp, which `new` normalises.
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+        Quaternion::new(
+            a * self.w + b * q.w,
+            a * self.x + b * q.x,
+            a * self.y + b * q.y,
+            a * self.z + b * q.z,
+        )
+    }
+
+    /// The rows of the rotation matrix.
+    pub fn to_rotation(&self) -> [[f64; 3]; 3] {
+        let Quaternion { w, x, y, z } = *self;
+        [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)],
+        ]
+    }
+}
+
+/// `a * b` rotates by `b`, then by `a`.
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, q: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * q.w - self.x * q.x - self.y * q.y - self.z * q.z,
+            self.w * q.x + self.x * q.w + self.y * q.z - self.z * q.y,
+            self.w * q.y - self.x * q.z + self.y * q.w + self.z * q.x,
+            self.w * q.z + self.x * q.y - self.y * q.x + self.z * q.w,
+        )
+    }
+}
+
+/// An affine transform together with its inverse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        matrix: Matrix4::IDENTITY,
+        inverse: Matrix4::IDENTITY,
+    };
+
+    /// `None` if `matrix` cannot be inverted.
+    pub fn new(matrix: Matrix4) -> Option<Transform> {
+        let inverse = matrix.inverse()?;
+        Some(Transform { matrix, inverse })
+    }
+
+    pub fn translate(offset: Vec3) -> Transform {
+        Transform {
+            matrix: Matrix4::affine(IDENTITY_3, offset),
+            inverse: Matrix4::affine(IDENTITY_3, -offset),
+        }
+    }
+
+    /// Scales by a factor per axis; none of them may be zero.
+    pub fn scale(factors: Vec3) -> Transform {
+        let diagonal = |v: Vec3| [[v.x, 0.0, 0.0], [0.0, v.y, 0.0], [0.0, 0.0, v.z]];
+        let inverse = Vec3::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z);
+        Transform {
+            matrix: Matrix4::affine(diagonal(factors), Vec3::zero()),
+            inverse: Matrix4::affine(diagonal(inverse), Vec3::zero()),
+        }
+    }
+
+    /// Rotation by `angle` degrees counterclockwise about `axis`, looking
+    /// against it.
+    pub fn rotate(axis: Vec3, angle: f64) -> Transform {
+        Transform::from_quaternion(&Quaternion::from_axis_angle(axis, angle))
+    }
+
+    pub fn rotate_x(angle: f64) -> Transform {
+        Transform::rotate(Vec3::new(1.0, 0.0, 0.0), angle)
+    }
+
+    pub fn rotate_y(angle: f64) -> Transform {
+        Transform::rotate(Vec3::new(0.0, 1.0, 0.0), angle)
+    }
+
+    pub fn rotate_z(angle: f64) -> Transform {
+        Transform::rotate(Vec3::new(0.0, 0.0, 1.0), angle)
+    }
+
+    /// Rotates about x, then y, then z, by angles in degrees.
+    pub fn euler(x: f64, y: f64, z: f64) -> Transform {
+        Transform::from_quaternion(&Quaternion::from_euler(x, y, z))
+    }
+
+    pub fn from_quaternion(q: &Quaternion) -> Transform {
+        Transform::rotation(q.to_rotation())
+    }
+
+    /// Places an object at `from` with its +z axis pointing at `to` and its
+    /// +y axis as close to `up` as possible.
+    pub fn look_at(from: Point3, to: Point3, up: Vec3) -> Transform {
+        let w = (to - from).unit_vector();
+        let u = up.cross(&w).unit_vector();
+        let v = w.cross(&u);
+        Transform::translate(from) * Transform::rotation([[u.x, v.x, w.x], [u.y, v.y, w.y], [u.z, v.z, w.z]])
+    }
+
+    /// An orthonormal matrix, whose inverse is its transpose.
+    fn rotation(rows: [[f64; 3]; 3]) -> Transform {
+        let transposed = std::array::from_fn(|i| std::array::from_fn(|j| rows[j][i]));
+        Transform {
+            matrix: Matrix4::affine(rows, Vec3::zero()),
+            inverse: Matrix4::affine(transposed, Vec3::zero()),
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: &Point3) -> Point3 {
+        let m = &self.matrix.m;
+        Point3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.matrix.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    /// Transforms a surface normal by the inverse transpose, which keeps it
+    /// perpendicular to the transformed surface. The result is not normalised.
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        let m = &self.inverse.m;
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+
+    /// The smallest box around the transformed `bbox`.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        let m = &self.matrix.m;
+        let mut min = [m[0][3], m[1][3], m[2][3]];
+        let mut max = min;
+        let (lo, hi) = ([bbox.min.x, bbox.min.y, bbox.min.z], [bbox.max.x, bbox.max.y, bbox.max.z]);
+        // Each output coordinate is a sum of terms that are each smallest and
+        // largest at one end of their input range.
+        for i in 0..3 {
+            for j in 0..3 {
+                let (a, b) = (m[i][j] * lo[j], m[i][j] * hi[j]);
+                min[i] += a.min(b);
+                max[i] += a.max(b);
+            }
+        }
+        Aabb::new(Point3::new(min[0], min[1], min[2]), Point3::new(max[0], max[1], max[2]))
+    }
+}
+
+/// `a * b` applies `b`, then `a`.
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            matrix: self.matrix * rhs.matrix,
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
+
+/// The pose of an animated object at one time: scaled, rotated and moved, in
+/// that order.
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translate: Vec3,
+    pub rotate: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: f64) -> Keyframe {
+        Keyframe {
+            time,
+            translate: Vec3::zero(),
+            rotate: Quaternion::IDENTITY,
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_translation(mut self, translate: Vec3) -> Keyframe {
+        self.translate = translate;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotate: Quaternion) -> Keyframe {
+        self.rotate = rotate;
+        self
+    }
+
+    /// No factor may be zero.
+    pub fn with_scale(mut self, scale: Vec3) -> Keyframe {
+        self.scale = scale;
+        self
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::translate(self.translate) * Transform::from_quaternion(&self.rotate) * Transform::scale(self.scale)
+    }
+}
+
+/// A transform that moves through keyframes over time. Translation and scale
+/// are interpolated linearly and rotation spherically; before the first and
+/// after the last keyframe the transform holds still.
+#[derive(Clone)]
+pub struct AnimatedTransform {
+    keys: Vec<Keyframe>,
+}
+
+/// Steps each keyframe interval is sampled in to bound the motion.
+const BOUND_STEPS: usize = 16;
+
+impl AnimatedTransform {
+    /// Sorts the keyframes by time; there must be at least one.
+    pub fn new(mut keys: Vec<Keyframe>) -> AnimatedTransform {
+        assert!(!keys.is_empty(), "an animated transform needs a keyframe");
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        AnimatedTransform { keys }
+    }
+
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        self.key_at(time).transform()
+    }
+
+    fn key_at(&self, time: f64) -> Keyframe {
+        let next = self.keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keys[0];
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1];
+        }
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        Keyframe {
+            time,
+            translate: a.translate + t * (b.translate - a.translate),
+            rotate: a.rotate.slerp(&b.rotate, t),
+            scale: a.scale + t * (b.scale - a.scale),
+        }
+    }
+
+    /// A box around `bbox` at every time from `t0` to `t1`. Each keyframe
+    /// interval is sampled at a few times and the boxes there joined, which
+    /// covers the corners moving in straight lines between samples. Rotation
+    /// bends their paths outwards a little, by at most about how far they are
+    /// from the chord halfway through a step, so the result is grown by twice
+    /// the largest such distance.
+    pub fn bounding_box(&self, bbox: &Aabb, t0: f64, t1: f64) -> Aabb {
+        let mut times = vec![t0];
+        for pair in self.keys.windows(2) {
+            let (a, b) = (pair[0].time.max(t0), pair[1].time.min(t1));
+            for step in 1..=BOUND_STEPS {
+                let time = a + (b - a) * step as f64 / BOUND_STEPS as f64;
+                if time > t0 && time < t1 {
+                    times.push(time);
+                }
+            }
+        }
+        times.push(t1);
+
+        let corners: [Point3; 8] = std::array::from_fn(|corner| {
+            Point3::new(
+                if corner & 1 == 0 { bbox.min.x } else { bbox.max.x },
+                if corner & 2 == 0 { bbox.min.y } else { bbox.max.y },
+                if corner & 4 == 0 { bbox.min.z } else { bbox.max.z },
+            )
+        });
+        let corners_at = |time: f64| {
+            let transform = self.at(time);
+            corners.map(|c| transform.point(&c))
+        };
+        let mut result = self.at(t0).bounding_box(bbox);
+        let mut previous = corners_at(t0);
+        let mut pad: f64 = 0.0;
+        for pair in times.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let middle = corners_at(0.5 * (a + b));
+            let next = corners_at(b);
+            for i in 0..8 {
+                pad = pad.max((middle[i] - 0.5 * (previous[i] + next[i])).length());
+            }
+            previous = next;
+            result = surrounding_box(result, self.at(b).bounding_box(bbox));
+        }
+        let pad = 2.0 * pad;
+        let pad = Vec3::new(pad, pad, pad);
+        Aabb::new(result.min - pad, result.max + pad)
+    }
+}
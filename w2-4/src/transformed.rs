@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::transform::Transform;
+use crate::sampler::Sampler;
+
+/// An object under an arbitrary affine transform, from object to world space.
+#[derive(Clone)]
+pub struct Transformed {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    inverse: Transform,
+}
+
+impl Transformed {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Transformed {
+        Transformed {
+            object,
+            transform,
+            inverse: transform.inverse(),
+        }
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // The direction is not normalised after the transform, so `t` means
+        // the same in both spaces.
+        let local = Ray::new(self.inverse.point(&r.origin), self.inverse.vector(&r.direction), r.time);
+        let mut rec = self.object.hit(sampler, &local, t_min, t_max)?;
+        rec.p = self.transform.point(&rec.p);
+        // The object already turned the normal against the ray, and a normal
+        // transformed by the inverse transpose keeps which side it is on.
+        rec.normal = self.transform.normal(&rec.normal).unit_vector();
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
+        self.object
+            .bounding_box(t0, t1)
+            .map(|bbox| self.transform.bounding_box(&bbox))
+    }
+}
@@ -1,16 +1,18 @@
+use std::sync::Arc;
 use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
+use crate::sampler::Sampler;
 
 #[derive(Clone)]
 pub struct Translate {
-    ptr: Box<dyn Hittable>,
+    ptr: Arc<dyn Hittable>,
     offset: Vec3,
 }
 
 impl Translate {
-    pub fn new(p: Box<dyn Hittable>, displacement: Vec3) -> Translate {
+    pub fn new(p: Arc<dyn Hittable>, displacement: Vec3) -> Translate {
         Translate {
             ptr: p,
             offset: displacement,
@@ -19,9 +21,9 @@ impl Translate {
 }
 
 impl Hittable for Translate {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, sampler: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let moved_r = Ray::new(r.origin - self.offset, r.direction, r.time);
-        self.ptr.hit(&moved_r, t_min, t_max).map(|mut rec| {
+        self.ptr.hit(sampler, &moved_r, t_min, t_max).map(|rec| {
             let mut rec2 = HitRecord {
                 p: rec.p + self.offset,
                 normal: rec.normal,
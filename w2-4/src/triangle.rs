@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::Tree;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use crate::sampler::Sampler;
+
+const EPSILON: f64 = 1e-8;
+const PADDING: f64 = 0.0001;
+
+#[derive(Clone)]
+pub struct Triangle {
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    mp: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, mp: Arc<dyn Material>) -> Triangle {
+        Triangle { v0, v1, v2, mp }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, _: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, u, v) = intersect(r, &self.v0, &self.v1, &self.v2, t_min, t_max)?;
+        let mut rec = HitRecord {
+            p: r.at(t),
+            normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            mat_ptr: &*self.mp,
+            t,
+            u,
+            v,
+            front_face: false
+        };
+        let outward_normal = (self.v1 - self.v0).cross(&(self.v2 - self.v0)).unit_vector();
+        rec.set_face_normal(r, &outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        Some(triangle_box(&self.v0, &self.v1, &self.v2))
+    }
+}
+
+/// One face of a `MeshData`, as indices into its buffers.
+#[derive(Clone, Copy)]
+pub struct Face {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+/// Vertex buffers and faces of an indexed triangle mesh. Faces must only
+/// index into the buffers.
+#[derive(Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<Face>,
+}
+
+/// BVH over the faces of one mesh; `faces` is in the tree's leaf order.
+struct MeshBvh {
+    tree: Tree,
+    faces: Vec<usize>,
+}
+
+/// A triangle mesh with its own BVH. Clones share the vertex buffers and the
+/// BVH, so one mesh can be placed several times with `Translate`/`RotateY` or
+/// given a different material cheaply.
+#[derive(Clone)]
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+    bvh: Arc<MeshBvh>,
+    mp: Arc<dyn Material>,
+}
+
+impl TriangleMesh {
+    pub fn new(data: Arc<MeshData>, mp: Arc<dyn Material>) -> TriangleMesh {
+        let faces = (0..data.faces.len()).collect();
+        TriangleMesh::from_faces(data, faces, mp)
+    }
+
+    /// A mesh made of only the listed faces of `data`, e.g. one material group
+    /// of a model whose groups share the vertex buffers.
+    pub fn from_faces(data: Arc<MeshData>, faces: Vec<usize>, mp: Arc<dyn Material>) -> TriangleMesh {
+        let bvh = Arc::new(MeshBvh::build(&data, faces));
+        TriangleMesh { data, bvh, mp }
+    }
+
+    pub fn data(&self) -> &Arc<MeshData> {
+        &self.data
+    }
+
+    /// The same geometry with another material.
+    pub fn with_material(&self, mp: Arc<dyn Material>) -> TriangleMesh {
+        TriangleMesh {
+            data: self.data.clone(),
+            bvh: self.bvh.clone(),
+            mp,
+        }
+    }
+
+    fn hit_face(&self, r: &Ray, face: &Face, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let p = &self.data.positions;
+        let [i0, i1, i2] = face.positions;
+        let (t, b1, b2) = intersect(r, &p[i0], &p[i1], &p[i2], t_min, t_max)?;
+        let b0 = 1.0 - b1 - b2;
+
+        let (u, v) = match face.uvs {
+            Some([t0, t1, t2]) => {
+                let uvs = &self.data.uvs;
+                (
+                    b0 * uvs[t0].0 + b1 * uvs[t1].0 + b2 * uvs[t2].0,
+                    b0 * uvs[t0].1 + b1 * uvs[t1].1 + b2 * uvs[t2].1,
+                )
+            }
+            None => (b1, b2),
+        };
+        let outward_normal = match face.normals {
+            Some([n0, n1, n2]) => {
+                let n = &self.data.normals;
+                (b0 * n[n0] + b1 * n[n1] + b2 * n[n2]).unit_vector()
+            }
+            None => (p[i1] - p[i0]).cross(&(p[i2] - p[i0])).unit_vector(),
+        };
+
+        let mut rec = HitRecord {
+            p: r.at(t),
+            normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            mat_ptr: &*self.mp,
+            t,
+            u,
+            v,
+            front_face: false
+        };
+        rec.set_face_normal(r, &outward_normal);
+        Some(rec)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, _: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.bvh.tree.hit(r, t_min, t_max, |i, closest| {
+            self.hit_face(r, &self.data.faces[self.bvh.faces[i]], t_min, closest)
+        })
+    }
+
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        self.bvh.tree.bbox()
+    }
+}
+
+impl MeshBvh {
+    fn build(data: &MeshData, faces: Vec<usize>) -> MeshBvh {
+        let boxes: Vec<Aabb> = faces
+            .iter()
+            .map(|&face| {
+                let [i0, i1, i2] = data.faces[face].positions;
+                triangle_box(&data.positions[i0], &data.positions[i1], &data.positions[i2])
+            })
+            .collect();
+        let tree = Tree::build(&boxes);
+        let faces = tree.order.iter().map(|&i| faces[i]).collect();
+        MeshBvh { tree, faces }
+    }
+}
+
+/// Möller–Trumbore; returns `t` and the barycentric coordinates of `v1` and
+/// `v2`.
+fn intersect(r: &Ray, v0: &Point3, v1: &Point3, v2: &Point3, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let edge1 = *v1 - *v0;
+    let edge2 = *v2 - *v0;
+    let h = r.direction.cross(&edge2);
+    let a = edge1.dot(&h);
+    if a.abs() < EPSILON {
+        return None;
+    }
+    let f = 1.0 / a;
+    let s = r.origin - *v0;
+    let u = f * s.dot(&h);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&edge1);
+    let v = f * r.direction.dot(&q);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = f * edge2.dot(&q);
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, u, v))
+}
+
+fn triangle_box(v0: &Point3, v1: &Point3, v2: &Point3) -> Aabb {
+    let padding = Vec3::new(PADDING, PADDING, PADDING);
+    let small = Point3::new(
+        v0.x.min(v1.x).min(v2.x),
+        v0.y.min(v1.y).min(v2.y),
+        v0.z.min(v1.z).min(v2.z),
+    );
+    let big = Point3::new(
+        v0.x.max(v1.x).max(v2.x),
+        v0.y.max(v1.y).max(v2.y),
+        v0.z.max(v1.z).max(v2.z),
+    );
+    Aabb::new(small - padding, big + padding)
+}
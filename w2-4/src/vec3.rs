@@ -6,8 +6,7 @@ use std::ops::Div;
 use std::ops::Range;
 use std::f64::consts::PI;
 use std::fmt;
-use rand::Rng;
-use rand::rngs::ThreadRng;
+use crate::sampler::Sampler;
 
 pub struct Vec3 {
   pub x: f64,
@@ -137,25 +136,25 @@ impl Vec3 {
     *self / self.length()
   }
 
-  pub fn random(rng: &mut ThreadRng) -> Vec3 {
+  pub fn random<S: Sampler + ?Sized>(sampler: &mut S) -> Vec3 {
     Vec3 {
-      x: rng.gen(),
-      y: rng.gen(),
-      z: rng.gen()
+      x: sampler.next_f64(),
+      y: sampler.next_f64(),
+      z: sampler.next_f64()
     }
   }
 
-  pub fn random_range(rng: &mut ThreadRng, range: Range<f64>) -> Vec3 {
+  pub fn random_range<S: Sampler + ?Sized>(sampler: &mut S, range: Range<f64>) -> Vec3 {
     Vec3 {
-      x: rng.gen_range(range.clone()),
-      y: rng.gen_range(range.clone()),
-      z: rng.gen_range(range)
+      x: sampler.next_range(range.clone()),
+      y: sampler.next_range(range.clone()),
+      z: sampler.next_range(range)
     }
   }
 
-  pub fn random_in_unit_sphere(rng: &mut ThreadRng) -> Vec3 {
+  pub fn random_in_unit_sphere<S: Sampler + ?Sized>(sampler: &mut S) -> Vec3 {
     loop {
-      let p = Vec3::random_range(rng, -1.0..1.0);
+      let p = Vec3::random_range(sampler, -1.0..1.0);
       if p.length_squared() >= 1.0 {
         continue;
       }
@@ -163,19 +162,41 @@ impl Vec3 {
     }
   }
 
-  pub fn random_unit_vector(rng: &mut ThreadRng) -> Vec3 {
-    let a: f64 = rng.gen_range(0.0..2.0*PI);
-    let z: f64 = rng.gen_range(-1.0..1.0);
+  pub fn random_unit_vector<S: Sampler + ?Sized>(sampler: &mut S) -> Vec3 {
+    let a: f64 = sampler.next_range(0.0..2.0*PI);
+    let z: f64 = sampler.next_range(-1.0..1.0);
     let r: f64 = (1.0 - z*z).sqrt();
     Vec3 {
       x: r*a.cos(),
       y: r*a.sin(),
-      z: z
+      z
     }
   }
 
-  pub fn random_in_hemisphere(rng: &mut ThreadRng, normal: &Vec3) -> Vec3 {
-    let in_unit_sphere = Vec3::random_in_unit_sphere(rng);
+  pub fn random_cosine_direction<S: Sampler + ?Sized>(sampler: &mut S) -> Vec3 {
+    let r1 = sampler.next_f64();
+    let r2 = sampler.next_f64();
+    let phi = 2.0*PI*r1;
+    let z = (1.0-r2).sqrt();
+    Vec3 {
+      x: phi.cos()*r2.sqrt(),
+      y: phi.sin()*r2.sqrt(),
+      z
+    }
+  }
+
+  pub fn random_to_sphere<S: Sampler + ?Sized>(sampler: &mut S, radius: f64, distance_squared: f64) -> Vec3 {
+    let r1 = sampler.next_f64();
+    let r2 = sampler.next_f64();
+    let z = 1.0 + r2*((1.0-radius*radius/distance_squared).sqrt() - 1.0);
+    let phi = 2.0*PI*r1;
+    let x = phi.cos()*(1.0-z*z).sqrt();
+    let y = phi.sin()*(1.0-z*z).sqrt();
+    Vec3 { x, y, z }
+  }
+
+  pub fn random_in_hemisphere<S: Sampler + ?Sized>(sampler: &mut S, normal: &Vec3) -> Vec3 {
+    let in_unit_sphere = Vec3::random_in_unit_sphere(sampler);
     if in_unit_sphere.dot(normal) > 0.0 {
       in_unit_sphere
     } else {
@@ -194,10 +215,10 @@ impl Vec3 {
     r_out_parallel + r_out_perp
   }
 
-   pub fn random_in_unit_disk(rng: &mut ThreadRng) -> Vec3 {
+   pub fn random_in_unit_disk<S: Sampler + ?Sized>(sampler: &mut S) -> Vec3 {
     let mut p: Vec3;
     loop {
-      p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+      p = Vec3::new(sampler.next_range(-1.0..1.0), sampler.next_range(-1.0..1.0), 0.0);
       if p.length_squared() >= 1.0 {
         continue;
       }
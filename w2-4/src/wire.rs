@@ -0,0 +1,192 @@
+use std::io::{self, Read, Write};
+
+use crate::camera::CameraSettings;
+use crate::mlt::MltSettings;
+use crate::photon::PhotonSettings;
+use crate::projection::Projection;
+use crate::render::{IntegratorKind, RenderSettings};
+use crate::vec3::Vec3;
+
+pub fn write_u32<W: Write>(out: &mut W, v: u32) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+
+pub fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub fn write_i32<W: Write>(out: &mut W, v: i32) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+
+pub fn read_i32<R: Read>(input: &mut R) -> io::Result<i32> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+pub fn write_u64<W: Write>(out: &mut W, v: u64) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+
+pub fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn write_f64<W: Write>(out: &mut W, v: f64) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+
+pub fn read_f64<R: Read>(input: &mut R) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(input)?))
+}
+
+pub fn write_vec3<W: Write>(out: &mut W, v: &Vec3) -> io::Result<()> {
+    for c in [v.x, v.y, v.z] {
+        write_f64(out, c)?;
+    }
+    Ok(())
+}
+
+pub fn read_vec3<R: Read>(input: &mut R) -> io::Result<Vec3> {
+    Ok(Vec3::new(read_f64(input)?, read_f64(input)?, read_f64(input)?))
+}
+
+pub fn write_str<W: Write>(out: &mut W, s: &str) -> io::Result<()> {
+    write_u32(out, s.len() as u32)?;
+    out.write_all(s.as_bytes())
+}
+
+pub fn read_str<R: Read>(input: &mut R) -> io::Result<String> {
+    let len = read_u32(input)? as usize;
+    let mut buf = Vec::new();
+    input.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_settings<W: Write>(out: &mut W, s: &RenderSettings) -> io::Result<()> {
+    for v in [s.image_width, s.image_height, s.samples_per_pixel, s.max_depth] {
+        write_i32(out, v)?;
+    }
+    write_vec3(out, &s.background)?;
+    write_u64(out, s.seed)?;
+    let (integrator, photons) = match s.integrator {
+        IntegratorKind::Recursive => (0, None),
+        IntegratorKind::Path => (1, None),
+        IntegratorKind::Mis => (2, None),
+        IntegratorKind::Bdpt => (3, None),
+        IntegratorKind::PhotonMap(photons) => (4, Some(photons)),
+        IntegratorKind::Sppm(photons) => (5, Some(photons)),
+        IntegratorKind::Mlt(_) => (6, None),
+    };
+    write_u32(out, integrator)?;
+    if let Some(photons) = photons {
+        write_u32(out, photons.photons)?;
+        write_f64(out, photons.radius)?;
+    }
+    if let IntegratorKind::Mlt(mlt) = s.integrator {
+        write_u32(out, mlt.bootstrap)?;
+        write_u32(out, mlt.chains)?;
+        write_f64(out, mlt.large_step)?;
+        write_f64(out, mlt.sigma)?;
+    }
+    Ok(())
+}
+
+pub fn read_settings<R: Read>(input: &mut R) -> io::Result<RenderSettings> {
+    Ok(RenderSettings {
+        image_width: read_i32(input)?,
+        image_height: read_i32(input)?,
+        samples_per_pixel: read_i32(input)?,
+        max_depth: read_i32(input)?,
+        background: read_vec3(input)?,
+        seed: read_u64(input)?,
+        integrator: match read_u32(input)? {
+            0 => IntegratorKind::Recursive,
+            1 => IntegratorKind::Path,
+            2 => IntegratorKind::Mis,
+            3 => IntegratorKind::Bdpt,
+            4 => IntegratorKind::PhotonMap(read_photons(input)?),
+            5 => IntegratorKind::Sppm(read_photons(input)?),
+            6 => IntegratorKind::Mlt(MltSettings {
+                bootstrap: read_u32(input)?,
+                chains: read_u32(input)?,
+                large_step: read_f64(input)?,
+                sigma: read_f64(input)?,
+            }),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown integrator")),
+        },
+    })
+}
+
+fn read_photons<R: Read>(input: &mut R) -> io::Result<PhotonSettings> {
+    Ok(PhotonSettings {
+        photons: read_u32(input)?,
+        radius: read_f64(input)?,
+    })
+}
+
+pub fn camera_words(c: &CameraSettings) -> [f64; 24] {
+    let projection = match c.projection {
+        Projection::Perspective => [0.0, 0.0],
+        Projection::Orthographic { height } => [1.0, height],
+        Projection::Fisheye { fov } => [2.0, fov],
+        Projection::Equirectangular => [3.0, 0.0],
+        Projection::CubeMap => [4.0, 0.0],
+    };
+    [
+        c.lookfrom.x, c.lookfrom.y, c.lookfrom.z,
+        c.lookat.x, c.lookat.y, c.lookat.z,
+        c.vup.x, c.vup.y, c.vup.z,
+        c.vfov, c.aperture, c.focus_dist,
+        c.time0, c.time1,
+        c.lookfrom1.x, c.lookfrom1.y, c.lookfrom1.z,
+        c.lookat1.x, c.lookat1.y, c.lookat1.z,
+        c.blades as f64, c.exposure,
+        projection[0], projection[1],
+    ]
+}
+
+pub fn write_camera<W: Write>(out: &mut W, c: &CameraSettings) -> io::Result<()> {
+    for v in camera_words(c) {
+        write_f64(out, v)?;
+    }
+    Ok(())
+}
+
+pub fn read_camera<R: Read>(input: &mut R) -> io::Result<CameraSettings> {
+    let mut words = [0.0; 24];
+    for w in words.iter_mut() {
+        *w = read_f64(input)?;
+    }
+    let projection = match words[22] as u32 {
+        0 => Projection::Perspective,
+        1 => Projection::Orthographic { height: words[23] },
+        2 => Projection::Fisheye { fov: words[23] },
+        3 => Projection::Equirectangular,
+        4 => Projection::CubeMap,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown camera projection")),
+    };
+    Ok(CameraSettings {
+        lookfrom: Vec3::new(words[0], words[1], words[2]),
+        lookat: Vec3::new(words[3], words[4], words[5]),
+        vup: Vec3::new(words[6], words[7], words[8]),
+        vfov: words[9],
+        aperture: words[10],
+        focus_dist: words[11],
+        time0: words[12],
+        time1: words[13],
+        lookfrom1: Vec3::new(words[14], words[15], words[16]),
+        lookat1: Vec3::new(words[17], words[18], words[19]),
+        blades: words[20] as u32,
+        exposure: words[21],
+        projection,
+    })
+}
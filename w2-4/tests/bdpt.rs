@@ -0,0 +1,55 @@
+mod common;
+
+use common::{brightness, cornell_box, mean_difference, path_mean};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::bdpt::BdptIntegrator;
+use weekend::path::PathIntegrator;
+use weekend::render::{Integrator, IntegratorKind, Renderer};
+
+#[test]
+fn bdpt_matches_the_path_integrator_on_the_cornell_box() {
+    let mut scene = cornell_box(24, 32);
+    scene.settings.integrator = IntegratorKind::Bdpt;
+    let bdpt = brightness(&scene.renderer().render(&scene.world, &*scene.camera()));
+    scene.settings.integrator = IntegratorKind::Mis;
+    scene.settings.samples_per_pixel = 128;
+    let path = brightness(&scene.renderer().render(&scene.world, &*scene.camera()));
+
+    let (difference, error) = mean_difference(&bdpt, &path);
+    let expected = path.iter().sum::<f64>() / path.len() as f64;
+    assert!(difference.abs() < 4.0 * error, "{} vs {} +- {}", expected + difference, expected, 4.0 * error);
+}
+
+#[test]
+fn bdpt_without_a_camera_makes_up_for_light_tracing() {
+    // Through `ray_color` no light path reaches the lens, so the other
+    // strategies must carry their full weight.
+    let scene = cornell_box(24, 1);
+    let (background, depth) = (scene.settings.background, scene.settings.max_depth);
+    let integrators: [Box<dyn Integrator>; 2] = [
+        Box::new(BdptIntegrator::new(background, depth).with_lights(scene.lights.clone())),
+        Box::new(PathIntegrator::new(background, depth).with_lights(scene.lights.clone()).with_next_event(true)),
+    ];
+    let mut rng = StdRng::seed_from_u64(3);
+    let means: Vec<(f64, f64)> = integrators
+        .iter()
+        .zip([4_000, 16_000])
+        .map(|(integrator, paths)| path_mean(&scene, &**integrator, &mut rng, paths))
+        .collect();
+    let ((actual, actual_error), (expected, expected_error)) = (means[0], means[1]);
+    let tolerance = 4.0 * actual_error.hypot(expected_error);
+    assert!((actual - expected).abs() < tolerance, "{} vs {} +- {}", actual, expected, tolerance);
+}
+
+#[test]
+fn splatted_renders_are_reproducible() {
+    let mut scene = cornell_box(16, 4);
+    scene.settings.integrator = IntegratorKind::Bdpt;
+    let renderer: Renderer = scene.renderer();
+    assert!(renderer.splats());
+    let camera = scene.camera();
+    let a = renderer.render(&scene.world, &*camera);
+    let b = renderer.render(&scene.world, &*camera);
+    assert!(a.pixels.iter().zip(&b.pixels).all(|(a, b)| a.x == b.x && a.y == b.y && a.z == b.z));
+}
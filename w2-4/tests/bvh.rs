@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::box_model::BoxModel;
+use weekend::bvh::Bvh;
+use weekend::hittable::{HitRecord, Hittable};
+use weekend::hittable_list::HittableList;
+use weekend::material::{Lambertian, Material};
+use weekend::ray::Ray;
+use weekend::sampler::Sampler;
+use weekend::sphere::Sphere;
+use weekend::texture::SolidColor;
+use weekend::vec3::{Color, Vec3};
+
+/// A material of its own for every object, so hits tell which object they
+/// are on.
+fn material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)))))
+}
+
+fn object_of(rec: &HitRecord) -> *const () {
+    rec.mat_ptr as *const dyn Material as *const ()
+}
+
+/// Fires rays from all around at the objects and checks the BVH finds the
+/// same closest hit as trying every object.
+fn assert_same_hits(list: HittableList, rng: &mut StdRng) {
+    let brute = HittableList { objects: list.objects.clone() };
+    let bvh = Bvh::new(list, 0.0, 1.0);
+    let bbox = bvh.bounding_box(0.0, 1.0).unwrap();
+    let size = (bbox.max - bbox.min).length();
+    let mut hits = 0;
+    for _ in 0..2000 {
+        let target = bbox.min + (bbox.max - bbox.min) * Vec3::random(rng);
+        let origin = target + size * Vec3::random_unit_vector(rng);
+        let r = Ray::new(origin, target - origin, 0.0);
+        let expected = brute.hit(rng, &r, 0.001, f64::INFINITY);
+        let actual = bvh.hit(rng, &r, 0.001, f64::INFINITY);
+        match (expected, actual) {
+            (None, None) => {}
+            (Some(expected), Some(actual)) => {
+                assert_eq!(expected.t, actual.t);
+                assert_eq!(object_of(&expected), object_of(&actual));
+                hits += 1;
+            }
+            (expected, actual) => panic!("hit {} vs {}", expected.is_some(), actual.is_some()),
+        }
+    }
+    assert!(hits > 200, "{}", hits);
+}
+
+#[test]
+fn bvh_hits_match_a_brute_force_search() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut list = HittableList::new();
+    for _ in 0..300 {
+        let p = Vec3::random_range(&mut rng, -50.0..50.0);
+        if rng.next_f64() < 0.5 {
+            list.add(Arc::new(Sphere::new(p, rng.next_range(0.5..5.0), material())));
+        } else {
+            let size = Vec3::random_range(&mut rng, 0.5..8.0);
+            list.add(Arc::new(BoxModel::new(p, p + size, material())));
+        }
+    }
+    assert_same_hits(list, &mut rng);
+}
+
+#[test]
+fn bvh_hits_match_with_coincident_centroids() {
+    // No plane separates any of these, so nodes are halved until they fit
+    // in a leaf.
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut list = HittableList::new();
+    for i in 0..50 {
+        let r = 1.0 + i as f64 * 0.1;
+        list.add(Arc::new(Sphere::new(Vec3::zero(), r, material())));
+        list.add(Arc::new(BoxModel::new(Vec3::new(-r, -0.5, -0.5), Vec3::new(r, 0.5, 0.5), material())));
+    }
+    assert_same_hits(list, &mut rng);
+}
+
+#[test]
+fn bvh_hits_match_below_the_depth_limit() {
+    // With centroids at 16^-i every split peels off the largest sphere
+    // alone, so the tree runs into its depth limit and the last leaf holds
+    // dozens of spheres on nearly one point.
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut list = HittableList::new();
+    for i in 0..100 {
+        let x = 0.0625f64.powi(i);
+        list.add(Arc::new(Sphere::new(Vec3::new(x, 0.0, 0.0), 0.4 * x, material())));
+    }
+    assert_same_hits(list, &mut rng);
+}
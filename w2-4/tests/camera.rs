@@ -0,0 +1,95 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::aperture::{Aperture, ApertureImage};
+use weekend::camera::{Exposure, PhysicalCamera};
+use weekend::scene::Scene;
+use weekend::vec3::Vec3;
+
+const SCENE: &str = r#"
+[camera]
+lookfrom = [0.0, 0.0, 10.0]
+lookat = [0.0, 0.0, 0.0]
+blades = 5
+
+[camera.lens]
+focal_length = 50.0
+f_number = 2.0
+units_per_metre = 10.0
+shutter_time = 0.5
+iso = 200.0
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+"#;
+
+fn parse(source: &str) -> Result<Scene, String> {
+    Scene::parse(&mut StdRng::seed_from_u64(0), source, Path::new(".")).map_err(|e| e.to_string())
+}
+
+#[test]
+fn lenses_set_the_field_of_view_and_aperture() {
+    let camera = PhysicalCamera::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, 0.0));
+    // A 50 mm lens on a full-frame sensor, held in landscape.
+    assert!((camera.vfov() - 26.991).abs() < 1e-3);
+    assert!((camera.aperture_diameter() - 0.05 / 2.8).abs() < 1e-12);
+    let settings = camera.settings();
+    assert_eq!(settings.focus_dist, 10.0);
+    assert_eq!(settings.exposure, 1.0);
+
+    // Sunny 16: a second at f/1 and ISO 100 is EV100 0.
+    let exposure = Exposure { shutter_time: 1.0, iso: 100.0 };
+    assert!((exposure.scale(1.0) - 1.0 / 1.2).abs() < 1e-12);
+    let faster = Exposure { shutter_time: 0.25, iso: 100.0 };
+    assert!((faster.scale(1.0) - exposure.scale(1.0) / 4.0).abs() < 1e-12);
+}
+
+#[test]
+fn polygon_apertures_stay_inside_the_blades() {
+    let aperture = Aperture::Polygon { blades: 6, rotation: 0.0 };
+    let mut rng = StdRng::seed_from_u64(1);
+    let apothem = (std::f64::consts::PI / 6.0).cos();
+    let mut furthest: f64 = 0.0;
+    for _ in 0..10_000 {
+        let p = aperture.sample(&mut rng);
+        assert_eq!(p.z, 0.0);
+        // With a corner straight up, the hexagon's sides face along x.
+        assert!(p.x.abs() <= apothem + 1e-12);
+        furthest = furthest.max(p.length());
+    }
+    assert!(furthest <= 1.0 + 1e-12 && furthest > 0.95);
+}
+
+#[test]
+fn aperture_images_pick_bright_pixels() {
+    // Only the top-right quarter of a 2x2 mask is open.
+    let image = ApertureImage::new(2, 2, &[0.0, 1.0, 0.0, 0.0]).unwrap();
+    let aperture = Aperture::Image(Arc::new(image));
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..1000 {
+        let p = aperture.sample(&mut rng);
+        assert!((0.0..=1.0).contains(&p.x) && (0.0..=1.0).contains(&p.y));
+    }
+    assert!(ApertureImage::new(2, 1, &[0.0, 0.0]).is_none());
+}
+
+#[test]
+fn scenes_describe_lenses() {
+    let scene = parse(SCENE).unwrap();
+    let camera = &scene.camera;
+    assert_eq!(camera.blades, 5);
+    assert_eq!(camera.focus_dist, 10.0);
+    assert!((camera.aperture - 0.25).abs() < 1e-12);
+    let exposure = Exposure { shutter_time: 0.5, iso: 200.0 }.scale(2.0);
+    assert!((camera.exposure - exposure).abs() < 1e-12);
+
+    let e = parse(&SCENE.replace("blades = 5", "vfov = 40.0")).err().unwrap();
+    assert!(e.contains("cannot also set vfov"), "{}", e);
+    let e = parse(&SCENE.replace("blades = 5", "blades = 2")).err().unwrap();
+    assert!(e.contains("at least 3 blades"), "{}", e);
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use weekend::checkpoint::{Checkpoint, CheckpointError};
+use weekend::framebuffer::Accumulator;
+use weekend::mlt::MltSettings;
+use weekend::photon::PhotonSettings;
+use weekend::render::{IntegratorKind, Progressive, Stopped};
+use weekend::scene::Scene;
+use weekend::vec3::Vec3;
+
+fn cornell_box(width: i32, samples: i32) -> Scene {
+    let mut scene = common::cornell_box(width, samples);
+    scene.settings.integrator = IntegratorKind::Mis;
+    scene
+}
+
+const PASSES: Progressive = Progressive {
+    pass_samples: 2,
+    time_limit: None,
+};
+
+fn checkpoint(scene: &Scene, accumulator: Accumulator) -> Checkpoint {
+    Checkpoint {
+        scene_hash: 42,
+        settings: scene.settings,
+        camera: scene.camera,
+        accumulator,
+    }
+}
+
+fn bytes(checkpoint: &Checkpoint) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    checkpoint.write(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn resumed_renders_match_uninterrupted_ones() {
+    let scene = cornell_box(8, 8);
+    let camera = scene.camera();
+    let mut uninterrupted = Accumulator::new(8, 8);
+    scene.renderer().render_progressive(&scene.world, &*camera, &PASSES, &mut uninterrupted, |_| {});
+
+    let cancel = AtomicBool::new(false);
+    let mut accumulator = Accumulator::new(8, 8);
+    let stopped = scene.renderer().with_cancel(&cancel).render_progressive(
+        &scene.world,
+        &*camera,
+        &PASSES,
+        &mut accumulator,
+        |accumulator| cancel.store(accumulator.passes == 2, Ordering::Relaxed),
+    );
+    assert_eq!(stopped, Stopped::Cancelled);
+    let path = std::env::temp_dir().join(format!("weekend-checkpoint-{}", std::process::id()));
+    checkpoint(&scene, accumulator).save(&path).unwrap();
+
+    let resumed = Checkpoint::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    resumed.check_resumable(42, &scene.settings, &scene.camera).unwrap();
+    let mut accumulator = resumed.accumulator;
+    assert_eq!((accumulator.samples, accumulator.passes), (4, 2));
+    let stopped = scene.renderer().render_progressive(&scene.world, &*camera, &PASSES, &mut accumulator, |_| {});
+    assert_eq!(stopped, Stopped::TargetReached);
+
+    assert_eq!((accumulator.samples, accumulator.passes), (8, 4));
+    let bits = |a: &Accumulator| -> Vec<[u64; 3]> {
+        a.image().pixels.iter().map(|c| [c.x, c.y, c.z].map(f64::to_bits)).collect()
+    };
+    assert_eq!(bits(&accumulator), bits(&uninterrupted));
+}
+
+#[test]
+fn checkpoints_of_other_renders_are_rejected() {
+    let scene = cornell_box(4, 8);
+    let saved = checkpoint(&scene, Accumulator::new(4, 4));
+    let mismatch = |hash, scene: &Scene| match saved.check_resumable(hash, &scene.settings, &scene.camera) {
+        Err(CheckpointError::Mismatch(message)) => message,
+        Err(e) => panic!("expected a mismatch, got {}", e),
+        Ok(()) => panic!("expected a mismatch"),
+    };
+
+    assert!(mismatch(43, &scene).ends_with("different scene"));
+    let mut moved = cornell_box(4, 8);
+    moved.camera.lookfrom = moved.camera.lookfrom + Vec3::new(0.0, 1e-9, 0.0);
+    assert!(mismatch(42, &moved).ends_with("different camera"));
+    let mut reseeded = cornell_box(4, 8);
+    reseeded.settings.seed = 1;
+    assert!(mismatch(42, &reseeded).ends_with("different seed"));
+
+    // Only the target may change.
+    let mut longer = cornell_box(4, 8);
+    longer.settings.samples_per_pixel = 64;
+    saved.check_resumable(42, &longer.settings, &longer.camera).unwrap();
+}
+
+#[test]
+fn damaged_checkpoints_are_errors() {
+    let scene = cornell_box(4, 8);
+    let bytes = bytes(&checkpoint(&scene, Accumulator::new(4, 4)));
+    let format_error = |bytes: &[u8]| match Checkpoint::read(&mut &bytes[..]) {
+        Err(CheckpointError::Format(message)) => message,
+        Err(e) => panic!("expected a format error, got {}", e),
+        Ok(_) => panic!("expected a format error"),
+    };
+
+    for len in 0..bytes.len() {
+        assert_eq!(format_error(&bytes[..len]), "file is truncated", "{} bytes", len);
+    }
+    let mut old = bytes.clone();
+    old[..8].copy_from_slice(b"WKCKPT4\n");
+    assert_eq!(format_error(&old), "not a checkpoint file");
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert_eq!(format_error(&longer), "trailing data");
+
+    // The image size follows the magic and the scene hash.
+    let resized = |width: i32, height: i32| {
+        let mut bytes = bytes.clone();
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[20..24].copy_from_slice(&height.to_le_bytes());
+        format_error(&bytes)
+    };
+    assert_eq!(resized(0, 4), "bad image size");
+    assert_eq!(resized(-1, -1), "bad image size");
+    assert_eq!(resized(i32::MAX, i32::MAX), "bad image size");
+    // Far more pixels than the file holds, which are never allocated.
+    assert_eq!(resized(100_000, 100_000), "file is truncated");
+    assert_eq!(resized(4, 5), "file is truncated");
+    assert!(Checkpoint::read(&mut &bytes[..]).is_ok());
+}
+
+#[test]
+fn checkpoints_keep_every_integrator_and_its_settings() {
+    let photons = PhotonSettings { photons: 5000, radius: 2.5 };
+    let mlt = MltSettings { bootstrap: 1000, chains: 8, large_step: 0.5, sigma: 0.02 };
+    let integrators = [
+        IntegratorKind::Recursive,
+        IntegratorKind::Path,
+        IntegratorKind::Mis,
+        IntegratorKind::Bdpt,
+        IntegratorKind::PhotonMap(photons),
+        IntegratorKind::Sppm(photons),
+        IntegratorKind::Mlt(mlt),
+    ];
+    for integrator in integrators {
+        let mut scene = cornell_box(4, 8);
+        scene.settings.integrator = integrator;
+        let saved = bytes(&checkpoint(&scene, Accumulator::new(4, 4)));
+        let read = Checkpoint::read(&mut &saved[..]).unwrap();
+        assert_eq!(read.settings.integrator, integrator);
+        read.check_resumable(42, &scene.settings, &scene.camera).unwrap();
+    }
+}
@@ -0,0 +1,55 @@
+//! Scenes and statistics shared by the integrator tests.
+
+// Each test binary uses only some of these.
+#![allow(dead_code)]
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::builtin_scenes;
+use weekend::framebuffer::Framebuffer;
+use weekend::render::Integrator;
+use weekend::sampler::Sampler;
+use weekend::scene::Scene;
+
+/// The built-in Cornell box, `width` pixels square.
+pub fn cornell_box(width: i32, samples: i32) -> Scene {
+    let mut scene = builtin_scenes::load(&mut StdRng::seed_from_u64(0), "cornell_box").unwrap().unwrap();
+    scene.settings.image_width = width;
+    scene.settings.image_height = width;
+    scene.settings.samples_per_pixel = samples;
+    scene
+}
+
+/// The brightness of each pixel, row by row.
+pub fn brightness(fb: &Framebuffer) -> Vec<f64> {
+    fb.pixels.iter().map(|c| (c.x + c.y + c.z) / 3.0).collect()
+}
+
+/// The mean of `values` and its standard error.
+pub fn mean_and_error(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.0);
+    (mean, (variance / n).sqrt())
+}
+
+/// The mean and standard error of the per-pixel differences.
+pub fn mean_difference(a: &[f64], b: &[f64]) -> (f64, f64) {
+    let d: Vec<f64> = a.iter().zip(b).map(|(a, b)| a - b).collect();
+    mean_and_error(&d)
+}
+
+/// The mean brightness of `paths` paths through random points of the image,
+/// and its standard error.
+pub fn path_mean(scene: &Scene, integrator: &dyn Integrator, rng: &mut StdRng, paths: usize) -> (f64, f64) {
+    let camera = scene.camera();
+    let brightness: Vec<f64> = (0..paths)
+        .map(|_| {
+            let (s, t) = (rng.next_f64(), rng.next_f64());
+            let r = camera.get_ray(rng, s, t);
+            let c = integrator.ray_color(rng, &r, &scene.world);
+            (c.x + c.y + c.z) / 3.0
+        })
+        .collect();
+    mean_and_error(&brightness)
+}
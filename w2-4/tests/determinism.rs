@@ -0,0 +1,36 @@
+mod common;
+
+use common::cornell_box;
+use weekend::framebuffer::Framebuffer;
+use weekend::mlt::MltSettings;
+use weekend::render::IntegratorKind;
+use weekend::scene::Scene;
+
+fn render_on(threads: usize, scene: &Scene) -> Framebuffer {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+    pool.install(|| scene.renderer().render(&scene.world, &*scene.camera()))
+}
+
+fn bits(fb: &Framebuffer) -> Vec<[u64; 3]> {
+    fb.pixels.iter().map(|c| [c.x.to_bits(), c.y.to_bits(), c.z.to_bits()]).collect()
+}
+
+#[test]
+fn renders_do_not_depend_on_the_thread_count() {
+    // BDPT and MLT splat onto a shared film from many threads at once.
+    let integrators = [
+        IntegratorKind::Mis,
+        IntegratorKind::Bdpt,
+        IntegratorKind::Mlt(MltSettings { bootstrap: 1000, chains: 16, ..MltSettings::default() }),
+    ];
+    for integrator in integrators {
+        let mut scene = cornell_box(16, 4);
+        scene.settings.integrator = integrator;
+        scene.settings.seed = 7;
+        let serial = bits(&render_on(1, &scene));
+        assert_eq!(serial, bits(&render_on(4, &scene)), "{:?}", integrator);
+
+        scene.settings.seed = 8;
+        assert_ne!(serial, bits(&render_on(4, &scene)), "{:?}", integrator);
+    }
+}
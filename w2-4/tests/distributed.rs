@@ -0,0 +1,162 @@
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::builtin_scenes;
+use weekend::distributed::{Coordinator, Event, Job, Worker};
+use weekend::scene::Scene;
+
+fn builtin(name: &str, seed: u64) -> Result<Scene, String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    builtin_scenes::load(&mut rng, name)
+        .ok_or_else(|| format!("unknown scene {}", name))?
+        .map_err(|e| e.to_string())
+}
+
+fn load(job: &Job) -> Result<Scene, String> {
+    builtin(&job.scene, job.settings.seed)
+}
+
+#[test]
+fn workers_render_the_same_image_as_a_local_render() {
+    let mut scene = builtin("cornell_box", 7).unwrap();
+    scene.settings.image_width = 20;
+    scene.settings.image_height = 14;
+    scene.settings.samples_per_pixel = 2;
+    scene.settings.max_depth = 4;
+    scene.settings.seed = 7;
+    let expected = scene.renderer().render(&scene.world, &*scene.camera());
+
+    let coordinator = Coordinator::bind("127.0.0.1:0").unwrap().with_tile_size(8);
+    let addr = coordinator.local_addr().unwrap();
+    let job = Job {
+        scene: "cornell_box".to_string(),
+        settings: scene.settings,
+        camera: scene.camera,
+    };
+
+    let workers = thread::spawn(move || {
+        // This worker takes a tile and goes away without returning it.
+        let mut dying = Worker::connect(addr, load).unwrap();
+        assert!(dying.next_tile().unwrap().is_some());
+        drop(dying);
+
+        let workers: Vec<_> = (0..3)
+            .map(|_| thread::spawn(move || Worker::connect(addr, load).unwrap().run().unwrap()))
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).sum::<usize>()
+    });
+    let fb = coordinator.render(&job, |_| {}).unwrap();
+
+    // 3 x 2 tiles, all rendered by the workers that stayed.
+    assert_eq!(workers.join().unwrap(), 6);
+    assert_eq!((fb.width, fb.height), (expected.width, expected.height));
+    for (a, b) in fb.pixels.iter().zip(expected.pixels.iter()) {
+        assert_eq!([a.x, a.y, a.z].map(f64::to_bits), [b.x, b.y, b.z].map(f64::to_bits));
+    }
+}
+
+#[test]
+fn tiles_of_stalled_workers_are_rendered_again() {
+    let mut scene = builtin("cornell_box", 7).unwrap();
+    scene.settings.image_width = 16;
+    scene.settings.image_height = 16;
+    scene.settings.samples_per_pixel = 1;
+    let expected = scene.renderer().render(&scene.world, &*scene.camera());
+
+    // No timeout is set, so slow workers are never dropped.
+    let coordinator = Coordinator::bind("127.0.0.1:0").unwrap().with_tile_size(8);
+    let addr = coordinator.local_addr().unwrap();
+    let job = Job {
+        scene: "cornell_box".to_string(),
+        settings: scene.settings,
+        camera: scene.camera,
+    };
+
+    let workers = thread::spawn(move || {
+        let mut worker = Worker::connect(addr, load).unwrap();
+        let tile = worker.next_tile().unwrap().unwrap();
+        worker.send(&worker.render(&tile)).unwrap();
+
+        // Once a tile is back, this worker takes one and never answers, but
+        // stays connected until the render is over.
+        let mut stalled = Worker::connect(addr, load).unwrap();
+        assert!(stalled.next_tile().unwrap().is_some());
+        let start = Instant::now();
+        let count = 1 + worker.run().unwrap();
+        drop(stalled);
+        (count, start.elapsed())
+    });
+    let left = Mutex::new(Vec::new());
+    let fb = coordinator
+        .render(&job, |event| {
+            if let Event::WorkerLeft { reason, .. } = event {
+                left.lock().unwrap().push(reason);
+            }
+        })
+        .unwrap();
+
+    // The other worker rendered its own tiles and a copy of the stalled one.
+    let (count, waited) = workers.join().unwrap();
+    assert_eq!(count, 4);
+    assert!(waited < Duration::from_secs(60), "{:?}", waited);
+    let mut left = left.into_inner().unwrap();
+    left.sort();
+    assert_eq!(left, ["cut off at the end", "finished"]);
+    for (a, b) in fb.pixels.iter().zip(expected.pixels.iter()) {
+        assert_eq!([a.x, a.y, a.z].map(f64::to_bits), [b.x, b.y, b.z].map(f64::to_bits));
+    }
+}
+
+#[test]
+fn silent_and_garbled_workers_are_dropped() {
+    let mut scene = builtin("cornell_box", 7).unwrap();
+    scene.settings.image_width = 16;
+    scene.settings.image_height = 16;
+    scene.settings.samples_per_pixel = 1;
+
+    let coordinator = Coordinator::bind("127.0.0.1:0")
+        .unwrap()
+        .with_tile_size(8)
+        .with_tile_timeout(Duration::from_millis(200));
+    let addr = coordinator.local_addr().unwrap();
+    let job = Job {
+        scene: "cornell_box".to_string(),
+        settings: scene.settings,
+        camera: scene.camera,
+    };
+
+    let (left, reasons) = mpsc::channel();
+    let left = Mutex::new(left);
+    let workers = thread::spawn(move || {
+        let mut silent = Worker::connect(addr, load).unwrap();
+        assert!(silent.next_tile().unwrap().is_some());
+        let silent_reason = reasons.recv().unwrap();
+
+        // Claims a payload of 4 GiB instead of saying it is ready.
+        let mut garbled = TcpStream::connect(addr).unwrap();
+        garbled.write_all(&[2, 0xff, 0xff, 0xff, 0xff]).unwrap();
+        let garbled_reason = reasons.recv().unwrap();
+
+        let count = Worker::connect(addr, load).unwrap().run().unwrap();
+        drop((silent, garbled));
+        (silent_reason, garbled_reason, count)
+    });
+    coordinator
+        .render(&job, |event| {
+            if let Event::WorkerLeft { reason, .. } = event {
+                left.lock().unwrap().send(reason).unwrap();
+            }
+        })
+        .unwrap();
+
+    let (silent, garbled, count) = workers.join().unwrap();
+    assert_eq!(silent, "timed out");
+    assert_eq!(garbled, "protocol error: message too large");
+    assert_eq!(count, 4);
+}
@@ -0,0 +1,52 @@
+use rayon::prelude::*;
+use weekend::framebuffer::Film;
+use weekend::vec3::Color;
+
+/// Splats `value` as it ends up in the sums of a 3 by 3 film, whose pixels
+/// take 4/9 of each splat.
+fn splat(film: &Film, s: f64, t: f64, value: f64) {
+    film.splat(s, t, Color::new(value, 0.0, 2.0 * value) * 2.25);
+}
+
+#[test]
+fn many_tiny_splats_add_up() {
+    let film = Film::new(3, 3);
+    (0..1_000_000).into_par_iter().for_each(|_| splat(&film, 0.0, 0.0, 1e-9));
+    let c = film.get(0, 2);
+    assert!((c.x - 1e-3).abs() < 1e-9, "{}", c.x);
+    assert!((c.z - 2e-3).abs() < 1e-9, "{}", c.z);
+    assert_eq!(c.y, 0.0);
+
+    // Down to the last bit of the fixed point.
+    let film = Film::new(3, 3);
+    let bit = 2f64.powi(-48);
+    (0..1000).into_par_iter().for_each(|_| splat(&film, 0.0, 0.0, bit));
+    assert_eq!(film.get(0, 2).x, 1000.0 * bit);
+}
+
+#[test]
+fn fireflies_do_not_overflow() {
+    let film = Film::new(3, 3);
+    splat(&film, 1.0, 1.0, 1e13);
+    splat(&film, 1.0, 1.0, 0.5);
+    assert_eq!(film.get(2, 0).x, 1e13 + 0.5);
+
+    // Splats beyond the range are clamped rather than wrapping around.
+    splat(&film, 0.5, 0.5, 1e30);
+    assert_eq!(film.get(1, 1).x, (1u64 << 46) as f64);
+}
+
+#[test]
+fn sums_of_fireflies_saturate() {
+    let film = Film::new(3, 3);
+    (0..1000).into_par_iter().for_each(|_| splat(&film, 0.5, 0.5, 1e30));
+    let c = film.get(1, 1);
+    let limit = (1u64 << 47) as f64;
+    assert!(c.x > 0.99 * limit && c.x <= limit, "{}", c.x);
+    assert!(c.z > 0.99 * limit && c.z <= limit, "{}", c.z);
+
+    // The same holds below zero.
+    (0..3000).into_par_iter().for_each(|_| splat(&film, 0.5, 0.5, -1e30));
+    let c = film.get(1, 1);
+    assert!(c.x < -0.99 * limit && c.x >= -limit, "{}", c.x);
+}
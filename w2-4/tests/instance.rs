@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::bvh::Bvh;
+use weekend::hittable::Hittable;
+use weekend::hittable_list::HittableList;
+use weekend::instance::Instance;
+use weekend::material::{Lambertian, Material};
+use weekend::ray::Ray;
+use weekend::sphere::Sphere;
+use weekend::texture::SolidColor;
+use weekend::vec3::{Color, Vec3};
+
+fn lambertian(c: f64) -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(c, c, c)))))
+}
+
+#[test]
+fn instances_share_their_object() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Vec3::zero(), 1.0, lambertian(0.5)));
+    let material = lambertian(0.8);
+    let mut list = HittableList::new();
+    for i in 0..100 {
+        for j in 0..100 {
+            let instance = Instance::new(sphere.clone())
+                .with_scale(0.4)
+                .with_rotation_y(i as f64)
+                .with_offset(Vec3::new(i as f64, 0.0, j as f64))
+                .with_material(material.clone());
+            list.add(Arc::new(instance));
+        }
+    }
+    let world = Bvh::new(list, 0.0, 1.0);
+
+    assert_eq!(Arc::strong_count(&sphere), 10_001);
+    assert_eq!(Arc::strong_count(&material), 10_001);
+    let r = Ray::new(Vec3::new(42.0, 10.0, 17.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+    let rec = world.hit(&mut rng, &r, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.p - Vec3::new(42.0, 0.4, 17.0)).length() < 1e-9);
+}
+
+#[test]
+fn instance_transforms_hits_and_bounds() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Vec3::new(1.0, 0.0, 0.0), 1.0, lambertian(0.5)));
+    let instance = Instance::new(sphere)
+        .with_scale(2.0)
+        .with_rotation_y(90.0)
+        .with_offset(Vec3::new(0.0, 5.0, 0.0));
+
+    // The sphere's centre (1, 0, 0) ends up at (0, 5, -2) with radius 2.
+    let r = Ray::new(Vec3::new(0.0, 5.0, 10.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+    let rec = instance.hit(&mut rng, &r, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.t - 10.0).abs() < 1e-9);
+    assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+    assert!(rec.front_face);
+
+    let bbox = instance.bounding_box(0.0, 1.0).unwrap();
+    assert!((bbox.min - Vec3::new(-2.0, 3.0, -4.0)).length() < 1e-9);
+    assert!((bbox.max - Vec3::new(2.0, 7.0, 0.0)).length() < 1e-9);
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::constant_medium::ConstantMedium;
+use weekend::hittable::Hittable;
+use weekend::material::{IsoTropic, Lambertian};
+use weekend::ray::Ray;
+use weekend::sphere::Sphere;
+use weekend::texture::SolidColor;
+use weekend::vec3::{Color, Vec3};
+
+#[test]
+fn media_draw_free_paths_from_the_sampler() {
+    let white = Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)));
+    let boundary = Arc::new(Sphere::new(Vec3::zero(), 100.0, Arc::new(Lambertian::new(white.clone()))));
+    let density = 2.0;
+    let medium = ConstantMedium::new(boundary, density, Arc::new(IsoTropic::new(white)));
+
+    // The same ray, traced again and again, scatters at different depths
+    // whose mean is the mean free path.
+    let r = Ray::new(Vec3::new(0.0, 0.0, 200.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+    let mut rng = StdRng::seed_from_u64(0);
+    let n = 20_000;
+    let depths: Vec<f64> =
+        (0..n).map(|_| medium.hit(&mut rng, &r, 0.001, f64::INFINITY).unwrap().t - 100.0).collect();
+    let mean = depths.iter().sum::<f64>() / n as f64;
+    assert!((mean - 1.0 / density).abs() < 0.02, "{}", mean);
+    assert!(depths.windows(2).all(|pair| pair[0] != pair[1]));
+
+    // And the sampler alone decides them.
+    let again: Vec<f64> = {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..n).map(|_| medium.hit(&mut rng, &r, 0.001, f64::INFINITY).unwrap().t - 100.0).collect()
+    };
+    assert_eq!(depths, again);
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use std::path::Path;
+
+use common::{cornell_box, mean_and_error};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::framebuffer::Framebuffer;
+use weekend::mlt::{MltSampler, MltSettings};
+use weekend::render::IntegratorKind;
+use weekend::sampler::{pass_sampler, Sampler};
+use weekend::scene::Scene;
+
+/// Distance between two numbers on the circle `[0, 1)` mutations wrap on.
+fn wrapped(a: f64, b: f64) -> f64 {
+    let d = (a - b).abs();
+    d.min(1.0 - d)
+}
+
+#[test]
+fn rejected_mutations_are_undone() {
+    let mut sampler = MltSampler::new(pass_sampler(1, 0, 0), 1e-6, 0.3);
+    let mut decide = StdRng::seed_from_u64(2);
+    let mut current: Vec<Option<f64>> = (0..3).map(|_| Some(sampler.next_f64())).collect();
+    let (mut small, mut large) = (0, 0);
+    for iteration in 0..2000 {
+        sampler.start_iteration();
+        // Paths draw different amounts of numbers from one try to the next.
+        let proposed: Vec<f64> = (0..2 + iteration % 5).map(|_| sampler.next_f64()).collect();
+        let near: Vec<bool> = proposed
+            .iter()
+            .zip(&current)
+            .filter_map(|(p, c)| c.map(|c| wrapped(*p, c) < 1e-4))
+            .collect();
+        // A small step moves every number a little; a large one draws all
+        // of them anew, wherever the last rejected step left them.
+        let is_small = near.iter().all(|&near| near);
+        assert!(is_small || near.iter().all(|&near| !near), "{}: {:?}", iteration, near);
+        if is_small {
+            small += 1;
+        } else {
+            large += 1;
+        }
+        if decide.next_f64() < 0.5 {
+            sampler.accept();
+            if !is_small {
+                current.truncate(proposed.len());
+            }
+            current.resize(current.len().max(proposed.len()), None);
+            for (c, p) in current.iter_mut().zip(&proposed) {
+                *c = Some(*p);
+            }
+        } else {
+            sampler.reject();
+        }
+    }
+    assert!(small > 1200 && large > 450, "{} {}", small, large);
+}
+
+/// Average brightness over `blocks` by `blocks` squares of the image.
+fn block_means(fb: &Framebuffer, blocks: usize) -> Vec<f64> {
+    let size = fb.width / blocks;
+    let mut means = vec![0.0; blocks * blocks];
+    for y in 0..blocks * size {
+        for x in 0..blocks * size {
+            let c = fb.get(x, y);
+            means[x / size + blocks * (y / size)] += (c.x + c.y + c.z) / 3.0 / (size * size) as f64;
+        }
+    }
+    means
+}
+
+#[test]
+fn mlt_matches_the_path_integrator_on_the_cornell_box() {
+    let mut scene = cornell_box(24, 128);
+    scene.settings.integrator = IntegratorKind::Mlt(MltSettings { bootstrap: 20_000, chains: 64, ..MltSettings::default() });
+    let mlt = block_means(&scene.renderer().render(&scene.world, &*scene.camera()), 2);
+    scene.settings.integrator = IntegratorKind::Mis;
+    scene.settings.samples_per_pixel = 512;
+    let path = block_means(&scene.renderer().render(&scene.world, &*scene.camera()), 2);
+
+    let (actual, expected) = (mean_and_error(&mlt).0, mean_and_error(&path).0);
+    assert!((actual - expected).abs() < 0.05 * expected, "{} vs {}", actual, expected);
+    // The chains spread the light where the path tracer finds it.
+    for (a, b) in mlt.iter().zip(&path) {
+        assert!((a - b).abs() < 0.15 * b, "{:?} vs {:?}", mlt, path);
+    }
+}
+
+#[test]
+fn mlt_renders_are_reproducible() {
+    let mut scene = cornell_box(16, 4);
+    scene.settings.integrator = IntegratorKind::Mlt(MltSettings { bootstrap: 1000, chains: 16, ..MltSettings::default() });
+    let renderer = scene.renderer();
+    assert!(renderer.splats());
+    let camera = scene.camera();
+    let a = renderer.render(&scene.world, &*camera);
+    let b = renderer.render(&scene.world, &*camera);
+    assert!(a.pixels.iter().zip(&b.pixels).all(|(a, b)| a.x == b.x && a.y == b.y && a.z == b.z));
+    assert!(a.pixels.iter().any(|c| c.x > 0.0));
+}
+
+#[test]
+fn scene_files_set_the_mlt_settings() {
+    let integrator = |render: &str| {
+        let source = format!("[render]\n{}\n[camera]\nlookfrom = [0.0, 0.0, 5.0]\nlookat = [0.0, 0.0, 0.0]\n", render);
+        Scene::parse(&mut StdRng::seed_from_u64(0), &source, Path::new("."))
+            .map(|scene| scene.settings.integrator)
+            .map_err(|e| e.to_string())
+    };
+    let mlt = IntegratorKind::Mlt(MltSettings { chains: 8, large_step: 0.5, ..MltSettings::default() });
+    assert_eq!(integrator("integrator = \"mlt\"\nchains = 8\nlarge_step_probability = 0.5").unwrap(), mlt);
+    let e = integrator("integrator = \"mlt\"\nlarge_step_probability = 1.5").unwrap_err();
+    assert!(e.contains("between 0 and 1"), "{}", e);
+    let e = integrator("integrator = \"bdpt\"\nchains = 8").unwrap_err();
+    assert!(e.contains("only apply to the mlt integrator"), "{}", e);
+}
+
+#[test]
+fn mlt_needs_two_pixels_each_way() {
+    let parse = |size: &str| {
+        let source = format!(
+            "[render]\n{}\nintegrator = \"mlt\"\n[camera]\nlookfrom = [0.0, 0.0, 5.0]\nlookat = [0.0, 0.0, 0.0]\n",
+            size
+        );
+        Scene::parse(&mut StdRng::seed_from_u64(0), &source, Path::new(".")).map(|_| ()).map_err(|e| e.to_string())
+    };
+    parse("image_width = 2\naspect_ratio = 1.0").unwrap();
+    for size in ["image_width = 1\naspect_ratio = 1.0", "image_width = 3\naspect_ratio = 2.0"] {
+        let e = parse(size).err().unwrap();
+        assert!(e.contains("at least 2 pixels"), "{}", e);
+    }
+}
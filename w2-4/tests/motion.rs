@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::animated::Animated;
+use weekend::box_model::BoxModel;
+use weekend::camera::CameraSettings;
+use weekend::hittable::Hittable;
+use weekend::material::{Lambertian, Material};
+use weekend::projection::Projection;
+use weekend::ray::Ray;
+use weekend::sampler::Sampler;
+use weekend::texture::SolidColor;
+use weekend::transform::{AnimatedTransform, Keyframe, Quaternion};
+use weekend::vec3::{Color, Vec3};
+
+fn lambertian() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)))))
+}
+
+fn close(a: Vec3, b: Vec3) -> bool {
+    (a - b).length() < 1e-9
+}
+
+fn spin() -> AnimatedTransform {
+    AnimatedTransform::new(vec![
+        Keyframe::new(0.0),
+        Keyframe::new(1.0)
+            .with_translation(Vec3::new(10.0, 0.0, 0.0))
+            .with_rotation(Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 90.0))
+            .with_scale(Vec3::new(2.0, 1.0, 1.0)),
+    ])
+}
+
+fn spinning_box() -> Animated {
+    let cube: Arc<dyn Hittable> = Arc::new(BoxModel::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0), lambertian()));
+    Animated::new(cube, spin())
+}
+
+#[test]
+fn animated_objects_move_with_ray_time() {
+    let animated = spinning_box();
+    let down = Vec3::new(0.0, -1.0, 0.0);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let rec = animated.hit(&mut rng, &Ray::new(Vec3::new(0.0, 5.0, 0.0), down, 0.0), 0.001, f64::INFINITY).unwrap();
+    assert!(close(rec.p, Vec3::new(0.0, 1.0, 0.0)));
+    assert!(close(rec.normal, Vec3::new(0.0, 1.0, 0.0)));
+    assert!(animated.hit(&mut rng, &Ray::new(Vec3::new(10.0, 5.0, 0.0), down, 0.0), 0.001, f64::INFINITY).is_none());
+
+    // At the end the box is at x = 10, stretched along z by the rotation.
+    assert!(animated.hit(&mut rng, &Ray::new(Vec3::new(10.0, 5.0, 1.9), down, 1.0), 0.001, f64::INFINITY).is_some());
+    assert!(animated.hit(&mut rng, &Ray::new(Vec3::new(11.9, 5.0, 0.0), down, 1.0), 0.001, f64::INFINITY).is_none());
+    assert!(animated.hit(&mut rng, &Ray::new(Vec3::new(0.0, 5.0, 0.0), down, 1.0), 0.001, f64::INFINITY).is_none());
+}
+
+#[test]
+fn animated_bounding_box_covers_the_motion() {
+    let animated = spinning_box();
+    let bbox = animated.bounding_box(0.0, 1.0).unwrap();
+    let corners: Vec<Vec3> = (0..8)
+        .map(|c| Vec3::new(
+            if c & 1 == 0 { -1.0 } else { 1.0 },
+            if c & 2 == 0 { -1.0 } else { 1.0 },
+            if c & 4 == 0 { -1.0 } else { 1.0 },
+        ))
+        .collect();
+
+    let motion = spin();
+    for i in 0..=1000 {
+        let transform = motion.at(i as f64 / 1000.0);
+        for c in corners.iter() {
+            let p = transform.point(c);
+            for a in 0..3 {
+                assert!(bbox.min.d(a) <= p.d(a) && p.d(a) <= bbox.max.d(a));
+            }
+        }
+    }
+    // Not much bigger than the exact box of the swept corners either.
+    assert!(bbox.max.x < 11.5 && bbox.min.z > -2.5);
+}
+
+#[test]
+fn slerp_turns_at_constant_speed() {
+    let axis = Vec3::new(1.0, 1.0, 0.0);
+    let a = Quaternion::from_axis_angle(axis, 10.0);
+    let b = Quaternion::from_axis_angle(axis, 130.0);
+    let mid = a.slerp(&b, 0.25);
+    assert!((mid.dot(&Quaternion::from_axis_angle(axis, 40.0)) - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn cameras_move_over_the_shutter() {
+    let settings = CameraSettings {
+        lookfrom: Vec3::new(0.0, 0.0, 0.0),
+        lookat: Vec3::new(0.0, 0.0, -1.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        vfov: 90.0,
+        aperture: 0.0,
+        focus_dist: 1.0,
+        time0: 0.0,
+        time1: 2.0,
+        lookfrom1: Vec3::new(4.0, 0.0, 0.0),
+        lookat1: Vec3::new(4.0, 0.0, -1.0),
+        blades: 0,
+        exposure: 1.0,
+        projection: Projection::Perspective,
+    };
+    let camera = settings.build(1.0);
+    let mut rng = StdRng::seed_from_u64(3);
+    for _ in 0..100 {
+        let (s, t) = (rng.next_f64(), rng.next_f64());
+        let r = camera.get_ray(&mut rng, s, t);
+        assert!(close(r.origin, Vec3::new(2.0 * r.time, 0.0, 0.0)));
+        assert!(close(r.direction, Vec3::new(2.0 * s - 1.0, 2.0 * t - 1.0, -1.0)));
+    }
+}
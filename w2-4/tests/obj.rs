@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use weekend::obj::{Obj, ObjError};
+
+const QUAD: &str = "\
+# a unit square and a triangle
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 4/1/1
+f -4//1 -3//1 -1//1
+";
+
+fn parse(source: &str) -> Result<Obj, ObjError> {
+    Obj::parse(source, Path::new("."))
+}
+
+/// The line and message of a parse error.
+fn parse_error(source: &str) -> (usize, String) {
+    match parse(source) {
+        Err(ObjError::Parse { line, message }) => (line, message),
+        Err(e) => panic!("expected a parse error, got {}", e),
+        Ok(_) => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+fn triangles_and_quads_load() {
+    let obj = parse(QUAD).unwrap();
+    let data = &obj.data;
+    assert_eq!((data.positions.len(), data.uvs.len(), data.normals.len()), (4, 3, 1));
+    // The quad is fanned into two triangles; relative indices count back
+    // from the last vertex.
+    let positions: Vec<[usize; 3]> = data.faces.iter().map(|f| f.positions).collect();
+    assert_eq!(positions, [[0, 1, 2], [0, 2, 3], [0, 1, 3]]);
+    assert_eq!(data.faces[1].uvs, Some([0, 2, 0]));
+    assert_eq!(data.faces[2].uvs, None);
+    assert_eq!(data.faces[2].normals, Some([0, 0, 0]));
+    assert_eq!(obj.groups.len(), 1);
+    assert_eq!(obj.groups[0].1, [0, 1, 2]);
+}
+
+#[test]
+fn bad_faces_are_reported_at_their_line() {
+    let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+    let cases = [
+        ("f 1 2\n", "a face needs at least three vertices"),
+        ("f 1 2 x\n", "expected a vertex index, found `x`"),
+        ("f 1/1 2 3\n", "texture coordinate index 1 is out of range"),
+        ("f 1//// 2 3\n", "malformed face vertex `1////`"),
+        ("f 1 2 4\n", "vertex index 4 is out of range"),
+        ("f 0 1 2\n", "vertex index 0 is out of range"),
+        ("f -4 -2 -1\n", "vertex index -4 is out of range"),
+        ("f 1//2 2 3\n", "normal index 2 is out of range"),
+    ];
+    for (face, expected) in cases {
+        let (line, message) = parse_error(&format!("{}{}", vertices, face));
+        assert_eq!((line, message.as_str()), (4, expected), "{}", face);
+    }
+}
+
+#[test]
+fn non_numeric_vertices_are_reported_at_their_line() {
+    assert_eq!(parse_error("v 0 0 0\nv 1 zero 0\n"), (2, "expected a number, found `zero`".to_string()));
+    assert_eq!(parse_error("v 0 0 0\n\nvn 1 0\n"), (3, "missing number".to_string()));
+    assert_eq!(parse_error("vt u\n"), (1, "expected a number, found `u`".to_string()));
+}
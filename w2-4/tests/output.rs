@@ -0,0 +1,68 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use image::codecs::hdr::HdrDecoder;
+use weekend::framebuffer::Framebuffer;
+use weekend::output::{self, ImageFormat};
+use weekend::vec3::Color;
+
+/// A 3 by 2 image, black but for the bottom right pixel.
+fn framebuffer() -> Framebuffer {
+    let mut fb = Framebuffer::new(3, 2);
+    fb.set(2, 1, Color::new(0.25, 1.0, 0.0));
+    fb
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("weekend-output-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn every_format_decodes_to_the_framebuffer() {
+    let dir = temp_dir();
+    let formats = [
+        ImageFormat::Ppm,
+        ImageFormat::PpmBinary,
+        ImageFormat::Png,
+        ImageFormat::Png16,
+        ImageFormat::Hdr,
+        ImageFormat::Exr,
+    ];
+    for (i, format) in formats.into_iter().enumerate() {
+        let path = dir.join(format!("{}.{}", i, format.extension()));
+        output::save(&framebuffer(), &path, format).unwrap();
+        let image = image::open(&path).unwrap_or_else(|e| panic!("{:?}: {}", format, e));
+        assert_eq!((image.width(), image.height()), (3, 2), "{:?}", format);
+        match format {
+            // Gamma 2 takes 0.25 to a half, and 1 clamps to the top value.
+            ImageFormat::Ppm | ImageFormat::PpmBinary | ImageFormat::Png => {
+                let image = image.as_rgb8().unwrap();
+                assert_eq!(image.get_pixel(2, 1).0, [128, 255, 0], "{:?}", format);
+                assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0], "{:?}", format);
+            }
+            ImageFormat::Png16 => {
+                let image = image.as_rgb16().unwrap();
+                assert_eq!(image.get_pixel(2, 1).0, [32768, 65535, 0]);
+                assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0]);
+            }
+            // The linear formats keep the values, which RGBE stores exactly
+            // for powers of two. `image::open` tone maps HDR files to 8 bits,
+            // so their floats are read directly.
+            ImageFormat::Hdr => {
+                let decoder = HdrDecoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+                let pixels = decoder.read_image_hdr().unwrap();
+                assert_eq!(pixels[5].0, [0.25, 1.0, 0.0]);
+                assert_eq!(pixels[0].0, [0.0, 0.0, 0.0]);
+            }
+            ImageFormat::Exr => {
+                let image = image.as_rgb32f().unwrap();
+                assert_eq!(image.get_pixel(2, 1).0, [0.25, 1.0, 0.0]);
+                assert_eq!(image.get_pixel(0, 0).0, [0.0, 0.0, 0.0]);
+            }
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
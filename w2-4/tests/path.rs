@@ -0,0 +1,150 @@
+mod common;
+
+use std::path::Path;
+use std::sync::Arc;
+
+use common::{cornell_box, path_mean};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::hittable_list::HittableList;
+use weekend::hittable::HitRecord;
+use weekend::material::{Material, Metal};
+use weekend::path::{Bounces, PathIntegrator};
+use weekend::ray::Ray;
+use weekend::render::{Integrator, RecursiveIntegrator};
+use weekend::scene::Scene;
+use weekend::sphere::Sphere;
+use weekend::vec3::{Color, Vec3};
+
+/// A fuzzy metal floor reflecting a small light, a glossy sphere and a big
+/// dim light.
+const GLOSSY: &str = r#"
+background = [0.0, 0.0, 0.0]
+
+[camera]
+lookfrom = [0.0, 2.0, 8.0]
+lookat = [0.0, 0.5, 0.0]
+vfov = 40.0
+
+[materials]
+floor = { type = "metal", albedo = [0.8, 0.8, 0.8], fuzz = 0.2 }
+bulb = { type = "diffuse_light", emit = [50.0, 50.0, 50.0] }
+panel = { type = "diffuse_light", emit = [1.0, 1.0, 1.0] }
+
+[[objects]]
+type = "xz_rect"
+x0 = -10.0
+x1 = 10.0
+z0 = -10.0
+z1 = 10.0
+k = 0.0
+material = "floor"
+
+[[objects]]
+type = "sphere"
+center = [0.0, 1.0, 0.0]
+radius = 1.0
+material = { type = "metal", albedo = [0.9, 0.6, 0.3], fuzz = 0.5 }
+
+[[objects]]
+type = "sphere"
+center = [1.5, 3.0, -2.0]
+radius = 0.2
+material = "bulb"
+
+[[objects]]
+type = "xy_rect"
+x0 = -4.0
+x1 = 4.0
+y0 = 0.0
+y1 = 4.0
+k = -4.0
+material = "panel"
+"#;
+
+#[test]
+fn roulette_matches_the_recursive_integrator() {
+    let scene = cornell_box(600, 1);
+    let (background, depth) = (scene.settings.background, scene.settings.max_depth);
+    let recursive = RecursiveIntegrator::new(background, depth).with_lights(scene.lights.clone());
+    // Roulette from the first bounce, to exercise it as much as possible.
+    let path = PathIntegrator::new(background, depth).with_lights(scene.lights.clone()).with_roulette_depth(0);
+
+    let (expected, expected_error) = path_mean(&scene, &recursive, &mut StdRng::seed_from_u64(1), 20_000);
+    let (actual, error) = path_mean(&scene, &path, &mut StdRng::seed_from_u64(2), 20_000);
+    let tolerance = 4.0 * expected_error.hypot(error);
+    assert!((actual - expected).abs() < tolerance, "{} vs {} +- {}", actual, expected, tolerance);
+
+    // Without the roulette the two follow the same paths, only summing them
+    // up in a different order.
+    let path = PathIntegrator::new(background, depth).with_lights(scene.lights.clone()).with_roulette_depth(depth);
+    let mean = |integrator: &dyn Integrator| path_mean(&scene, integrator, &mut StdRng::seed_from_u64(1), 1000).0;
+    let (a, b) = (mean(&path), mean(&recursive));
+    assert!((a - b).abs() < 1e-9 * b, "{} vs {}", a, b);
+}
+
+#[test]
+fn bounce_limits_end_paths_by_kind() {
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 0.0, -2.0), 1.0, Arc::new(Metal::new(Color::new(0.5, 0.5, 0.5), 0.0)))));
+    let background = Color::new(1.0, 1.0, 1.0);
+    let r = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+    let color = |bounces| {
+        let integrator = PathIntegrator::new(background, 10).with_bounces(bounces);
+        integrator.ray_color(&mut StdRng::seed_from_u64(0), &r, &world).x
+    };
+    let mirror = Bounces { diffuse: 0, specular: 1, volume: 0 };
+    assert_eq!(color(mirror), 0.5);
+    assert_eq!(color(Bounces { specular: 0, ..mirror }), 0.0);
+}
+
+/// Checks MIS against the recursive integrator, which mixes in light
+/// sampling without weighing it.
+fn matches_in_expectation(scene: &Scene, paths: usize) {
+    let (background, depth) = (scene.settings.background, scene.settings.max_depth);
+    let recursive = RecursiveIntegrator::new(background, depth).with_lights(scene.lights.clone());
+    let mis = PathIntegrator::new(background, depth).with_lights(scene.lights.clone()).with_next_event(true);
+
+    let (expected, expected_error) = path_mean(scene, &recursive, &mut StdRng::seed_from_u64(1), paths);
+    let (actual, error) = path_mean(scene, &mis, &mut StdRng::seed_from_u64(2), paths);
+    let tolerance = 4.0 * expected_error.hypot(error);
+    assert!((actual - expected).abs() < tolerance, "{} vs {} +- {}", actual, expected, tolerance);
+}
+
+#[test]
+fn next_event_estimation_matches_the_recursive_integrator() {
+    matches_in_expectation(&cornell_box(600, 1), 20_000);
+    let glossy = Scene::parse(&mut StdRng::seed_from_u64(0), GLOSSY, Path::new(".")).unwrap();
+    assert_eq!(glossy.lights.objects.len(), 2);
+    matches_in_expectation(&glossy, 20_000);
+}
+
+#[test]
+fn metal_lobes_have_the_density_they_are_sampled_with() {
+    let metal = Metal::new(Color::new(1.0, 1.0, 1.0), 0.3);
+    let normal = Vec3::new(0.0, 1.0, 0.0);
+    let rec = HitRecord {
+        p: Vec3::new(0.0, 0.0, 0.0),
+        normal,
+        mat_ptr: &metal,
+        t: 1.0,
+        u: 0.0,
+        v: 0.0,
+        front_face: true,
+    };
+    let r_in = Ray::new(Vec3::new(-1.0, 0.2, 0.0), Vec3::new(1.0, -0.2, 0.0), 0.0);
+    let mut rng = StdRng::seed_from_u64(4);
+
+    // Integrated over the sphere, the density is the share of bounces that
+    // stay above the surface.
+    let n = 200_000;
+    let integral: f64 = (0..n)
+        .map(|_| metal.specular_pdf(&r_in, &rec, &Vec3::random_unit_vector(&mut rng)))
+        .sum::<f64>() * 4.0 * std::f64::consts::PI / n as f64;
+    let kept = (0..n).filter(|_| metal.scatter(&mut rng, &r_in, &rec).is_some()).count() as f64 / n as f64;
+    assert!(kept < 0.99 && (integral - kept).abs() < 0.02, "{} vs {}", integral, kept);
+
+    // Directions the lobe cannot reach have no density.
+    assert_eq!(metal.specular_pdf(&r_in, &rec, &Vec3::new(-1.0, 0.2, 0.0)), 0.0);
+    assert_eq!(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0).specular_pdf(&r_in, &rec, &Vec3::new(1.0, 0.2, 0.0)), 0.0);
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use std::f64::consts::PI;
+use std::path::Path;
+
+use common::{brightness, cornell_box, mean_and_error};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::photon::{Photon, PhotonMap, PhotonMapIntegrator, PhotonSettings};
+use weekend::render::{Integrator, IntegratorKind};
+use weekend::sampler::Sampler;
+use weekend::scene::Scene;
+use weekend::vec3::{Color, Point3, Vec3};
+
+fn random_photons(n: usize) -> Vec<Photon> {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..n)
+        .map(|_| Photon {
+            p: Vec3::random_range(&mut rng, -1.0..1.0),
+            direction: Vec3::new(0.0, 1.0, 0.0),
+            power: Color::new(1.0, 1.0, 1.0),
+        })
+        .collect()
+}
+
+#[test]
+fn kd_tree_finds_what_a_linear_search_finds() {
+    let photons = random_photons(2000);
+    let map = PhotonMap::new(photons.clone());
+    assert_eq!(map.len(), 2000);
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..50 {
+        let p = Vec3::random_range(&mut rng, -1.2..1.2);
+        let radius = rng.next_range(0.05..0.4);
+        let mut found = Vec::new();
+        map.within(&p, radius, |photon| found.push(photon.p.x));
+        let mut expected: Vec<f64> =
+            photons.iter().filter(|photon| (photon.p - p).length() <= radius).map(|photon| photon.p.x).collect();
+        found.sort_by(f64::total_cmp);
+        expected.sort_by(f64::total_cmp);
+        assert_eq!(found, expected);
+
+        let mut distances: Vec<f64> = photons.iter().map(|photon| (photon.p - p).length()).collect();
+        distances.sort_by(f64::total_cmp);
+        assert!((map.nearest_distance(&p, 10) - distances[9]).abs() < 1e-12);
+    }
+}
+
+fn mean_brightness(scene: &Scene) -> f64 {
+    mean_and_error(&brightness(&scene.renderer().render(&scene.world, &*scene.camera()))).0
+}
+
+#[test]
+fn photon_mapping_lights_the_cornell_box_like_path_tracing() {
+    let mut scene = cornell_box(24, 64);
+    scene.settings.integrator = IntegratorKind::Mis;
+    let expected = mean_brightness(&scene);
+    scene.settings.samples_per_pixel = 4;
+    scene.settings.integrator = IntegratorKind::PhotonMap(PhotonSettings { photons: 50_000, radius: 0.0 });
+    let actual = mean_brightness(&scene);
+    // The radius blurs light across edges, which leaves the total about the
+    // same.
+    assert!((actual - expected).abs() < 0.1 * expected, "{} vs {}", actual, expected);
+}
+
+#[test]
+fn progressive_passes_shrink_the_radius() {
+    let scene = cornell_box(24, 1);
+    let (background, depth) = (scene.settings.background, scene.settings.max_depth);
+    let settings = PhotonSettings { photons: 1000, radius: 10.0 };
+    let fixed = PhotonMapIntegrator::new(background, depth, settings).with_lights(scene.lights.clone());
+    assert_eq!(fixed.radius(5), 10.0);
+    assert!(fixed.for_pass(&scene.world, 1).is_none());
+
+    let sppm = PhotonMapIntegrator::new(background, depth, settings)
+        .with_lights(scene.lights.clone())
+        .with_progressive(true)
+        .trace(&scene.world);
+    assert!(sppm.photon_map().len() > 1000);
+    assert_eq!(sppm.radius(0), 10.0);
+    let radii: Vec<f64> = (0..50).map(|pass| sppm.radius(pass)).collect();
+    assert!(radii.windows(2).all(|pair| pair[1] < pair[0]));
+    // r^2 falls off as n^(a - 1) with a = 2/3.
+    let ratio = (radii[49] / radii[24]).powi(2);
+    assert!((ratio - 0.5f64.powf(1.0 / 3.0)).abs() < 0.02, "{}", ratio);
+    assert!(sppm.for_pass(&scene.world, 0).is_none());
+    assert!(sppm.for_pass(&scene.world, 1).is_some());
+}
+
+/// Power per unit area arriving around `p`.
+fn irradiance(map: &PhotonMap, p: &Point3, radius: f64) -> f64 {
+    let mut power = 0.0;
+    map.within(p, radius, |photon| power += (photon.power.x + photon.power.y + photon.power.z) / 3.0);
+    power / (PI * radius * radius)
+}
+
+#[test]
+fn glass_spheres_focus_photons_into_caustics() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/caustics.toml");
+    let scene = Scene::load(&mut StdRng::seed_from_u64(0), &path).unwrap();
+    let settings = PhotonSettings { photons: 100_000, radius: 0.0 };
+    let integrator = PhotonMapIntegrator::new(scene.settings.background, scene.settings.max_depth, settings)
+        .with_lights(scene.lights.clone())
+        .trace(&scene.world);
+    let map = integrator.photon_map();
+
+    // Under the large sphere, away from the light, and in the open at the
+    // same distance from the light on the other side.
+    let brightest = (0..13)
+        .flat_map(|i| (0..15).map(move |j| Point3::new(-2.0 + 0.1 * i as f64, 0.0, -0.6 + 0.1 * j as f64)))
+        .map(|p| irradiance(map, &p, 0.05))
+        .fold(0.0, f64::max);
+    let open = irradiance(map, &Point3::new(2.46, 0.0, 0.15), 0.05);
+    assert!(brightest > 3.0 * open, "{} vs {}", brightest, open);
+}
+
+#[test]
+fn scene_files_set_the_photon_settings() {
+    let integrator = |render: &str| {
+        let source = format!("[render]\n{}\n[camera]\nlookfrom = [0.0, 0.0, 5.0]\nlookat = [0.0, 0.0, 0.0]\n", render);
+        Scene::parse(&mut StdRng::seed_from_u64(0), &source, Path::new("."))
+            .map(|scene| scene.settings.integrator)
+            .map_err(|e| e.to_string())
+    };
+    let photon_map = IntegratorKind::PhotonMap(PhotonSettings::default());
+    assert_eq!(integrator("integrator = \"photon_map\"").unwrap(), photon_map);
+    let sppm = IntegratorKind::Sppm(PhotonSettings { photons: 5000, radius: 2.5 });
+    assert_eq!(integrator("integrator = \"sppm\"\nphotons = 5000\nphoton_radius = 2.5").unwrap(), sppm);
+    let e = integrator("integrator = \"mis\"\nphotons = 5000").unwrap_err();
+    assert!(e.contains("only apply to the photon_map and sppm"), "{}", e);
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use weekend::framebuffer::{Accumulator, Framebuffer};
+use weekend::render::{IntegratorKind, Progressive, Stopped};
+use weekend::scene::Scene;
+use weekend::vec3::Color;
+
+fn cornell_box(width: i32, samples: i32) -> Scene {
+    let mut scene = common::cornell_box(width, samples);
+    scene.settings.integrator = IntegratorKind::Mis;
+    scene
+}
+
+const PASSES: Progressive = Progressive {
+    pass_samples: 4,
+    time_limit: None,
+};
+
+#[test]
+fn progressive_renders_average_their_passes() {
+    let scene = cornell_box(12, 12);
+    let renderer = scene.renderer();
+    let camera = scene.camera();
+    let mut accumulator = Accumulator::new(12, 12);
+    let mut images = Vec::new();
+    let stopped = renderer.render_progressive(&scene.world, &*camera, &PASSES, &mut accumulator, |accumulator| {
+        images.push(accumulator.image())
+    });
+    assert_eq!(stopped, Stopped::TargetReached);
+    assert_eq!((accumulator.samples, accumulator.passes), (12, 3));
+
+    let passes: Vec<Framebuffer> = (0..3)
+        .map(|pass| renderer.render_pass(&scene.world, &*camera, pass, 4).unwrap())
+        .collect();
+    for (k, image) in images.iter().enumerate() {
+        for (i, pixel) in image.pixels.iter().enumerate() {
+            let mean = passes[..=k].iter().fold(Color::zero(), |sum, fb| sum + fb.pixels[i])
+                / (k + 1) as f64;
+            assert!((*pixel - mean).length() < 1e-12 * (1.0 + mean.length()), "pass {}, pixel {}", k, i);
+        }
+    }
+}
+
+#[test]
+fn progressive_renders_stop_when_cancelled_or_out_of_time() {
+    let scene = cornell_box(4, 100);
+    let camera = scene.camera();
+
+    let cancel = AtomicBool::new(false);
+    let renderer = scene.renderer().with_cancel(&cancel);
+    let mut accumulator = Accumulator::new(4, 4);
+    let stopped = renderer.render_progressive(&scene.world, &*camera, &PASSES, &mut accumulator, |accumulator| {
+        if accumulator.passes == 2 {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    });
+    assert_eq!((stopped, accumulator.passes), (Stopped::Cancelled, 2));
+
+    // The first pass finishes however short the time limit.
+    let hurried = Progressive {
+        time_limit: Some(Duration::from_nanos(1)),
+        ..PASSES
+    };
+    let mut accumulator = Accumulator::new(4, 4);
+    let stopped = scene.renderer().render_progressive(&scene.world, &*camera, &hurried, &mut accumulator, |_| {});
+    assert_eq!((stopped, accumulator.passes), (Stopped::TimeLimit, 1));
+
+    // A resumed render that already has its samples does no more.
+    let mut accumulator = Accumulator::new(4, 4);
+    accumulator.add(&Framebuffer::new(4, 4), 100);
+    let stopped = scene.renderer().render_progressive(&scene.world, &*camera, &PASSES, &mut accumulator, |_| {
+        panic!("no pass was needed")
+    });
+    assert_eq!((stopped, accumulator.passes), (Stopped::TargetReached, 1));
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::camera::{Camera, CameraSettings};
+use weekend::checkpoint::Checkpoint;
+use weekend::framebuffer::Accumulator;
+use weekend::projection::Projection;
+use weekend::render::{IntegratorKind, RenderSettings};
+use weekend::scene::Scene;
+use weekend::vec3::Vec3;
+
+/// Looking down -z from the origin, with y up.
+fn settings(projection: Projection) -> CameraSettings {
+    CameraSettings {
+        lookfrom: Vec3::new(0.0, 0.0, 0.0),
+        lookat: Vec3::new(0.0, 0.0, -1.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        vfov: 90.0,
+        aperture: 0.0,
+        focus_dist: 1.0,
+        time0: 0.0,
+        time1: 0.0,
+        lookfrom1: Vec3::new(0.0, 0.0, 0.0),
+        lookat1: Vec3::new(0.0, 0.0, -1.0),
+        blades: 0,
+        exposure: 1.0,
+        projection,
+    }
+}
+
+fn direction(camera: &dyn Camera, s: f64, t: f64) -> Vec3 {
+    camera.get_ray(&mut StdRng::seed_from_u64(0), s, t).direction.unit_vector()
+}
+
+fn close(a: Vec3, b: Vec3) -> bool {
+    (a - b).length() < 1e-9
+}
+
+#[test]
+fn orthographic_rays_are_parallel() {
+    let camera = settings(Projection::Orthographic { height: 4.0 }).build(2.0);
+    for (s, t) in [(0.0, 0.0), (0.5, 0.5), (1.0, 0.25)] {
+        let r = camera.get_ray(&mut StdRng::seed_from_u64(0), s, t);
+        assert!(close(r.direction.unit_vector(), Vec3::new(0.0, 0.0, -1.0)));
+        assert!(close(r.origin, Vec3::new((s - 0.5) * 8.0, (t - 0.5) * 4.0, 0.0)));
+    }
+}
+
+#[test]
+fn fisheyes_are_equidistant() {
+    let camera = settings(Projection::Fisheye { fov: 180.0 }).build(1.0);
+    assert!(close(direction(&*camera, 0.5, 0.5), Vec3::new(0.0, 0.0, -1.0)));
+    // The top edge is 90 degrees off the axis, halfway to it 45.
+    assert!(close(direction(&*camera, 0.5, 1.0), Vec3::new(0.0, 1.0, 0.0)));
+    let half = std::f64::consts::FRAC_1_SQRT_2;
+    assert!(close(direction(&*camera, 0.0, 0.5), Vec3::new(-1.0, 0.0, 0.0)));
+    assert!(close(direction(&*camera, 0.75, 0.5), Vec3::new(half, 0.0, -half)));
+}
+
+#[test]
+fn equirectangular_covers_the_sphere() {
+    let camera = settings(Projection::Equirectangular).build(2.0);
+    assert!(close(direction(&*camera, 0.5, 0.5), Vec3::new(0.0, 0.0, -1.0)));
+    assert!(close(direction(&*camera, 0.75, 0.5), Vec3::new(1.0, 0.0, 0.0)));
+    assert!(close(direction(&*camera, 0.0, 0.5), Vec3::new(0.0, 0.0, 1.0)));
+    assert!(close(direction(&*camera, 0.3, 1.0), Vec3::new(0.0, 1.0, 0.0)));
+}
+
+#[test]
+fn cube_map_faces_look_along_the_axes() {
+    let camera = settings(Projection::CubeMap).build(1.5);
+    let faces = [
+        (1.0 / 6.0, 0.75, Vec3::new(1.0, 0.0, 0.0)),
+        (0.5, 0.75, Vec3::new(-1.0, 0.0, 0.0)),
+        (5.0 / 6.0, 0.75, Vec3::new(0.0, 1.0, 0.0)),
+        (1.0 / 6.0, 0.25, Vec3::new(0.0, -1.0, 0.0)),
+        (0.5, 0.25, Vec3::new(0.0, 0.0, -1.0)),
+        (5.0 / 6.0, 0.25, Vec3::new(0.0, 0.0, 1.0)),
+    ];
+    for (s, t, forward) in faces {
+        assert!(close(direction(&*camera, s, t), forward));
+    }
+    // Each face spans 90 degrees, and the front one is upright.
+    let up_right = direction(&*camera, 7.0 / 12.0, 0.375);
+    assert!(close(up_right, Vec3::new(0.5, 0.5, -1.0).unit_vector()));
+}
+
+#[test]
+fn projections_survive_checkpoints() {
+    for projection in [Projection::Orthographic { height: 3.0 }, Projection::Fisheye { fov: 220.0 }, Projection::CubeMap] {
+        let checkpoint = Checkpoint {
+            scene_hash: 0,
+            settings: RenderSettings {
+                image_width: 2,
+                image_height: 2,
+                samples_per_pixel: 1,
+                max_depth: 1,
+                background: Vec3::new(0.0, 0.0, 0.0),
+                seed: 0,
+                integrator: IntegratorKind::Recursive,
+            },
+            camera: settings(projection),
+            accumulator: Accumulator::new(2, 2),
+        };
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        assert_eq!(Checkpoint::read(&mut &bytes[..]).unwrap().camera.projection, projection);
+    }
+}
+
+#[test]
+fn scenes_choose_a_projection() {
+    let scene = |projection: &str| {
+        let source = format!(
+            "[camera]\nlookfrom = [0.0, 0.0, 5.0]\nlookat = [0.0, 0.0, 0.0]\nvfov = 90.0\nfocus_dist = 2.0\n{}\n",
+            projection
+        );
+        Scene::parse(&mut StdRng::seed_from_u64(0), &source, Path::new(".")).map_err(|e| e.to_string())
+    };
+    // As tall as the 90 degree view at the focus distance.
+    let camera = scene("projection = { type = \"orthographic\" }").unwrap().camera;
+    match camera.projection {
+        Projection::Orthographic { height } => assert!((height - 4.0).abs() < 1e-12),
+        other => panic!("{:?}", other),
+    }
+    let camera = scene("projection = { type = \"fisheye\" }").unwrap().camera;
+    assert_eq!(camera.projection, Projection::Fisheye { fov: 180.0 });
+    let camera = scene("projection = { type = \"cube_map\" }").unwrap().camera;
+    assert_eq!(camera.projection, Projection::CubeMap);
+
+    let e = scene("aperture = 0.1\nprojection = { type = \"equirectangular\" }").err().unwrap();
+    assert!(e.contains("only a perspective camera"), "{}", e);
+    let e = scene("projection = { type = \"fisheye\", fov = 400.0 }").err().unwrap();
+    assert!(e.contains("between 0 and 360"), "{}", e);
+}
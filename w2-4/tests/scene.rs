@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::render::IntegratorKind;
+use weekend::scene::{Scene, SceneError};
+
+const CAMERA: &str = r#"
+[camera]
+lookfrom = [0.0, 0.0, 5.0]
+lookat = [0.0, 0.0, 0.0]
+"#;
+
+/// The line and message of the error from parsing `CAMERA` followed by
+/// `objects`, which starts on line 5.
+fn error(objects: &str) -> (usize, String) {
+    let source = format!("{}{}", CAMERA, objects);
+    match Scene::parse(&mut StdRng::seed_from_u64(0), &source, Path::new(".")) {
+        Ok(_) => panic!("parsed:\n{}", source),
+        Err(SceneError::Parse { line, message }) | Err(SceneError::Invalid { line, message }) => (line, message),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[test]
+fn good_scenes_parse() {
+    let source = format!(
+        "{}{}",
+        CAMERA,
+        r#"
+[materials]
+grey = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "grey"
+"#
+    );
+    let scene = Scene::parse(&mut StdRng::seed_from_u64(0), &source, Path::new(".")).unwrap();
+    assert_eq!(scene.world.objects.len(), 1);
+}
+
+#[test]
+fn malformed_toml_reports_its_line() {
+    let (line, message) = error("\n[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0\nradius = 1.0\n");
+    // The parser notices on the next line that the array did not end.
+    assert_eq!(line, 9, "{}", message);
+    assert!(message.contains("expected `]`"), "{}", message);
+}
+
+#[test]
+fn unknown_materials_report_the_object_line() {
+    let objects = r#"
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "chrome"
+"#;
+    let (line, message) = error(objects);
+    assert_eq!(line, 6, "{}", message);
+    assert!(message.contains("unknown material `chrome`"), "{}", message);
+}
+
+#[test]
+fn missing_fields_report_their_table() {
+    let objects = r#"
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+
+[[objects]]
+type = "sphere"
+center = [0.0, 2.0, 0.0]
+material = { type = "lambertian", albedo = [0.5, 0.5, 0.5] }
+"#;
+    let (line, message) = error(objects);
+    assert!(message.contains("radius"), "{}", message);
+    assert_eq!(line, 12, "{}", message);
+}
+
+#[test]
+fn images_must_be_two_pixels_each_way() {
+    // 100 / 200 rounds down to no rows at all.
+    for render in ["image_width = 100\naspect_ratio = 200.0", "image_width = 1", "aspect_ratio = -1.0"] {
+        let (line, message) = error(&format!("\n[render]\n{}\n", render));
+        assert_eq!(line, 6, "{}", message);
+        assert!(message.contains("at least 2 pixels wide and high"), "{}", message);
+    }
+}
+
+#[test]
+fn render_tables_choose_the_integrator() {
+    let integrator = |name: &str| {
+        let source = format!("[render]\nintegrator = \"{}\"\n{}", name, CAMERA);
+        Scene::parse(&mut StdRng::seed_from_u64(0), &source, Path::new(".")).map(|scene| scene.settings.integrator)
+    };
+    assert_eq!(integrator("recursive").unwrap(), IntegratorKind::Recursive);
+    assert_eq!(integrator("path").unwrap(), IntegratorKind::Path);
+    assert_eq!(integrator("mis").unwrap(), IntegratorKind::Mis);
+    assert_eq!(integrator("bdpt").unwrap(), IntegratorKind::Bdpt);
+    match integrator("whitted") {
+        Err(SceneError::Parse { line, message }) => {
+            assert_eq!(line, 2, "{}", message);
+            assert!(message.contains("unknown variant `whitted`"), "{}", message);
+        }
+        _ => panic!("expected a parse error"),
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::scene::Scene;
+use weekend::timeline::{Timeline, Track};
+use weekend::vec3::Vec3;
+
+const SCENE: &str = r#"
+[camera]
+lookfrom = [0.0, 0.0, 10.0]
+lookat = [0.0, 0.0, 0.0]
+vfov = 40.0
+
+[animation]
+frames = [0, 10]
+fps = 10.0
+shutter = 0.5
+
+[[animation.camera]]
+time = 0.0
+lookfrom = [0.0, 0.0, 10.0]
+
+[[animation.camera]]
+time = 1.0
+lookfrom = [10.0, 0.0, 10.0]
+vfov = 60.0
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = { type = "metal", albedo = [[0.0, [1.0, 1.0, 1.0]], [1.0, [0.0, 0.0, 0.0]]], fuzz = [[0.0, 0.0], [1.0, 1.0]] }
+"#;
+
+fn parse(frame: Option<u32>) -> Scene {
+    Scene::parse_frame(&mut StdRng::seed_from_u64(0), SCENE, Path::new("."), frame).unwrap()
+}
+
+fn close(a: Vec3, b: Vec3) -> bool {
+    (a - b).length() < 1e-9
+}
+
+#[test]
+fn tracks_interpolate_and_hold() {
+    let track = Track::new(vec![(2.0, 20.0), (0.0, 0.0), (1.0, 5.0)]).unwrap();
+    assert_eq!(track.at(-1.0), 0.0);
+    assert_eq!(track.at(0.5), 2.5);
+    assert_eq!(track.at(1.5), 12.5);
+    assert_eq!(track.at(3.0), 20.0);
+    assert!(Track::<f64>::new(Vec::new()).is_none());
+}
+
+#[test]
+fn frames_set_the_camera_and_shutter() {
+    let scene = parse(Some(4));
+    let camera = &scene.camera;
+    assert_eq!((camera.time0, camera.time1), (0.4, 0.45));
+    assert!(close(camera.lookfrom, Vec3::new(4.0, 0.0, 10.0)));
+    assert!(close(camera.lookfrom1, Vec3::new(4.5, 0.0, 10.0)));
+    assert!(close(camera.lookat, Vec3::new(0.0, 0.0, 0.0)));
+    // vfov has a single key, so it holds that value.
+    assert_eq!(camera.vfov, 60.0);
+
+    let timeline = scene.timeline.unwrap();
+    assert_eq!(timeline.frames(), 0..=10);
+    assert_eq!(Timeline::file_name(7, "png"), "frame_0007.png");
+
+    // Without a frame the scene is in its first one.
+    assert!(close(parse(None).camera.lookfrom, Vec3::new(0.0, 0.0, 10.0)));
+}
+
+#[test]
+fn bad_animations_are_rejected() {
+    let reversed = SCENE.replace("frames = [0, 10]", "frames = [10, 0]");
+    let e = Scene::parse(&mut StdRng::seed_from_u64(0), &reversed, Path::new(".")).err().unwrap();
+    assert!(e.to_string().contains("first <= last"), "{}", e);
+
+    let keyless = SCENE.replace("fuzz = [[0.0, 0.0], [1.0, 1.0]]", "fuzz = []");
+    let e = Scene::parse(&mut StdRng::seed_from_u64(0), &keyless, Path::new(".")).err().unwrap();
+    assert!(e.to_string().contains("at least one key"), "{}", e);
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use weekend::hittable::Hittable;
+use weekend::material::{Lambertian, Material};
+use weekend::ray::Ray;
+use weekend::sphere::Sphere;
+use weekend::texture::SolidColor;
+use weekend::transform::{Matrix4, Quaternion, Transform};
+use weekend::transformed::Transformed;
+use weekend::vec3::{Color, Vec3};
+
+fn lambertian() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)))))
+}
+
+fn close(a: Vec3, b: Vec3) -> bool {
+    (a - b).length() < 1e-9
+}
+
+#[test]
+fn constructors_agree() {
+    let p = Vec3::new(0.3, -1.2, 2.5);
+    let axis = Vec3::new(1.0, 2.0, -0.5);
+
+    let t = Transform::rotate(axis, 37.0);
+    let q = Transform::from_quaternion(&Quaternion::from_axis_angle(axis, 37.0));
+    assert!(close(t.point(&p), q.point(&p)));
+    assert!(close(Transform::rotate_y(90.0).point(&Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 0.0, -1.0)));
+
+    let euler = Transform::euler(10.0, 20.0, 30.0);
+    let composed = Transform::from_quaternion(
+        &(Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 30.0)
+            * Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 20.0)
+            * Quaternion::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), 10.0)),
+    );
+    assert!(close(euler.point(&p), composed.point(&p)));
+
+    let from = Vec3::new(1.0, 2.0, 3.0);
+    let look = Transform::look_at(from, Vec3::new(4.0, 2.0, 3.0), Vec3::new(0.0, 1.0, 0.0));
+    assert!(close(look.point(&Vec3::zero()), from));
+    assert!(close(look.vector(&Vec3::new(0.0, 0.0, 1.0)), Vec3::new(1.0, 0.0, 0.0)));
+    assert!(close(look.vector(&Vec3::new(0.0, 1.0, 0.0)), Vec3::new(0.0, 1.0, 0.0)));
+}
+
+#[test]
+fn general_matrices_invert() {
+    let m = Matrix4::new([
+        [2.0, 1.0, 0.0, 3.0],
+        [0.0, 1.0, 4.0, -1.0],
+        [1.0, 0.0, 1.0, 2.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    let t = Transform::new(m).unwrap();
+    let p = Vec3::new(0.7, -0.2, 1.9);
+    assert!(close(t.inverse().point(&t.point(&p)), p));
+    assert!(Transform::new(Matrix4::new([[0.0; 4]; 4])).is_none());
+}
+
+#[test]
+fn transformed_sphere_becomes_an_ellipsoid() {
+    let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Vec3::zero(), 1.0, lambertian()));
+    let transform = Transform::translate(Vec3::new(0.0, 0.0, -5.0))
+        * Transform::rotate_z(90.0)
+        * Transform::scale(Vec3::new(1.0, 3.0, 1.0));
+    let ellipsoid = Transformed::new(sphere, transform);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    // The long axis now lies along x.
+    let r = Ray::new(Vec3::new(10.0, 0.0, -5.0), Vec3::new(-2.0, 0.0, 0.0), 0.0);
+    let rec = ellipsoid.hit(&mut rng, &r, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.t - 3.5).abs() < 1e-9);
+    assert!(close(rec.p, Vec3::new(3.0, 0.0, -5.0)));
+    assert!(close(rec.normal, Vec3::new(1.0, 0.0, 0.0)));
+    assert!(rec.front_face);
+
+    // Off the axes the normal is not the transformed object normal: at
+    // (3 cos a, sin a) it is proportional to (cos a / 3, sin a).
+    let a = 0.6f64;
+    let target = Vec3::new(3.0 * a.cos(), a.sin(), -5.0);
+    let normal = Vec3::new(a.cos() / 3.0, a.sin(), 0.0).unit_vector();
+    let r = Ray::new(target + 2.0 * normal, -normal, 0.0);
+    let rec = ellipsoid.hit(&mut rng, &r, 0.001, f64::INFINITY).unwrap();
+    assert!(close(rec.p, target));
+    assert!(close(rec.normal, normal));
+
+    let bbox = ellipsoid.bounding_box(0.0, 1.0).unwrap();
+    assert!(close(bbox.min, Vec3::new(-3.0, -1.0, -6.0)));
+    assert!(close(bbox.max, Vec3::new(3.0, 1.0, -4.0)));
+}